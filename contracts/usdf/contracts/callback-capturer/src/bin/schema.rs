@@ -3,7 +3,8 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, export_schema_with_title, remove_schemas, schema_for};
 
-use callback_capturer::msg::{AdminResponse, ExecuteMsg, InstantiateMsg, QueryMsg, ResultResponse};
+use callback_capturer::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, ResultResponse};
+use simple_ica::admin::AdminsResponse;
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -13,7 +14,8 @@ fn main() {
 
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema_with_title(&schema_for!(ExecuteMsg), &out_dir, "ExecuteMsg");
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
     export_schema_with_title(&schema_for!(QueryMsg), &out_dir, "QueryMsg");
-    export_schema(&schema_for!(AdminResponse), &out_dir);
+    export_schema(&schema_for!(AdminsResponse), &out_dir);
     export_schema(&schema_for!(ResultResponse), &out_dir);
 }
@@ -6,11 +6,14 @@ use cosmwasm_std::{
 };
 
 use cw2::set_contract_version;
-use simple_ica::ReceiveIcaResponseMsg;
+use simple_ica::admin::{AdminExecuteMsg, AdminRole};
+use simple_ica::migrate::{is_older, previous_version, Version};
+use simple_ica::receiver::CallbackRouter;
+use simple_ica::{PacketKind, ReceiveIcaResponseMsg};
 
 use crate::error::ContractError;
-use crate::msg::{AdminResponse, ExecuteMsg, InstantiateMsg, QueryMsg, ResultResponse};
-use crate::state::{Config, CONFIG, RESULTS};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, ResultResponse};
+use crate::state::{Config, ADMINS, CONFIG, CONFIG_V0_2, RESULTS};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:callback-capturer";
@@ -24,14 +27,35 @@ pub fn instantiate(
     msg: InstantiateMsg,
 ) -> StdResult<Response> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    ADMINS.set(deps.storage, &info.sender, AdminRole::Owner)?;
     let cfg = Config {
-        admin: info.sender,
         simple_ica_controller: deps.api.addr_validate(&msg.simple_ica_controller)?,
     };
     CONFIG.save(deps.storage, &cfg)?;
     Ok(Response::default())
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let previous = previous_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    if is_older(previous.as_ref(), &Version::new(0, 3, 0)) {
+        // the admin becomes an owner
+        let old = CONFIG_V0_2.load(deps.storage)?;
+        ADMINS.set(deps.storage, &old.admin, AdminRole::Owner)?;
+        let cfg = Config {
+            simple_ica_controller: old.simple_ica_controller,
+        };
+        CONFIG.save(deps.storage, &cfg)?;
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let from_version = previous.map_or_else(|| "none".to_string(), |v| v.to_string());
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", from_version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -58,6 +82,8 @@ pub fn execute(
             transfer_channel_id,
         } => execute_send_funds(deps, env, info, ica_channel_id, transfer_channel_id),
         ExecuteMsg::ReceiveIcaResponse(resp) => execute_receive_ibc_response(deps, env, info, resp),
+        ExecuteMsg::Admin(msg) => Ok(ADMINS.execute(deps, &info, msg)?),
+        ExecuteMsg::AcceptControllerAdmin {} => execute_accept_controller_admin(deps, info),
     }
 }
 
@@ -69,15 +95,14 @@ pub fn execute_send_msgs(
     msgs: Vec<CosmosMsg<Empty>>,
     callback_id: String,
 ) -> Result<Response, ContractError> {
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
     let cfg = CONFIG.load(deps.storage)?;
-    if !cfg.admin.eq(&info.sender) {
-        return Err(ContractError::Unauthorized {});
-    }
 
     let ica_msg = simple_ica_controller::msg::ExecuteMsg::SendMsgs {
         channel_id,
         msgs,
         callback_id: Some(callback_id),
+        fee: None,
     };
     let msg = WasmMsg::Execute {
         contract_addr: cfg.simple_ica_controller.into(),
@@ -97,15 +122,14 @@ pub fn execute_ibc_query(
     msgs: Vec<QueryRequest<Empty>>,
     callback_id: String,
 ) -> Result<Response, ContractError> {
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
     let cfg = CONFIG.load(deps.storage)?;
-    if !cfg.admin.eq(&info.sender) {
-        return Err(ContractError::Unauthorized {});
-    }
 
     let ica_msg = simple_ica_controller::msg::ExecuteMsg::IbcQuery {
         channel_id,
         msgs,
         callback_id: Some(callback_id),
+        fee: None,
     };
     let msg = WasmMsg::Execute {
         contract_addr: cfg.simple_ica_controller.into(),
@@ -123,10 +147,8 @@ pub fn execute_check_remote_balance(
     info: MessageInfo,
     channel_id: String,
 ) -> Result<Response, ContractError> {
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
     let cfg = CONFIG.load(deps.storage)?;
-    if !cfg.admin.eq(&info.sender) {
-        return Err(ContractError::Unauthorized {});
-    }
 
     let ica_msg = simple_ica_controller::msg::ExecuteMsg::CheckRemoteBalance { channel_id };
     let msg = WasmMsg::Execute {
@@ -146,10 +168,8 @@ pub fn execute_send_funds(
     ica_channel_id: String,
     transfer_channel_id: String,
 ) -> Result<Response, ContractError> {
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
     let cfg = CONFIG.load(deps.storage)?;
-    if !cfg.admin.eq(&info.sender) {
-        return Err(ContractError::Unauthorized {});
-    }
 
     let ica_msg = simple_ica_controller::msg::ExecuteMsg::SendFunds {
         ica_channel_id,
@@ -165,41 +185,75 @@ pub fn execute_send_funds(
     Ok(res)
 }
 
+pub fn execute_accept_controller_admin(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    ADMINS.assert_owner(deps.storage, &info.sender)?;
+    let cfg = CONFIG.load(deps.storage)?;
+
+    let ica_msg = simple_ica_controller::msg::ExecuteMsg::Admin(AdminExecuteMsg::AcceptAdmin {});
+    let msg = WasmMsg::Execute {
+        contract_addr: cfg.simple_ica_controller.into(),
+        msg: to_binary(&ica_msg)?,
+        funds: vec![],
+    };
+
+    let res = Response::new().add_message(msg);
+    Ok(res)
+}
+
 pub fn execute_receive_ibc_response(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     resp: ReceiveIcaResponseMsg,
 ) -> Result<Response, ContractError> {
     // only the simple ica controller can send this message as callback
     let cfg = CONFIG.load(deps.storage)?;
-    if !cfg.simple_ica_controller.eq(&info.sender) {
-        return Err(ContractError::Unauthorized {});
-    }
+    CallbackRouter::new().route("", store_result).dispatch(
+        deps,
+        env,
+        &info,
+        &cfg.simple_ica_controller,
+        resp,
+    )
+}
 
+// we capture every callback, whatever its id
+fn store_result(
+    deps: DepsMut,
+    _env: Env,
+    resp: ReceiveIcaResponseMsg,
+) -> Result<Response, ContractError> {
     RESULTS.save(deps.storage, &resp.id, &resp.msg)?;
 
-    let res = Response::new()
+    let mut res = Response::new()
         .add_attribute("action", "receive_callback")
         .add_attribute("id", resp.id);
+    // controllers before callback version 2 do not tell which packet it was
+    if let Some(packet) = resp.packet {
+        let kind = match packet.kind {
+            PacketKind::Dispatch => "dispatch",
+            PacketKind::Query => "query",
+            PacketKind::Trade => "trade",
+            PacketKind::Timeout => "timeout",
+        };
+        res = res
+            .add_attribute("channel_id", packet.channel_id)
+            .add_attribute("kind", kind);
+    }
     Ok(res)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-        QueryMsg::Admin {} => to_binary(&query_admin(deps)?),
+        QueryMsg::Admins {} => to_binary(&ADMINS.query(deps.storage)?),
         QueryMsg::Result { id } => to_binary(&query_result(deps, id)?),
     }
 }
 
-pub fn query_admin(deps: Deps) -> StdResult<AdminResponse> {
-    let cfg = CONFIG.load(deps.storage)?;
-    Ok(AdminResponse {
-        admin: cfg.admin.into(),
-    })
-}
-
 pub fn query_result(deps: Deps, id: String) -> StdResult<ResultResponse> {
     let result = RESULTS.load(deps.storage, &id)?;
     Ok(ResultResponse { result })
@@ -209,8 +263,40 @@ pub fn query_result(deps: Deps, id: String) -> StdResult<ResultResponse> {
 mod tests {
     use super::*;
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coins, BankMsg, BankQuery, SubMsg, WasmMsg};
-    use simple_ica::{IbcQueryResponse, StdAck};
+    use cosmwasm_std::{coins, Addr, BankMsg, BankQuery, SubMsg, WasmMsg};
+    use simple_ica::testing::mock_query_callback;
+    use simple_ica::{IbcQueryResponse, SimpleIcaError, StdAck};
+
+    use crate::state::ConfigV0_2;
+
+    #[test]
+    fn migrate_state_of_v0_2() {
+        let mut deps = mock_dependencies();
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.2.0").unwrap();
+        let old = ConfigV0_2 {
+            admin: Addr::unchecked("alice"),
+            simple_ica_controller: Addr::unchecked("simple_ica"),
+        };
+        CONFIG_V0_2.save(&mut deps.storage, &old).unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(res.attributes[1].value, "0.2.0");
+        let cfg = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(cfg.simple_ica_controller.as_str(), "simple_ica");
+        let admins = ADMINS.query(&deps.storage).unwrap();
+        assert_eq!(admins.admins.len(), 1);
+        assert_eq!(admins.admins[0].address, "alice");
+        assert_eq!(admins.admins[0].role, AdminRole::Owner);
+
+        // the admin keeps using the contract, and migrating again changes nothing
+        let msg = ExecuteMsg::CheckRemoteBalance {
+            channel_id: "channel-1".into(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(res.attributes[1].value, CONTRACT_VERSION);
+        assert_eq!(ADMINS.query(&deps.storage).unwrap(), admins);
+    }
 
     #[test]
     fn send_message_enforces_permissions() {
@@ -244,7 +330,7 @@ mod tests {
         // bob cannot execute them
         let info = mock_info(bob, &[]);
         let err = execute(deps.as_mut(), mock_env(), info, execute_msg.clone()).unwrap_err();
-        assert_eq!(err, ContractError::Unauthorized {});
+        assert_eq!(err, SimpleIcaError::NotAdmin(bob.into()).into());
 
         // but alice can (original owner)
         let info = mock_info(alice, &[]);
@@ -255,6 +341,7 @@ mod tests {
                 channel_id: channel.to_string(),
                 msgs,
                 callback_id: Some("test".to_string()),
+                fee: None,
             })
             .unwrap(),
             funds: vec![],
@@ -300,6 +387,7 @@ mod tests {
                 channel_id: channel.to_string(),
                 msgs: queries,
                 callback_id: Some(callback.to_string()),
+                fee: None,
             })
             .unwrap(),
             funds: vec![],
@@ -314,11 +402,18 @@ mod tests {
             .unwrap(),
         );
         let info = mock_info(ica, &[]);
-        let msg = ExecuteMsg::ReceiveIcaResponse(ReceiveIcaResponseMsg {
-            id: callback.to_string(),
-            msg: ack.clone(),
-        });
-        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = ExecuteMsg::ReceiveIcaResponse(mock_query_callback(callback, vec![b"{}".into()]));
+        // which only the controller may send
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("someone", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, SimpleIcaError::NotController("someone".into()).into());
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(res.attributes[3], ("kind", "query"));
 
         // now make sure we can query this
         let data = query_result(deps.as_ref(), callback.to_string()).unwrap();
@@ -1,4 +1,5 @@
 use cosmwasm_std::StdError;
+use simple_ica::SimpleIcaError;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -6,6 +7,6 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
-    #[error("Unauthorized")]
-    Unauthorized {},
+    #[error("{0}")]
+    SimpleIca(#[from] SimpleIcaError),
 }
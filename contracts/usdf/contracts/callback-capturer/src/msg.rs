@@ -2,6 +2,7 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{CosmosMsg, Empty, QueryRequest};
+use simple_ica::admin::AdminExecuteMsg;
 use simple_ica::{ReceiveIcaResponseMsg, StdAck};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -9,6 +10,9 @@ pub struct InstantiateMsg {
     pub simple_ica_controller: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
@@ -40,24 +44,24 @@ pub enum ExecuteMsg {
         transfer_channel_id: String,
     },
     ReceiveIcaResponse(ReceiveIcaResponseMsg),
+    /// Manages the admins. Owners change the admins, operators may only send packets.
+    Admin(AdminExecuteMsg),
+    /// Takes the admin role the controller proposed to this contract, so it can send
+    /// packets through it (owners only)
+    AcceptControllerAdmin {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    /// Shows admin
-    Admin {},
+    /// Shows the admins and the roles proposed to others
+    Admins {},
     // Get result for the given callback id
     Result {
         id: String,
     },
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub struct AdminResponse {
-    pub admin: String,
-}
-
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct ResultResponse {
     pub result: StdAck,
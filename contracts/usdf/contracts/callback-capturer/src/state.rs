@@ -3,13 +3,22 @@ use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::Addr;
 use cw_storage_plus::{Item, Map};
+use simple_ica::admin::Admins;
 use simple_ica::StdAck;
 
+pub const ADMINS: Admins = Admins::new("admins", "admin_proposals");
 pub const CONFIG: Item<Config> = Item::new("config");
 pub const RESULTS: Map<&str, StdAck> = Map::new("results");
+/// The config up to 0.2, with the only admin
+pub const CONFIG_V0_2: Item<ConfigV0_2> = Item::new("config");
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct Config {
+    pub simple_ica_controller: Addr,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ConfigV0_2 {
     pub admin: Addr,
     pub simple_ica_controller: Addr,
 }
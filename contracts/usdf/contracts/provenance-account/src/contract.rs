@@ -0,0 +1,124 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+
+use cw1::CanExecuteResponse;
+use cw1_whitelist::contract::{
+    execute_execute, execute_freeze, execute_update_admins, query_admin_list,
+};
+use cw1_whitelist::state::ADMIN_LIST;
+use cw1_whitelist::ContractError;
+use cw2::set_contract_version;
+use provwasm_std::ProvenanceMsg;
+
+use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+
+// version info for migration info
+const CONTRACT_NAME: &str = "crates.io:provenance-account";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> StdResult<Response<ProvenanceMsg>> {
+    cw1_whitelist::contract::instantiate(deps.branch(), env, info, msg)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response<ProvenanceMsg>, ContractError> {
+    match msg {
+        ExecuteMsg::Execute { msgs } => execute_execute(deps, env, info, msgs),
+        ExecuteMsg::Freeze {} => with_provenance(execute_freeze(deps, env, info)?),
+        ExecuteMsg::UpdateAdmins { admins } => {
+            with_provenance(execute_update_admins(deps, env, info, admins)?)
+        }
+    }
+}
+
+// the admin changes send no messages, only their attributes are kept
+fn with_provenance(res: Response) -> Result<Response<ProvenanceMsg>, ContractError> {
+    Ok(Response::new().add_attributes(res.attributes))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::AdminList {} => to_binary(&query_admin_list(deps)?),
+        QueryMsg::CanExecute { sender, .. } => to_binary(&query_can_execute(deps, sender)?),
+    }
+}
+
+// like cw1-whitelist, any message an admin sends can be executed
+fn query_can_execute(deps: Deps, sender: String) -> StdResult<CanExecuteResponse> {
+    let cfg = ADMIN_LIST.load(deps.storage)?;
+    Ok(CanExecuteResponse {
+        can_execute: cfg.is_admin(&sender),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coins, from_binary, Addr, BankMsg, CosmosMsg};
+    use provwasm_std::{bind_name, NameBinding};
+
+    #[test]
+    fn admins_execute_provenance_msgs() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            admins: vec!["host".into()],
+            mutable: false,
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("host", &[]), msg).unwrap();
+
+        // plain messages come as they would to cw1-whitelist, Provenance ones along with them
+        let bind: CosmosMsg<ProvenanceMsg> = bind_name(
+            "acct.pb",
+            Addr::unchecked("account"),
+            NameBinding::Restricted,
+        )
+        .unwrap();
+        let send: CosmosMsg<ProvenanceMsg> = BankMsg::Send {
+            to_address: "bob".into(),
+            amount: coins(5, "nhash"),
+        }
+        .into();
+        let msg = ExecuteMsg::Execute {
+            msgs: vec![bind.clone(), send.clone()],
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("someone", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let res = execute(deps.as_mut(), mock_env(), mock_info("host", &[]), msg).unwrap();
+        let msgs: Vec<_> = res.messages.into_iter().map(|sub| sub.msg).collect();
+        assert_eq!(msgs, vec![bind, send]);
+
+        let query_msg = QueryMsg::CanExecute {
+            sender: "host".into(),
+            msg: BankMsg::Send {
+                to_address: "bob".into(),
+                amount: coins(5, "nhash"),
+            }
+            .into(),
+        };
+        let res: CanExecuteResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert!(res.can_execute);
+    }
+}
@@ -0,0 +1,7 @@
+//! The accounts of a simple-ica host on Provenance. It is cw1-whitelist with `ProvenanceMsg`
+//! as its custom message, so the accounts run Provenance messages as well as plain ones.
+
+pub mod contract;
+pub mod msg;
+
+pub use cw1_whitelist::ContractError;
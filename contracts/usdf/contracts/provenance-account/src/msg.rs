@@ -0,0 +1,6 @@
+use provwasm_std::ProvenanceMsg;
+
+pub use cw1_whitelist::msg::{AdminListResponse, InstantiateMsg};
+
+pub type ExecuteMsg = cw1_whitelist::msg::ExecuteMsg<ProvenanceMsg>;
+pub type QueryMsg = cw1_whitelist::msg::QueryMsg<ProvenanceMsg>;
@@ -3,8 +3,10 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
+use simple_ica::admin::AdminsResponse;
 use simple_ica_controller::msg::{
-    AccountResponse, AdminResponse, ExecuteMsg, InstantiateMsg, ListAccountsResponse, QueryMsg,
+    AccountResponse, ExecuteMsg, InstantiateMsg, ListAccountsResponse, MigrateMsg, QueryMsg,
+    SudoMsg,
 };
 
 fn main() {
@@ -16,7 +18,9 @@ fn main() {
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
-    export_schema(&schema_for!(AdminResponse), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
+    export_schema(&schema_for!(SudoMsg), &out_dir);
+    export_schema(&schema_for!(AdminsResponse), &out_dir);
     export_schema(&schema_for!(AccountResponse), &out_dir);
     export_schema(&schema_for!(ListAccountsResponse), &out_dir);
 }
@@ -0,0 +1,120 @@
+use cosmwasm_std::{
+    to_binary, Attribute, DepsMut, IbcMsg, Reply, Response, StdError, StdResult, Storage, SubMsg,
+};
+
+use simple_ica::{
+    BroadcastResponse, BroadcastResult, ReceiveIcaResponseMsg, StdAck, CALLBACK_VERSION,
+};
+
+use crate::callback::callback_msg;
+use crate::error::ContractError;
+use crate::fee::learn_sequence;
+use crate::state::{BROADCASTS, BROADCASTS_AWAITING_SEQUENCE, BROADCAST_PACKETS};
+
+pub const BROADCAST_REPLY_ID: u64 = 3;
+
+/// Sends a broadcast packet on a channel whose sequence we do not know, so the reply tells it
+pub fn untracked_broadcast_packet(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    batch_id: u64,
+    msg: IbcMsg,
+) -> StdResult<SubMsg> {
+    let mut awaiting = BROADCASTS_AWAITING_SEQUENCE
+        .may_load(storage)?
+        .unwrap_or_default();
+    awaiting.push((channel_id.to_string(), batch_id));
+    BROADCASTS_AWAITING_SEQUENCE.save(storage, &awaiting)?;
+    Ok(SubMsg::reply_on_success(msg, BROADCAST_REPLY_ID))
+}
+
+/// Replies come in the order of the packets, so the first one waiting gets the sequence.
+/// The channel counts its packets from then on.
+pub fn reply_broadcast(deps: DepsMut, reply: Reply) -> Result<Response, ContractError> {
+    let response = reply.result.into_result().map_err(StdError::generic_err)?;
+    let mut awaiting = BROADCASTS_AWAITING_SEQUENCE
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    if awaiting.is_empty() {
+        return Err(StdError::generic_err("no broadcast awaiting its sequence").into());
+    }
+    let (channel_id, batch_id) = awaiting.remove(0);
+    BROADCASTS_AWAITING_SEQUENCE.save(deps.storage, &awaiting)?;
+    let sequence = learn_sequence(deps.storage, &channel_id, &response.events)?;
+
+    BROADCAST_PACKETS.save(deps.storage, (&channel_id, sequence), &batch_id)?;
+    let mut broadcast = BROADCASTS.load(deps.storage, batch_id)?;
+    if let Some(packet) = broadcast
+        .packets
+        .iter_mut()
+        .find(|packet| packet.channel_id == channel_id)
+    {
+        packet.sequence = sequence;
+    }
+    BROADCASTS.save(deps.storage, batch_id, &broadcast)?;
+
+    Ok(Response::new()
+        .add_attribute("broadcast_channel_id", channel_id)
+        .add_attribute("broadcast_sequence", sequence.to_string()))
+}
+
+/// Records the result of a packet sent by `BroadcastMsgs`, returning None if it was not.
+/// Once all packets of the broadcast are done, the sender gets one callback with all results.
+pub fn record_broadcast_result(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    sequence: u64,
+    result: StdAck,
+) -> StdResult<Option<(Vec<Attribute>, Option<SubMsg>)>> {
+    let batch_id = match BROADCAST_PACKETS.may_load(storage, (channel_id, sequence))? {
+        Some(batch_id) => batch_id,
+        None => return Ok(None),
+    };
+    BROADCAST_PACKETS.remove(storage, (channel_id, sequence));
+
+    let mut broadcast = BROADCASTS.load(storage, batch_id)?;
+    if let Some(packet) = broadcast
+        .packets
+        .iter_mut()
+        .find(|packet| packet.channel_id == channel_id && packet.sequence == sequence)
+    {
+        packet.result = Some(result);
+    }
+    let mut attributes = vec![Attribute::new("batch_id", batch_id.to_string())];
+    if broadcast
+        .packets
+        .iter()
+        .any(|packet| packet.result.is_none())
+    {
+        BROADCASTS.save(storage, batch_id, &broadcast)?;
+        return Ok(Some((attributes, None)));
+    }
+    BROADCASTS.remove(storage, batch_id);
+    attributes.push(Attribute::new("broadcast", "done"));
+
+    let id = match broadcast.callback_id {
+        Some(id) => id,
+        None => return Ok(Some((attributes, None))),
+    };
+    let results = broadcast
+        .packets
+        .into_iter()
+        .filter_map(|packet| {
+            packet.result.map(|result| BroadcastResult {
+                channel_id: packet.channel_id,
+                sequence: packet.sequence,
+                result,
+            })
+        })
+        .collect();
+    // the results name their packets, so the callback is not about one of them
+    let callback = ReceiveIcaResponseMsg {
+        id: id.clone(),
+        msg: StdAck::Result(to_binary(&BroadcastResponse { results })?),
+        version: CALLBACK_VERSION,
+        packet: None,
+    };
+    attributes.push(Attribute::new("callback_id", id));
+    let msg = callback_msg(storage, broadcast.sender.into(), callback)?;
+    Ok(Some((attributes, Some(msg))))
+}
@@ -0,0 +1,84 @@
+use cosmwasm_std::{
+    DepsMut, Env, Reply, ReplyOn, Response, StdError, StdResult, Storage, SubMsg, SubMsgResult,
+};
+
+use simple_ica::{CallbackPacket, PacketKind, ReceiveIcaResponseMsg};
+
+use crate::error::ContractError;
+use crate::state::{
+    FailedCallback, PendingCallback, ACCOUNTS, CALLBACKS_IN_FLIGHT, CONFIG, FAILED_CALLBACKS,
+    NEXT_FAILURE_ID,
+};
+
+pub const CALLBACK_REPLY_ID: u64 = 2;
+
+/// Sends the result of a packet to the contract that asked for it, with the configured gas limit.
+/// If the receiver fails or runs out of gas, the ack still goes through, and the callback is
+/// kept for the receiver to retry.
+pub fn callback_msg(
+    storage: &mut dyn Storage,
+    receiver: String,
+    callback: ReceiveIcaResponseMsg,
+) -> StdResult<SubMsg> {
+    let cfg = CONFIG.load(storage)?;
+    let msg = callback.clone().into_cosmos_msg(&receiver)?;
+    let mut in_flight = CALLBACKS_IN_FLIGHT.may_load(storage)?.unwrap_or_default();
+    in_flight.push(PendingCallback { receiver, callback });
+    CALLBACKS_IN_FLIGHT.save(storage, &in_flight)?;
+    Ok(SubMsg {
+        id: CALLBACK_REPLY_ID,
+        msg,
+        gas_limit: Some(cfg.callback_gas_limit),
+        reply_on: ReplyOn::Always,
+    })
+}
+
+/// Describes a packet of the channel for its callback
+pub fn callback_packet(
+    storage: &dyn Storage,
+    channel_id: &str,
+    sequence: Option<u64>,
+    kind: PacketKind,
+) -> StdResult<CallbackPacket> {
+    let remote_account = ACCOUNTS
+        .may_load(storage, channel_id)?
+        .and_then(|account| account.remote_addr);
+    Ok(CallbackPacket {
+        channel_id: channel_id.to_string(),
+        sequence,
+        kind,
+        remote_account,
+    })
+}
+
+/// Called for every callback, in the order they were sent. A failed callback reverted what the
+/// receiver did, and is kept for retry.
+pub fn reply_callback(deps: DepsMut, env: Env, reply: Reply) -> Result<Response, ContractError> {
+    let mut in_flight = CALLBACKS_IN_FLIGHT
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    if in_flight.is_empty() {
+        return Err(StdError::generic_err("no callback in flight").into());
+    }
+    let pending = in_flight.remove(0);
+    CALLBACKS_IN_FLIGHT.save(deps.storage, &in_flight)?;
+    let error = match reply.result {
+        SubMsgResult::Err(error) => error,
+        SubMsgResult::Ok(_) => return Ok(Response::new()),
+    };
+
+    let failure_id = NEXT_FAILURE_ID.may_load(deps.storage)?.unwrap_or(1);
+    NEXT_FAILURE_ID.save(deps.storage, &(failure_id + 1))?;
+    let failed = FailedCallback {
+        callback: pending.callback,
+        error: error.clone(),
+        failed_time: env.block.time,
+    };
+    FAILED_CALLBACKS.save(deps.storage, (&pending.receiver, failure_id), &failed)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "callback_failed")
+        .add_attribute("receiver", pending.receiver)
+        .add_attribute("failure_id", failure_id.to_string())
+        .add_attribute("error", error))
+}
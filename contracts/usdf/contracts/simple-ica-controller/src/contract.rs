@@ -1,72 +1,293 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, CosmosMsg, Deps, DepsMut, Empty, Env, IbcMsg, MessageInfo, Order, QueryRequest,
-    QueryResponse, Response, StdError, StdResult,
+    to_binary, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Empty, Env, IbcMsg, MessageInfo,
+    Order, QueryRequest, QueryResponse, Reply, Response, StdError, StdResult, Storage, Timestamp,
 };
+use cw2::set_contract_version;
+use cw_storage_plus::Bound;
 
-use simple_ica::PacketMsg;
+use simple_ica::admin::AdminRole;
+use simple_ica::fee::{add_coins, IbcFee};
+use simple_ica::migrate::previous_version;
+use simple_ica::{IcaVersion, PacketMsg};
 
+use crate::broadcast::{reply_broadcast, untracked_broadcast_packet, BROADCAST_REPLY_ID};
+use crate::callback::{reply_callback, CALLBACK_REPLY_ID};
+use crate::error::ContractError;
+use crate::fee::{format_coins, next_sequence, prepare_packet, reply_sequence, SEQUENCE_REPLY_ID};
 use crate::ibc::PACKET_LIFETIME;
+use crate::migrate::{migrate_state, sequence_probes};
 use crate::msg::{
-    AccountInfo, AccountResponse, AdminResponse, ExecuteMsg, InstantiateMsg, LatestQueryResponse,
-    ListAccountsResponse, QueryMsg,
+    AccountInfo, AccountResponse, BalanceHistoryResponse, BalanceSnapshot, BroadcastPacketInfo,
+    BroadcastStatusResponse, ChannelStatsResponse, ChannelStatus, ClosedAccountInfo, ExecuteMsg,
+    FailedCallbackInfo, FailedCallbacksResponse, FeeRefundResponse, FinishedJobInfo,
+    FinishedJobsResponse, InstantiateMsg, LatestQueryResponse, ListAccountsResponse,
+    ListClosedAccountsResponse, ListPacketFeesResponse, ListQueryResultsResponse, MigrateMsg,
+    PacketFeeInfo, PacketFeeResponse, PendingTransferInfo, PendingTransfersResponse, QueryMsg,
+    QueryResultResponse, ScheduledJobInfo, SudoMsg, TransferRefundResponse, UpcomingJobsResponse,
 };
-use crate::state::{Config, ACCOUNTS, CONFIG, LATEST_QUERIES};
+use crate::provenance::ProvenanceQueryMsg;
+use crate::schedule::{execute_cancel_job, execute_crank, execute_schedule_msgs};
+use crate::state::{
+    AccountData, Broadcast, BroadcastPacket, Config, FundsAndMsgs, QueryResult, ACCOUNTS, ADMINS,
+    BALANCE_HISTORY, BROADCASTS, BROADCAST_PACKETS, CHANNEL_STATS, CHANNEL_VERSIONS,
+    CLOSED_ACCOUNTS, CONFIG, DEFAULT_BALANCE_REFRESH_INTERVAL, DEFAULT_CALLBACK_GAS_LIMIT,
+    DEFAULT_QUERY_RESULT_TTL, FAILED_CALLBACKS, FEE_ESCROWS, FEE_REFUNDS, FINISHED_JOBS,
+    FUNDS_AND_MSGS, JOB_QUEUE, LAST_BALANCE_REFRESH, LATEST_QUERIES, NEXT_BATCH_ID,
+    NEXT_REQUEST_ID, PENDING_QUERIES, PENDING_TRANSFERS, QUERY_RESULTS, SCHEDULED_JOBS,
+    TRANSFERS_BY_SENDER, TRANSFER_REFUNDS,
+};
+use crate::trade::execute_remote_trade;
+use crate::transfer::{reply_transfer, tracked_transfers, transfer_complete, TRANSFER_REPLY_ID};
+
+// version info for migration info
+const CONTRACT_NAME: &str = "crates.io:simple-ica-controller";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// pagination of query results and balance history
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+// how many expired query results are removed at once
+const PRUNE_LIMIT: usize = 10;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    _msg: InstantiateMsg,
+    msg: InstantiateMsg,
 ) -> StdResult<Response> {
-    let cfg = Config { admin: info.sender };
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    ADMINS.set(deps.storage, &info.sender, AdminRole::Owner)?;
+    let cfg = Config {
+        query_result_ttl: msg.query_result_ttl.unwrap_or(DEFAULT_QUERY_RESULT_TTL),
+        balance_refresh_interval: msg
+            .balance_refresh_interval
+            .unwrap_or(DEFAULT_BALANCE_REFRESH_INTERVAL),
+        callback_gas_limit: msg.callback_gas_limit.unwrap_or(DEFAULT_CALLBACK_GAS_LIMIT),
+        ibc_hooks: msg.ibc_hooks.unwrap_or_default(),
+        crank_reward: msg.crank_reward.unwrap_or_default(),
+    };
     CONFIG.save(deps.storage, &cfg)?;
 
     Ok(Response::new().add_attribute("action", "instantiate"))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let previous = previous_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    migrate_state(deps.storage, previous.as_ref())?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    let probes = sequence_probes(deps.storage, &env)?;
+
+    let from_version = previous.map_or_else(|| "none".to_string(), |v| v.to_string());
+    Ok(Response::new()
+        .add_submessages(probes)
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", from_version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::UpdateAdmin { admin } => execute_update_admin(deps, info, admin),
+        ExecuteMsg::Admin(msg) => Ok(ADMINS.execute(deps, &info, msg)?),
         ExecuteMsg::SendMsgs {
             channel_id,
             msgs,
             callback_id,
-        } => execute_send_msgs(deps, env, info, channel_id, msgs, callback_id),
+            fee,
+        } => execute_send_msgs(deps, env, info, channel_id, msgs, callback_id, fee),
+        ExecuteMsg::SendProvenanceMsgs {
+            channel_id,
+            msgs,
+            callback_id,
+            fee,
+        } => execute_send_provenance_msgs(deps, env, info, channel_id, msgs, callback_id, fee),
         ExecuteMsg::CheckRemoteBalance { channel_id } => {
             execute_check_remote_balance(deps, env, info, channel_id)
         }
+        ExecuteMsg::ClaimOrphaned {
+            channel_id,
+            orphaned_channel_id,
+        } => execute_claim_orphaned(deps, env, info, channel_id, orphaned_channel_id),
         ExecuteMsg::IbcQuery {
             channel_id,
             msgs,
             callback_id,
-        } => execute_ibc_query(deps, env, info, channel_id, msgs, callback_id),
+            fee,
+        } => execute_ibc_query(deps, env, info, channel_id, msgs, callback_id, fee),
+        ExecuteMsg::ProvenanceQuery {
+            channel_id,
+            msgs,
+            callback_id,
+            fee,
+        } => execute_provenance_query(deps, env, info, channel_id, msgs, callback_id, fee),
         ExecuteMsg::SendFunds {
             ica_channel_id,
             transfer_channel_id,
         } => execute_send_funds(deps, env, info, ica_channel_id, transfer_channel_id),
+        ExecuteMsg::SendFundsAndMsgs {
+            ica_channel_id,
+            transfer_channel_id,
+            msgs,
+            callback_id,
+        } => execute_send_funds_and_msgs(
+            deps,
+            env,
+            info,
+            ica_channel_id,
+            transfer_channel_id,
+            msgs,
+            callback_id,
+        ),
+        ExecuteMsg::RemoteTrade {
+            ica_channel_id,
+            transfer_channel_id,
+            trade,
+            callback_id,
+        } => execute_remote_trade(
+            deps,
+            env,
+            info,
+            ica_channel_id,
+            transfer_channel_id,
+            trade,
+            callback_id,
+        ),
+        ExecuteMsg::BroadcastMsgs {
+            channel_ids,
+            msgs,
+            callback_id,
+        } => execute_broadcast_msgs(deps, env, info, channel_ids, msgs, callback_id),
+        ExecuteMsg::ScheduleMsgs {
+            channel_id,
+            msgs,
+            callback_id,
+            schedule,
+        } => execute_schedule_msgs(deps, info, channel_id, msgs, callback_id, schedule),
+        ExecuteMsg::Crank {} => execute_crank(deps, env, info),
+        ExecuteMsg::CancelJob { job_id } => execute_cancel_job(deps, env, info, job_id),
+        ExecuteMsg::ClaimFeeRefund {} => execute_claim_fee_refund(deps, info),
+        ExecuteMsg::ClaimRefund {} => execute_claim_refund(deps, info),
+        ExecuteMsg::RetryCallback { failure_id } => execute_retry_callback(deps, info, failure_id),
+        ExecuteMsg::UpdateCallbackGasLimit { gas_limit } => {
+            execute_update_callback_gas_limit(deps, info, gas_limit)
+        }
+        ExecuteMsg::UpdateCrankReward { reward } => execute_update_crank_reward(deps, info, reward),
+        ExecuteMsg::UpdateQueryResultTtl { ttl } => {
+            execute_update_query_result_ttl(deps, info, ttl)
+        }
+        ExecuteMsg::UpdateBalanceRefreshInterval { interval } => {
+            execute_update_balance_refresh_interval(deps, info, interval)
+        }
+        ExecuteMsg::UpdateIbcHooks { enabled } => execute_update_ibc_hooks(deps, info, enabled),
+        ExecuteMsg::RefreshBalances {} => execute_refresh_balances(deps, env),
     }
 }
 
-pub fn execute_update_admin(
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, reply: Reply) -> Result<Response, ContractError> {
+    match reply.id {
+        TRANSFER_REPLY_ID => reply_transfer(deps, reply),
+        CALLBACK_REPLY_ID => reply_callback(deps, env, reply),
+        BROADCAST_REPLY_ID => reply_broadcast(deps, reply),
+        SEQUENCE_REPLY_ID => reply_sequence(deps, reply),
+        _ => Err(ContractError::InvalidReplyId),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::IbcLifecycleComplete(callback) => transfer_complete(deps, env, callback),
+    }
+}
+
+pub fn execute_update_query_result_ttl(
     deps: DepsMut,
     info: MessageInfo,
-    new_admin: String,
-) -> StdResult<Response> {
+    ttl: u64,
+) -> Result<Response, ContractError> {
     // auth check
+    ADMINS.assert_owner(deps.storage, &info.sender)?;
     let mut cfg = CONFIG.load(deps.storage)?;
-    if info.sender != cfg.admin {
-        return Err(StdError::generic_err("Only admin may set new admin"));
-    }
-    cfg.admin = deps.api.addr_validate(&new_admin)?;
+    cfg.query_result_ttl = ttl;
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "handle_update_query_result_ttl")
+        .add_attribute("ttl", ttl.to_string()))
+}
+
+pub fn execute_update_callback_gas_limit(
+    deps: DepsMut,
+    info: MessageInfo,
+    gas_limit: u64,
+) -> Result<Response, ContractError> {
+    // auth check
+    ADMINS.assert_owner(deps.storage, &info.sender)?;
+    let mut cfg = CONFIG.load(deps.storage)?;
+    cfg.callback_gas_limit = gas_limit;
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "handle_update_callback_gas_limit")
+        .add_attribute("gas_limit", gas_limit.to_string()))
+}
+
+pub fn execute_update_crank_reward(
+    deps: DepsMut,
+    info: MessageInfo,
+    reward: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    // auth check
+    ADMINS.assert_owner(deps.storage, &info.sender)?;
+    let mut cfg = CONFIG.load(deps.storage)?;
+    cfg.crank_reward = add_coins(&reward, &[]);
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "handle_update_crank_reward")
+        .add_attribute("reward", format_coins(&cfg.crank_reward)))
+}
+
+pub fn execute_update_balance_refresh_interval(
+    deps: DepsMut,
+    info: MessageInfo,
+    interval: u64,
+) -> Result<Response, ContractError> {
+    // auth check
+    ADMINS.assert_owner(deps.storage, &info.sender)?;
+    let mut cfg = CONFIG.load(deps.storage)?;
+    cfg.balance_refresh_interval = interval;
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "handle_update_balance_refresh_interval")
+        .add_attribute("interval", interval.to_string()))
+}
+
+pub fn execute_update_ibc_hooks(
+    deps: DepsMut,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    // auth check
+    ADMINS.assert_owner(deps.storage, &info.sender)?;
+    let mut cfg = CONFIG.load(deps.storage)?;
+    cfg.ibc_hooks = enabled;
     CONFIG.save(deps.storage, &cfg)?;
 
     Ok(Response::new()
-        .add_attribute("action", "handle_update_admin")
-        .add_attribute("new_admin", cfg.admin))
+        .add_attribute("action", "handle_update_ibc_hooks")
+        .add_attribute("enabled", enabled.to_string()))
 }
 
 pub fn execute_send_msgs(
@@ -76,146 +297,572 @@ pub fn execute_send_msgs(
     channel_id: String,
     msgs: Vec<CosmosMsg>,
     callback_id: Option<String>,
-) -> StdResult<Response> {
+    fee: Option<IbcFee>,
+) -> Result<Response, ContractError> {
     // auth check
-    let cfg = CONFIG.load(deps.storage)?;
-    if info.sender != cfg.admin {
-        return Err(StdError::generic_err("Only admin may send messages"));
-    }
-    // ensure the channel exists (not found if not registered)
-    ACCOUNTS.load(deps.storage, &channel_id)?;
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
+    // ensure the channel exists (not found if not registered) and is open
+    open_account(deps.storage, &channel_id)?;
 
-    // construct a packet to send
-    let sender = info.sender.into();
-    let packet = PacketMsg::Dispatch {
-        sender,
+    let data = dispatch_data(
+        deps.storage,
+        &channel_id,
+        info.sender.to_string(),
         msgs,
         callback_id,
-    };
+    )?;
+    let (_, res) = prepare_packet(deps.storage, &env, &info, &channel_id, fee)?;
     let msg = IbcMsg::SendPacket {
         channel_id,
-        data: to_binary(&packet)?,
+        data,
         timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
     };
 
-    let res = Response::new()
+    let res = res
         .add_message(msg)
         .add_attribute("action", "handle_send_msgs");
     Ok(res)
 }
 
+pub fn execute_send_provenance_msgs(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    channel_id: String,
+    msgs: Vec<Binary>,
+    callback_id: Option<String>,
+    fee: Option<IbcFee>,
+) -> Result<Response, ContractError> {
+    // auth check
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
+    open_account(deps.storage, &channel_id)?;
+    let version = load_version(deps.storage, &channel_id)?;
+
+    // the host checks the messages, as we do not know the Provenance types
+    let data = version.encode_packet(&PacketMsg::ProvenanceDispatch {
+        sender: info.sender.to_string(),
+        msgs,
+        callback_id,
+    })?;
+    let (_, res) = prepare_packet(deps.storage, &env, &info, &channel_id, fee)?;
+    let msg = IbcMsg::SendPacket {
+        channel_id,
+        data,
+        timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
+    };
+
+    let res = res
+        .add_message(msg)
+        .add_attribute("action", "handle_send_provenance_msgs");
+    Ok(res)
+}
+
+pub fn execute_broadcast_msgs(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    channel_ids: Vec<String>,
+    msgs: Vec<CosmosMsg>,
+    callback_id: Option<String>,
+) -> Result<Response, ContractError> {
+    // auth check
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
+    if channel_ids.is_empty() {
+        return Err(StdError::generic_err("No channels to broadcast to").into());
+    }
+
+    let batch_id = NEXT_BATCH_ID.may_load(deps.storage)?.unwrap_or(1);
+    NEXT_BATCH_ID.save(deps.storage, &(batch_id + 1))?;
+    let mut packets: Vec<BroadcastPacket> = vec![];
+    let mut res = Response::new();
+    for channel_id in channel_ids {
+        // ensure the channel exists (not found if not registered) and is open
+        open_account(deps.storage, &channel_id)?;
+        if packets.iter().any(|packet| packet.channel_id == channel_id) {
+            return Err(
+                StdError::generic_err(format!("Channel {} is listed twice", channel_id)).into(),
+            );
+        }
+        // the packets have no callback of their own, the sender gets one for all of them
+        let data = dispatch_data(
+            deps.storage,
+            &channel_id,
+            info.sender.to_string(),
+            msgs.clone(),
+            None,
+        )?;
+        let msg = IbcMsg::SendPacket {
+            channel_id: channel_id.clone(),
+            data,
+            timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
+        };
+        // channels connected by older versions tell the sequence in the reply
+        let sequence = match next_sequence(deps.storage, &channel_id)? {
+            Some(sequence) => {
+                BROADCAST_PACKETS.save(deps.storage, (&channel_id, sequence), &batch_id)?;
+                res = res.add_message(msg);
+                sequence
+            }
+            None => {
+                let msg = untracked_broadcast_packet(deps.storage, &channel_id, batch_id, msg)?;
+                res = res.add_submessage(msg);
+                0
+            }
+        };
+        packets.push(BroadcastPacket {
+            channel_id,
+            sequence,
+            result: None,
+        });
+    }
+    let broadcast = Broadcast {
+        sender: info.sender,
+        callback_id,
+        packets,
+    };
+    BROADCASTS.save(deps.storage, batch_id, &broadcast)?;
+
+    let res = res
+        .add_attribute("action", "handle_broadcast_msgs")
+        .add_attribute("batch_id", batch_id.to_string())
+        .set_data(to_binary(&batch_id)?);
+    Ok(res)
+}
+
+/// Encodes the messages to dispatch in the version the channel speaks
+pub(crate) fn dispatch_data(
+    storage: &dyn Storage,
+    channel_id: &str,
+    sender: String,
+    msgs: Vec<CosmosMsg>,
+    callback_id: Option<String>,
+) -> Result<Binary, ContractError> {
+    let version = load_version(storage, channel_id)?;
+    let data = version.encode_packet(&PacketMsg::Dispatch {
+        sender,
+        msgs,
+        callback_id,
+    })?;
+    Ok(data)
+}
+
 pub fn execute_ibc_query(
-    _deps: DepsMut,
+    deps: DepsMut,
     env: Env,
     info: MessageInfo,
     channel_id: String,
     msgs: Vec<QueryRequest<Empty>>,
     callback_id: Option<String>,
-) -> StdResult<Response> {
-    // construct a packet to send
-    let sender = info.sender.into();
+    fee: Option<IbcFee>,
+) -> Result<Response, ContractError> {
     let packet = PacketMsg::IbcQuery {
-        sender,
+        sender: info.sender.to_string(),
         msgs,
-        callback_id,
+        callback_id: callback_id.clone(),
+    };
+    let res = send_query(deps, env, info, channel_id, packet, callback_id, fee)?;
+    Ok(res.add_attribute("action", "handle_check_remote_balance"))
+}
+
+pub fn execute_provenance_query(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    channel_id: String,
+    msgs: Vec<ProvenanceQueryMsg>,
+    callback_id: Option<String>,
+    fee: Option<IbcFee>,
+) -> Result<Response, ContractError> {
+    let packet = PacketMsg::ProvenanceQuery {
+        sender: info.sender.to_string(),
+        msgs: msgs
+            .iter()
+            .map(ProvenanceQueryMsg::to_request)
+            .collect::<StdResult<_>>()?,
+        callback_id: callback_id.clone(),
     };
+    let res = send_query(deps, env, info, channel_id, packet, callback_id, fee)?;
+    Ok(res.add_attribute("action", "handle_provenance_query"))
+}
+
+// sends a query packet, keeping its result under a new request id
+fn send_query(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    channel_id: String,
+    packet: PacketMsg,
+    callback_id: Option<String>,
+    fee: Option<IbcFee>,
+) -> Result<Response, ContractError> {
+    open_account(deps.storage, &channel_id)?;
+    let version = load_version(deps.storage, &channel_id)?;
+
+    let (sequence, mut res) = prepare_packet(deps.storage, &env, &info, &channel_id, fee)?;
+
+    // we can only match the ack to the request by the packet sequence,
+    // so results of channels connected by older versions are not kept
+    if let Some(sequence) = sequence {
+        let cfg = CONFIG.load(deps.storage)?;
+        prune_query_results(deps.storage, &env, cfg.query_result_ttl)?;
+
+        let request_id = NEXT_REQUEST_ID.may_load(deps.storage)?.unwrap_or(1);
+        NEXT_REQUEST_ID.save(deps.storage, &(request_id + 1))?;
+        let result = QueryResult {
+            channel_id: channel_id.clone(),
+            sender: info.sender,
+            callback_id,
+            sent_time: env.block.time,
+            response: None,
+            received_time: None,
+            timed_out: false,
+        };
+        QUERY_RESULTS.save(deps.storage, request_id, &result)?;
+        PENDING_QUERIES.save(deps.storage, (&channel_id, sequence), &request_id)?;
+        res = res
+            .add_attribute("request_id", request_id.to_string())
+            .set_data(to_binary(&request_id)?);
+    }
+
     let msg = IbcMsg::SendPacket {
         channel_id,
-        data: to_binary(&packet)?,
+        data: version.encode_packet(&packet)?,
         timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
     };
 
+    Ok(res.add_message(msg))
+}
+
+pub fn execute_check_remote_balance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    channel_id: String,
+) -> Result<Response, ContractError> {
+    // auth check
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
+    // ensure the channel exists (not found if not registered) and is open
+    open_account(deps.storage, &channel_id)?;
+    let version = load_version(deps.storage, &channel_id)?;
+    let msg = balances_packet(deps.storage, &env, channel_id, version)?;
+
     let res = Response::new()
         .add_message(msg)
         .add_attribute("action", "handle_check_remote_balance");
     Ok(res)
 }
 
-pub fn execute_check_remote_balance(
+pub fn execute_claim_orphaned(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     channel_id: String,
-) -> StdResult<Response> {
-    // auth check
-    let cfg = CONFIG.load(deps.storage)?;
-    if info.sender != cfg.admin {
-        return Err(StdError::generic_err("Only admin may send messages"));
-    }
-    // ensure the channel exists (not found if not registered)
-    ACCOUNTS.load(deps.storage, &channel_id)?;
+    orphaned_channel_id: String,
+) -> Result<Response, ContractError> {
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
+    open_account(deps.storage, &channel_id)?;
+    let version = load_version(deps.storage, &channel_id)?;
 
-    // construct a packet to send
-    let packet = PacketMsg::Balances {};
+    next_sequence(deps.storage, &channel_id)?;
+    let packet = PacketMsg::ClaimOrphaned {
+        channel_id: orphaned_channel_id,
+    };
     let msg = IbcMsg::SendPacket {
         channel_id,
-        data: to_binary(&packet)?,
+        data: version.encode_packet(&packet)?,
         timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
     };
 
     let res = Response::new()
         .add_message(msg)
-        .add_attribute("action", "handle_check_remote_balance");
+        .add_attribute("action", "handle_claim_orphaned");
     Ok(res)
 }
 
+pub fn execute_refresh_balances(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    // intentionally no auth check, but we limit how often this may be done
+    let cfg = CONFIG.load(deps.storage)?;
+    if let Some(last) = LAST_BALANCE_REFRESH.may_load(deps.storage)? {
+        let next = last.plus_seconds(cfg.balance_refresh_interval);
+        if env.block.time < next {
+            return Err(ContractError::RefreshTooSoon { next });
+        }
+    }
+    LAST_BALANCE_REFRESH.save(deps.storage, &env.block.time)?;
+
+    let channels = ACCOUNTS
+        .range(deps.storage, None, None, Order::Ascending)
+        .filter_map(|r| match r {
+            Ok((_, account)) if account.status == ChannelStatus::Closed => None,
+            r => Some(r.map(|(channel_id, _)| channel_id)),
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut res = Response::new().add_attribute("action", "handle_refresh_balances");
+    for channel_id in channels {
+        let version = load_version(deps.storage, &channel_id)?;
+        let msg = balances_packet(deps.storage, &env, channel_id, version)?;
+        res = res.add_message(msg);
+    }
+    Ok(res)
+}
+
+// construct a Balances packet to send
+pub(crate) fn balances_packet(
+    storage: &mut dyn Storage,
+    env: &Env,
+    channel_id: String,
+    version: IcaVersion,
+) -> StdResult<IbcMsg> {
+    next_sequence(storage, &channel_id)?;
+    let packet = PacketMsg::Balances {};
+    Ok(IbcMsg::SendPacket {
+        channel_id,
+        data: version.encode_packet(&packet)?,
+        timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
+    })
+}
+
 pub fn execute_send_funds(
     deps: DepsMut,
     env: Env,
-    mut info: MessageInfo,
+    info: MessageInfo,
     ica_channel_id: String,
     transfer_channel_id: String,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
     // intentionally no auth check
 
     // require some funds
-    let amount = match info.funds.pop() {
-        Some(coin) => coin,
-        None => {
-            return Err(StdError::generic_err(
-                "you must send the coins you wish to ibc transfer",
-            ))
-        }
-    };
-    // if there are any more coins, reject the message
-    if !info.funds.is_empty() {
-        return Err(StdError::generic_err("you can only ibc transfer one coin"));
+    if info.funds.is_empty() {
+        return Err(
+            StdError::generic_err("you must send the coins you wish to ibc transfer").into(),
+        );
     }
+    let remote_addr = remote_address(deps.storage, &ica_channel_id)?;
+    let res = Response::new().add_attribute("action", "handle_send_funds");
 
-    // load remote account
-    let data = ACCOUNTS.load(deps.storage, &ica_channel_id)?;
-    let remote_addr = match data.remote_addr {
-        Some(addr) => addr,
+    // without ibc-hooks we do not learn what happens to the transfers
+    if !CONFIG.load(deps.storage)?.ibc_hooks {
+        let timeout = env.block.time.plus_seconds(PACKET_LIFETIME);
+        let transfers = info.funds.into_iter().map(|amount| IbcMsg::Transfer {
+            channel_id: transfer_channel_id.clone(),
+            to_address: remote_addr.clone(),
+            amount,
+            timeout: timeout.into(),
+        });
+        return Ok(res.add_messages(transfers));
+    }
+
+    // we follow the transfers, so the sender can claim the funds if they fail
+    let transfers = tracked_transfers(
+        deps.storage,
+        &env,
+        &info.sender,
+        &transfer_channel_id,
+        &remote_addr,
+        info.funds,
+        None,
+    )?;
+    Ok(res.add_submessages(transfers))
+}
+
+pub fn execute_send_funds_and_msgs(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    ica_channel_id: String,
+    transfer_channel_id: String,
+    msgs: Vec<CosmosMsg>,
+    callback_id: Option<String>,
+) -> Result<Response, ContractError> {
+    // auth check
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
+    if info.funds.is_empty() {
+        return Err(
+            StdError::generic_err("you must send the coins you wish to ibc transfer").into(),
+        );
+    }
+    let remote_addr = remote_address(deps.storage, &ica_channel_id)?;
+
+    let batch_id = NEXT_BATCH_ID.may_load(deps.storage)?.unwrap_or(1);
+    NEXT_BATCH_ID.save(deps.storage, &(batch_id + 1))?;
+    let batch = FundsAndMsgs {
+        sender: info.sender.clone(),
+        ica_channel_id,
+        msgs,
+        trade: None,
+        callback_id,
+        pending: info.funds.len() as u32,
+        failed: vec![],
+    };
+    FUNDS_AND_MSGS.save(deps.storage, batch_id, &batch)?;
+
+    let transfers = tracked_transfers(
+        deps.storage,
+        &env,
+        &info.sender,
+        &transfer_channel_id,
+        &remote_addr,
+        info.funds,
+        Some(batch_id),
+    )?;
+    let res = Response::new()
+        .add_submessages(transfers)
+        .add_attribute("action", "handle_send_funds_and_msgs")
+        .add_attribute("batch_id", batch_id.to_string())
+        .set_data(to_binary(&batch_id)?);
+    Ok(res)
+}
+
+// the remote account of the channel, which must be known to send funds to it
+pub(crate) fn remote_address(
+    storage: &dyn Storage,
+    ica_channel_id: &str,
+) -> Result<String, ContractError> {
+    let data = open_account(storage, ica_channel_id)?;
+    match data.remote_addr {
+        Some(addr) => Ok(addr),
         None => {
-            return Err(StdError::generic_err(
-                "We don't have the remote address for this channel",
-            ))
+            Err(StdError::generic_err("We don't have the remote address for this channel").into())
         }
-    };
+    }
+}
+
+pub fn execute_claim_fee_refund(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let amount = FEE_REFUNDS
+        .may_load(deps.storage, &info.sender)?
+        .ok_or(ContractError::NoFeeRefund)?;
+    FEE_REFUNDS.remove(deps.storage, &info.sender);
 
-    // construct a packet to send
-    let msg = IbcMsg::Transfer {
-        channel_id: transfer_channel_id,
-        to_address: remote_addr,
+    let msg = BankMsg::Send {
+        to_address: info.sender.into(),
         amount,
-        timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
     };
+    let res = Response::new()
+        .add_message(msg)
+        .add_attribute("action", "handle_claim_fee_refund");
+    Ok(res)
+}
 
+pub fn execute_claim_refund(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let amount = TRANSFER_REFUNDS
+        .may_load(deps.storage, &info.sender)?
+        .ok_or(ContractError::NoTransferRefund)?;
+    TRANSFER_REFUNDS.remove(deps.storage, &info.sender);
+
+    let msg = BankMsg::Send {
+        to_address: info.sender.into(),
+        amount,
+    };
     let res = Response::new()
         .add_message(msg)
-        .add_attribute("action", "handle_send_funds");
+        .add_attribute("action", "handle_claim_refund");
     Ok(res)
 }
 
+/// Sends a failed callback again. It is only sent to the receiver, which pays the gas,
+/// so there is no gas limit and a failure reverts the retry.
+pub fn execute_retry_callback(
+    deps: DepsMut,
+    info: MessageInfo,
+    failure_id: u64,
+) -> Result<Response, ContractError> {
+    let key = (info.sender.as_str(), failure_id);
+    let failed = FAILED_CALLBACKS
+        .may_load(deps.storage, key)?
+        .ok_or(ContractError::UnknownFailedCallback(failure_id))?;
+    FAILED_CALLBACKS.remove(deps.storage, key);
+
+    let msg = failed.callback.into_cosmos_msg(info.sender.as_str())?;
+    Ok(Response::new()
+        .add_message(msg)
+        .add_attribute("action", "handle_retry_callback")
+        .add_attribute("failure_id", failure_id.to_string()))
+}
+
+/// Removes the oldest query results once they are older than the ttl.
+/// This is done a few at a time, so it is cheap enough to do on every query.
+pub(crate) fn prune_query_results(storage: &mut dyn Storage, env: &Env, ttl: u64) -> StdResult<()> {
+    let expired = QUERY_RESULTS
+        .range(storage, None, None, Order::Ascending)
+        .take(PRUNE_LIMIT)
+        .take_while(|r| match r {
+            Ok((_, result)) => result.sent_time.plus_seconds(ttl) < env.block.time,
+            Err(_) => true,
+        })
+        .map(|r| r.map(|(request_id, _)| request_id))
+        .collect::<StdResult<Vec<_>>>()?;
+    for request_id in expired {
+        QUERY_RESULTS.remove(storage, request_id);
+    }
+    Ok(())
+}
+
+/// Loads the account of the channel, which must be open to send packets on it
+pub(crate) fn open_account(
+    storage: &dyn Storage,
+    channel_id: &str,
+) -> Result<AccountData, ContractError> {
+    let account = ACCOUNTS.load(storage, channel_id)?;
+    if account.status == ChannelStatus::Closed {
+        return Err(ContractError::ChannelClosed(channel_id.to_string()));
+    }
+    Ok(account)
+}
+
+pub(crate) fn load_version(storage: &dyn Storage, channel_id: &str) -> StdResult<IcaVersion> {
+    Ok(CHANNEL_VERSIONS
+        .may_load(storage, channel_id)?
+        .unwrap_or(IcaVersion::V2))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
     match msg {
-        QueryMsg::Admin {} => to_binary(&query_admin(deps)?),
+        QueryMsg::Admins {} => to_binary(&ADMINS.query(deps.storage)?),
         QueryMsg::Account { channel_id } => to_binary(&query_account(deps, channel_id)?),
         QueryMsg::ListAccounts {} => to_binary(&query_list_accounts(deps)?),
+        QueryMsg::ChannelStats { channel_id } => to_binary(&query_channel_stats(deps, channel_id)?),
         QueryMsg::LatestQueryResult { channel_id } => {
             to_binary(&query_latest_ibc_query_result(deps, channel_id)?)
         }
+        QueryMsg::ListClosedAccounts {} => to_binary(&query_list_closed_accounts(deps)?),
+        QueryMsg::BalanceHistory {
+            channel_id,
+            start_after,
+            end_at,
+            limit,
+        } => to_binary(&query_balance_history(
+            deps,
+            channel_id,
+            start_after,
+            end_at,
+            limit,
+        )?),
+        QueryMsg::QueryResult { request_id } => to_binary(&query_query_result(deps, request_id)?),
+        QueryMsg::ListQueryResults { start_after, limit } => {
+            to_binary(&query_list_query_results(deps, start_after, limit)?)
+        }
+        QueryMsg::PacketFee {
+            channel_id,
+            sequence,
+        } => to_binary(&query_packet_fee(deps, channel_id, sequence)?),
+        QueryMsg::ListPacketFees { channel_id } => {
+            to_binary(&query_list_packet_fees(deps, channel_id)?)
+        }
+        QueryMsg::FeeRefund { address } => to_binary(&query_fee_refund(deps, address)?),
+        QueryMsg::UpcomingJobs { limit } => to_binary(&query_upcoming_jobs(deps, limit)?),
+        QueryMsg::FinishedJobs { start_after, limit } => {
+            to_binary(&query_finished_jobs(deps, start_after, limit)?)
+        }
+        QueryMsg::Broadcast { batch_id } => to_binary(&query_broadcast(deps, batch_id)?),
+        QueryMsg::PendingTransfers { address } => {
+            to_binary(&query_pending_transfers(deps, address)?)
+        }
+        QueryMsg::FailedCallbacks { address } => to_binary(&query_failed_callbacks(deps, address)?),
+        QueryMsg::TransferRefund { address } => to_binary(&query_transfer_refund(deps, address)?),
     }
 }
 
@@ -224,44 +871,305 @@ fn query_account(deps: Deps, channel_id: String) -> StdResult<AccountResponse> {
     Ok(account.into())
 }
 
+fn query_channel_stats(deps: Deps, channel_id: String) -> StdResult<ChannelStatsResponse> {
+    let account = ACCOUNTS.load(deps.storage, &channel_id)?;
+    let stats = CHANNEL_STATS
+        .may_load(deps.storage, &channel_id)?
+        .unwrap_or_default();
+    Ok(ChannelStatsResponse {
+        status: account.status,
+        stats,
+    })
+}
+
 fn query_latest_ibc_query_result(deps: Deps, channel_id: String) -> StdResult<LatestQueryResponse> {
     LATEST_QUERIES.load(deps.storage, &channel_id)
 }
 
+fn query_balance_history(
+    deps: Deps,
+    channel_id: String,
+    start_after: Option<Timestamp>,
+    end_at: Option<Timestamp>,
+    limit: Option<u32>,
+) -> StdResult<BalanceHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let min = start_after.map(|time| Bound::exclusive((time.nanos(), u64::MAX)));
+    let max = end_at.map(|time| Bound::inclusive((time.nanos(), u64::MAX)));
+    let mut snapshots: Vec<BalanceSnapshot> = vec![];
+    for r in BALANCE_HISTORY
+        .sub_prefix(&channel_id)
+        .range(deps.storage, min, max, Order::Ascending)
+    {
+        let ((nanos, sequence), balances) = r?;
+        let time = Timestamp::from_nanos(nanos);
+        // the snapshots of one time stay on one page, so `start_after` skips none of them
+        if snapshots.len() >= limit && snapshots.last().map(|s| s.time) != Some(time) {
+            break;
+        }
+        snapshots.push(BalanceSnapshot {
+            time,
+            sequence,
+            balances,
+        });
+    }
+    Ok(BalanceHistoryResponse { snapshots })
+}
+
+fn query_query_result(deps: Deps, request_id: u64) -> StdResult<QueryResultResponse> {
+    let result = QUERY_RESULTS.load(deps.storage, request_id)?;
+    Ok(QueryResultResponse::convert(request_id, result))
+}
+
+fn query_list_query_results(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ListQueryResultsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let results = QUERY_RESULTS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|r| {
+            let (request_id, result) = r?;
+            Ok(QueryResultResponse::convert(request_id, result))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ListQueryResultsResponse { results })
+}
+
 fn query_list_accounts(deps: Deps) -> StdResult<ListAccountsResponse> {
     let accounts = ACCOUNTS
         .range(deps.storage, None, None, Order::Ascending)
         .map(|r| {
             let (channel_id, account) = r?;
-            Ok(AccountInfo::convert(channel_id, account))
+            let stats = CHANNEL_STATS
+                .may_load(deps.storage, &channel_id)?
+                .unwrap_or_default();
+            Ok(AccountInfo::convert(channel_id, account, stats))
         })
         .collect::<StdResult<_>>()?;
     Ok(ListAccountsResponse { accounts })
 }
 
-fn query_admin(deps: Deps) -> StdResult<AdminResponse> {
-    let Config { admin } = CONFIG.load(deps.storage)?;
-    Ok(AdminResponse {
-        admin: admin.into(),
+fn query_list_closed_accounts(deps: Deps) -> StdResult<ListClosedAccountsResponse> {
+    let accounts = CLOSED_ACCOUNTS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|r| {
+            let (remote_addr, closed) = r?;
+            Ok(ClosedAccountInfo {
+                remote_addr,
+                channel_id: closed.channel_id,
+                last_update_time: closed.last_update_time,
+                remote_balance: closed.remote_balance,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ListClosedAccountsResponse { accounts })
+}
+
+fn query_packet_fee(deps: Deps, channel_id: String, sequence: u64) -> StdResult<PacketFeeResponse> {
+    let escrow = FEE_ESCROWS.load(deps.storage, (&channel_id, sequence))?;
+    Ok(PacketFeeResponse {
+        payer: escrow.payer.into(),
+        fee: escrow.fee,
+    })
+}
+
+fn query_list_packet_fees(deps: Deps, channel_id: String) -> StdResult<ListPacketFeesResponse> {
+    let fees = FEE_ESCROWS
+        .prefix(&channel_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|r| {
+            let (sequence, escrow) = r?;
+            Ok(PacketFeeInfo {
+                sequence,
+                payer: escrow.payer.into(),
+                fee: escrow.fee,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ListPacketFeesResponse { fees })
+}
+
+fn query_fee_refund(deps: Deps, address: String) -> StdResult<FeeRefundResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let amount = FEE_REFUNDS
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    Ok(FeeRefundResponse { amount })
+}
+
+fn query_upcoming_jobs(deps: Deps, limit: Option<u32>) -> StdResult<UpcomingJobsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let jobs = JOB_QUEUE
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(limit)
+        .map(|r| {
+            let (_, job_id) = r?;
+            let job = SCHEDULED_JOBS.load(deps.storage, job_id)?;
+            Ok(ScheduledJobInfo {
+                job_id,
+                sender: job.sender.into(),
+                channel_id: job.channel_id,
+                msgs: job.msgs,
+                callback_id: job.callback_id,
+                next_run: job.next_run,
+                interval: job.interval,
+                runs: job.runs,
+                runs_left: job.runs_left,
+                reward: job.reward,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(UpcomingJobsResponse { jobs })
+}
+
+fn query_finished_jobs(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<FinishedJobsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let jobs = FINISHED_JOBS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|r| {
+            let (job_id, job) = r?;
+            Ok(FinishedJobInfo {
+                job_id,
+                sender: job.sender.into(),
+                channel_id: job.channel_id,
+                callback_id: job.callback_id,
+                runs: job.runs,
+                finished_time: job.finished_time,
+                error: job.error,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(FinishedJobsResponse { jobs })
+}
+
+fn query_broadcast(deps: Deps, batch_id: u64) -> StdResult<BroadcastStatusResponse> {
+    let broadcast = BROADCASTS.load(deps.storage, batch_id)?;
+    let packets = broadcast
+        .packets
+        .into_iter()
+        .map(|packet| BroadcastPacketInfo {
+            channel_id: packet.channel_id,
+            sequence: packet.sequence,
+            result: packet.result,
+        })
+        .collect();
+    Ok(BroadcastStatusResponse {
+        sender: broadcast.sender.into(),
+        callback_id: broadcast.callback_id,
+        packets,
     })
 }
 
+fn query_pending_transfers(deps: Deps, address: String) -> StdResult<PendingTransfersResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let transfers = TRANSFERS_BY_SENDER
+        .sub_prefix(&address)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .map(|r| {
+            let (transfer_channel_id, sequence) = r?;
+            let pending = PENDING_TRANSFERS.load(deps.storage, (&transfer_channel_id, sequence))?;
+            Ok(PendingTransferInfo {
+                transfer_channel_id,
+                sequence,
+                amount: pending.amount,
+                batch_id: pending.batch_id,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(PendingTransfersResponse { transfers })
+}
+
+fn query_transfer_refund(deps: Deps, address: String) -> StdResult<TransferRefundResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let amount = TRANSFER_REFUNDS
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    Ok(TransferRefundResponse { amount })
+}
+
+fn query_failed_callbacks(deps: Deps, address: String) -> StdResult<FailedCallbacksResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let callbacks = FAILED_CALLBACKS
+        .prefix(address.as_str())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|r| {
+            let (failure_id, failed) = r?;
+            Ok(FailedCallbackInfo {
+                failure_id,
+                callback: failed.callback,
+                error: failed.error,
+                failed_time: failed.failed_time,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(FailedCallbacksResponse { callbacks })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use simple_ica::admin::AdminExecuteMsg;
+    use simple_ica::SimpleIcaError;
 
     const CREATOR: &str = "creator";
 
     #[test]
     fn instantiate_works() {
         let mut deps = mock_dependencies();
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg::default();
         let info = mock_info(CREATOR, &[]);
         let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
         assert_eq!(0, res.messages.len());
 
-        let admin = query_admin(deps.as_ref()).unwrap();
-        assert_eq!(CREATOR, admin.admin.as_str());
+        let admins = ADMINS.query(&deps.storage).unwrap();
+        assert_eq!(admins.admins.len(), 1);
+        assert_eq!(CREATOR, admins.admins[0].address.as_str());
+        assert_eq!(AdminRole::Owner, admins.admins[0].role);
+    }
+
+    #[test]
+    fn operators_may_not_change_the_config() {
+        let mut deps = mock_dependencies();
+        let info = mock_info(CREATOR, &[]);
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            InstantiateMsg::default(),
+        )
+        .unwrap();
+
+        // nothing changes before the operator accepts
+        let propose = ExecuteMsg::Admin(AdminExecuteMsg::ProposeAdmin {
+            address: "operator".into(),
+            role: AdminRole::Operator,
+        });
+        execute(deps.as_mut(), mock_env(), info, propose).unwrap();
+        let operator = mock_info("operator", &[]);
+        let send = ExecuteMsg::CheckRemoteBalance {
+            channel_id: "channel-7".into(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), operator.clone(), send.clone()).unwrap_err();
+        assert_eq!(err, SimpleIcaError::NotAdmin("operator".into()).into());
+        let accept = ExecuteMsg::Admin(AdminExecuteMsg::AcceptAdmin {});
+        execute(deps.as_mut(), mock_env(), operator.clone(), accept).unwrap();
+
+        // operators may send packets (to a channel that exists), but only owners change the config
+        let err = execute(deps.as_mut(), mock_env(), operator.clone(), send).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::NotFound { .. })));
+        let ttl = ExecuteMsg::UpdateQueryResultTtl { ttl: 60 };
+        let err = execute(deps.as_mut(), mock_env(), operator, ttl).unwrap_err();
+        assert_eq!(err, SimpleIcaError::NotOwner("operator".into()).into());
     }
 }
@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Timestamp};
 
 use simple_ica::SimpleIcaError;
 
@@ -15,6 +15,44 @@ pub enum ContractError {
     #[error("No account for channel {0}")]
     UnregisteredChannel(String),
 
+    #[error("Channel {0} was closed")]
+    ChannelClosed(String),
+
     #[error("remote account changed from {old} to {addr}")]
     RemoteAccountChanged { addr: String, old: String },
+
+    #[error(
+        "Packets on channel {0} were not counted since the connect, so they can not be tracked"
+    )]
+    UnknownSequence(String),
+
+    #[error("Sent funds must be the total fee of '{0}'")]
+    FeeFundsMismatch(String),
+
+    #[error("Sent funds must be the crank reward of all runs, '{0}'")]
+    RewardFundsMismatch(String),
+
+    #[error("No scheduled job is due")]
+    NoJobsDue,
+
+    #[error("No scheduled job {0}")]
+    UnknownJob(u64),
+
+    #[error("No fee refund to claim")]
+    NoFeeRefund,
+
+    #[error("No transfer refund to claim")]
+    NoTransferRefund,
+
+    #[error("No failed callback {0} for the sender")]
+    UnknownFailedCallback(u64),
+
+    #[error("Invalid reply id")]
+    InvalidReplyId,
+
+    #[error("Balances can be refreshed again at {next}")]
+    RefreshTooSoon { next: Timestamp },
+
+    #[error("Transfers can not be followed without ibc-hooks")]
+    IbcHooksDisabled,
 }
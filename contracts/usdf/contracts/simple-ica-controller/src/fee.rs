@@ -0,0 +1,150 @@
+use cosmwasm_std::{
+    Attribute, Coin, DepsMut, Env, Event, MessageInfo, Order, Reply, Response, StdError, StdResult,
+    Storage,
+};
+
+use simple_ica::fee::{add_coins, IbcFee};
+
+use crate::error::ContractError;
+use crate::state::{EscrowedFee, CHANNEL_PORTS, FEE_ESCROWS, FEE_REFUNDS, LAST_SEQUENCES};
+use crate::stats::record_sent;
+
+/// Counts a packet we are about to send on the channel, returning its sequence if we know it
+pub fn next_sequence(storage: &mut dyn Storage, channel_id: &str) -> StdResult<Option<u64>> {
+    record_sent(storage, channel_id)?;
+    match LAST_SEQUENCES.may_load(storage, channel_id)? {
+        Some(last) => {
+            LAST_SEQUENCES.save(storage, channel_id, &(last + 1))?;
+            Ok(Some(last + 1))
+        }
+        None => Ok(None),
+    }
+}
+
+/// The sequence of the packet sent by a submessage, from the events in its reply
+pub fn sent_sequence(events: &[Event]) -> Option<u64> {
+    send_packet_attr(events, "packet_sequence").and_then(|value| value.parse().ok())
+}
+
+fn send_packet_attr<'a>(events: &'a [Event], key: &str) -> Option<&'a str> {
+    events
+        .iter()
+        .filter(|event| event.ty == "send_packet")
+        .flat_map(|event| &event.attributes)
+        .find(|attr| attr.key == key)
+        .map(|attr| attr.value.as_str())
+}
+
+/// Starts counting the packets of a channel connected by an older version, from the reply
+/// to a packet we sent on it. Returns the sequence of that packet.
+pub fn learn_sequence(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    events: &[Event],
+) -> StdResult<u64> {
+    let sequence =
+        sent_sequence(events).ok_or_else(|| StdError::generic_err("no sequence for the packet"))?;
+    if !LAST_SEQUENCES.has(storage, channel_id) {
+        let port_id = send_packet_attr(events, "packet_src_port")
+            .ok_or_else(|| StdError::generic_err("no port for the packet"))?;
+        LAST_SEQUENCES.save(storage, channel_id, &sequence)?;
+        CHANNEL_PORTS.save(storage, channel_id, &port_id.to_string())?;
+    }
+    Ok(sequence)
+}
+
+pub const SEQUENCE_REPLY_ID: u64 = 4;
+
+/// The reply to a packet sent to learn the sequence of its channel
+pub fn reply_sequence(deps: DepsMut, reply: Reply) -> Result<Response, ContractError> {
+    let response = reply.result.into_result().map_err(StdError::generic_err)?;
+    let channel_id = send_packet_attr(&response.events, "packet_src_channel")
+        .ok_or_else(|| StdError::generic_err("no channel for the packet"))?
+        .to_string();
+    let sequence = learn_sequence(deps.storage, &channel_id, &response.events)?;
+    Ok(Response::new()
+        .add_attribute("channel_id", channel_id)
+        .add_attribute("sequence", sequence.to_string()))
+}
+
+/// Counts the packet about to be sent on the channel, and escrows the fee for it if requested,
+/// paid with the funds sent along. The chain rejects the fee if the channel has no fee
+/// middleware. Returns the sequence of the packet if we know it, and a
+/// response with the message paying the fee, which must be executed right before the packet.
+pub fn prepare_packet(
+    storage: &mut dyn Storage,
+    env: &Env,
+    info: &MessageInfo,
+    channel_id: &str,
+    fee: Option<IbcFee>,
+) -> Result<(Option<u64>, Response), ContractError> {
+    let fee = match fee {
+        Some(fee) => fee,
+        None => return Ok((next_sequence(storage, channel_id)?, Response::new())),
+    };
+    let total = fee.total();
+    if total.is_empty() || add_coins(&info.funds, &[]) != total {
+        return Err(ContractError::FeeFundsMismatch(format_coins(&total)));
+    }
+    let sequence = next_sequence(storage, channel_id)?
+        .ok_or_else(|| ContractError::UnknownSequence(channel_id.to_string()))?;
+    // saved together with the sequence
+    let port_id = CHANNEL_PORTS.load(storage, channel_id)?;
+
+    let msg = fee.pay_packet_fee_msg(&port_id, channel_id, env.contract.address.as_str());
+    let escrow = EscrowedFee {
+        payer: info.sender.clone(),
+        fee,
+    };
+    FEE_ESCROWS.save(storage, (channel_id, sequence), &escrow)?;
+    let res = Response::new()
+        .add_message(msg)
+        .add_attribute("sequence", sequence.to_string());
+    Ok((Some(sequence), res))
+}
+
+/// Once a packet is done, the fee middleware pays the relayers and sends the rest back to us.
+/// `refund` tells which part that is. We keep it for the payer to claim.
+pub fn refund_fee(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    sequence: u64,
+    refund: fn(&IbcFee) -> Vec<Coin>,
+) -> StdResult<Vec<Attribute>> {
+    let escrow = match FEE_ESCROWS.may_load(storage, (channel_id, sequence))? {
+        Some(escrow) => escrow,
+        None => return Ok(vec![]),
+    };
+    FEE_ESCROWS.remove(storage, (channel_id, sequence));
+
+    let amount = refund(&escrow.fee);
+    if !amount.is_empty() {
+        FEE_REFUNDS.update(storage, &escrow.payer, |old| -> StdResult<_> {
+            Ok(add_coins(&old.unwrap_or_default(), &amount))
+        })?;
+    }
+    Ok(vec![
+        Attribute::new("fee_payer", escrow.payer),
+        Attribute::new("fee_refund", format_coins(&amount)),
+    ])
+}
+
+/// When a channel closes, the fee middleware refunds the fees of all packets in flight
+pub fn refund_channel_fees(storage: &mut dyn Storage, channel_id: &str) -> StdResult<()> {
+    let sequences = FEE_ESCROWS
+        .prefix(channel_id)
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for sequence in sequences {
+        refund_fee(storage, channel_id, sequence, IbcFee::total)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn format_coins(coins: &[Coin]) -> String {
+    coins
+        .iter()
+        .map(Coin::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
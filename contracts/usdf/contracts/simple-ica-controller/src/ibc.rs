@@ -1,19 +1,30 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    from_slice, to_binary, DepsMut, Env, Ibc3ChannelOpenResponse, IbcBasicResponse,
-    IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcMsg, IbcPacketAckMsg,
-    IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, StdResult,
+    from_slice, Coin, DepsMut, Env, Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannelCloseMsg,
+    IbcChannelConnectMsg, IbcChannelOpenMsg, IbcMsg, IbcPacketAckMsg, IbcPacketReceiveMsg,
+    IbcPacketTimeoutMsg, IbcReceiveResponse, Order, StdResult, Storage, Timestamp,
 };
 
+use simple_ica::fee::IbcFee;
 use simple_ica::{
-    check_order, check_version, BalancesResponse, PacketMsg, ReceiveIcaResponseMsg, StdAck,
-    WhoAmIResponse,
+    check_channel, check_order, check_version, version_proposal, BalancesResponse,
+    ClaimOrphanedResponse, ErrorCode, PacketKind, PacketMsg, ReceiveIcaResponseMsg, StdAck,
+    WhoAmIResponse, SUPPORTED_VERSIONS,
 };
 
+use crate::broadcast::record_broadcast_result;
+use crate::callback::{callback_msg, callback_packet};
+use crate::contract::load_version;
 use crate::error::ContractError;
-use crate::msg::LatestQueryResponse;
-use crate::state::{AccountData, ACCOUNTS, LATEST_QUERIES};
+use crate::fee::{format_coins, next_sequence, refund_channel_fees, refund_fee};
+use crate::msg::{ChannelStatus, LatestQueryResponse};
+use crate::state::{
+    AccountData, ClosedAccount, ACCOUNTS, BALANCE_HISTORY, CHANNEL_PORTS, CHANNEL_VERSIONS,
+    CLOSED_ACCOUNTS, LAST_SEQUENCES, LATEST_QUERIES, MAX_BALANCE_SNAPSHOTS, PENDING_QUERIES,
+    QUERY_RESULTS,
+};
+use crate::stats::{record_ack, record_close, record_timeout};
 
 // TODO: make configurable?
 /// packets live one hour
@@ -27,10 +38,22 @@ pub fn ibc_channel_open(
     msg: IbcChannelOpenMsg,
 ) -> Result<Option<Ibc3ChannelOpenResponse>, ContractError> {
     let channel = msg.channel();
-    check_order(&channel.order)?;
-    check_version(&channel.version)?;
+    // if the relayer left the version to us, we propose all the versions we speak
+    if channel.version.is_empty() && msg.counterparty_version().is_none() {
+        check_order(&channel.order)?;
+        return Ok(Some(Ibc3ChannelOpenResponse {
+            version: version_proposal(SUPPORTED_VERSIONS),
+        }));
+    }
+
+    // on OpenInit the version may be a proposal, which the host picks from
+    check_channel(&channel.order, &channel.version)?;
     if let Some(counter_version) = msg.counterparty_version() {
-        check_version(counter_version)?;
+        // on OpenTry we pick the version to speak from the counterparty proposal
+        let version = check_channel(&channel.order, counter_version)?;
+        return Ok(Some(Ibc3ChannelOpenResponse {
+            version: version.to_string(),
+        }));
     }
 
     Ok(None)
@@ -42,19 +65,30 @@ pub fn ibc_channel_connect(
     deps: DepsMut,
     env: Env,
     msg: IbcChannelConnectMsg,
-) -> StdResult<IbcBasicResponse> {
+) -> Result<IbcBasicResponse, ContractError> {
     let channel = msg.channel();
     let channel_id = &channel.endpoint.channel_id;
 
+    let version = msg.counterparty_version().unwrap_or(&channel.version);
+    check_order(&channel.order)?;
+    // the handshake is over, so this must be the one version the host picked
+    let ica_version = check_version(version)?;
+    CHANNEL_VERSIONS.save(deps.storage, channel_id, &ica_version)?;
+
+    // we count the packets from here on, so we know which one a fee is paid for
+    LAST_SEQUENCES.save(deps.storage, channel_id, &0)?;
+    CHANNEL_PORTS.save(deps.storage, channel_id, &channel.endpoint.port_id)?;
+
     // create an account holder the channel exists (not found if not registered)
     let data = AccountData::default();
     ACCOUNTS.save(deps.storage, channel_id, &data)?;
 
     // construct a packet to send
+    next_sequence(deps.storage, channel_id)?;
     let packet = PacketMsg::WhoAmI {};
     let msg = IbcMsg::SendPacket {
         channel_id: channel_id.clone(),
-        data: to_binary(&packet)?,
+        data: ica_version.encode_packet(&packet)?,
         timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
     };
 
@@ -65,17 +99,33 @@ pub fn ibc_channel_connect(
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-/// On closed channel, simply delete the account from our local store
+/// On closed channel, mark the account as closed in our local store
 pub fn ibc_channel_close(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     msg: IbcChannelCloseMsg,
 ) -> StdResult<IbcBasicResponse> {
     let channel = msg.channel();
 
-    // remove the channel
+    // keep the closed channel, and remember the account in case the host gives it back
     let channel_id = &channel.endpoint.channel_id;
-    ACCOUNTS.remove(deps.storage, channel_id);
+    if let Some(mut data) = ACCOUNTS.may_load(deps.storage, channel_id)? {
+        if let Some(remote_addr) = &data.remote_addr {
+            let closed = ClosedAccount {
+                channel_id: channel_id.clone(),
+                last_update_time: data.last_update_time,
+                remote_balance: data.remote_balance.clone(),
+            };
+            CLOSED_ACCOUNTS.save(deps.storage, remote_addr, &closed)?;
+        }
+        data.status = ChannelStatus::Closed;
+        ACCOUNTS.save(deps.storage, channel_id, &data)?;
+    }
+    record_close(deps.storage, channel_id, env.block.time)?;
+    // the version stays, packets in flight still time out after the close
+    LAST_SEQUENCES.remove(deps.storage, channel_id);
+    CHANNEL_PORTS.remove(deps.storage, channel_id);
+    refund_channel_fees(deps.storage, channel_id)?;
 
     Ok(IbcBasicResponse::new()
         .add_attribute("action", "ibc_close")
@@ -99,48 +149,104 @@ pub fn ibc_packet_ack(
     deps: DepsMut,
     env: Env,
     msg: IbcPacketAckMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    // the relayers earned the receive and ack fees
+    let packet = &msg.original_packet;
+    let refund = refund_fee(
+        deps.storage,
+        &packet.src.channel_id,
+        packet.sequence,
+        IbcFee::refund_on_ack,
+    )?;
+    Ok(acknowledge_packet(deps, env, msg)?.add_attributes(refund))
+}
+
+fn acknowledge_packet(
+    deps: DepsMut,
+    env: Env,
+    msg: IbcPacketAckMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     // which local channel was this packet send from
     let caller = msg.original_packet.src.channel_id.clone();
-    // we need to parse the ack based on our request
-    let original_packet: PacketMsg = from_slice(&msg.original_packet.data)?;
     let res: StdAck = from_slice(&msg.acknowledgement.data)?;
+    record_ack(deps.storage, &caller, &res, env.block.time)?;
 
+    // we need to parse the ack based on our request
+    let version = load_version(deps.storage, &caller)?;
+    let original_packet = version.decode_packet(&msg.original_packet.data)?;
     match original_packet {
         PacketMsg::Dispatch {
             sender,
             callback_id,
             ..
-        } => acknowledge_dispatch(deps, env, caller, sender, callback_id, msg),
+        }
+        | PacketMsg::ProvenanceDispatch {
+            sender,
+            callback_id,
+            ..
+        } => {
+            let sequence = msg.original_packet.sequence;
+            let kind = PacketKind::Dispatch;
+            acknowledge_dispatch(deps, caller, sequence, sender, callback_id, kind, res)
+        }
+        PacketMsg::Trade {
+            sender,
+            callback_id,
+            ..
+        } => {
+            let sequence = msg.original_packet.sequence;
+            let kind = PacketKind::Trade;
+            acknowledge_dispatch(deps, caller, sequence, sender, callback_id, kind, res)
+        }
         PacketMsg::IbcQuery {
             sender,
             callback_id,
             ..
+        }
+        | PacketMsg::ProvenanceQuery {
+            sender,
+            callback_id,
+            ..
         } => acknowledge_query(deps, env, caller, sender, callback_id, msg),
         PacketMsg::WhoAmI {} => acknowledge_who_am_i(deps, caller, res),
-        PacketMsg::Balances {} => acknowledge_balances(deps, env, caller, res),
+        PacketMsg::Balances {} => {
+            let sequence = msg.original_packet.sequence;
+            acknowledge_balances(deps, env, caller, sequence, res)
+        }
+        PacketMsg::ClaimOrphaned { channel_id } => acknowledge_claim_orphaned(channel_id, res),
     }
 }
 
-// receive PacketMsg::Dispatch response
-#[allow(clippy::unnecessary_wraps)]
+// receive PacketMsg::Dispatch response, or the one of a trade
 fn acknowledge_dispatch(
-    _deps: DepsMut,
-    _env: Env,
-    _caller: String,
+    deps: DepsMut,
+    caller: String,
+    sequence: u64,
     sender: String,
     callback_id: Option<String>,
-    ack: IbcPacketAckMsg,
+    kind: PacketKind,
+    msg: StdAck,
 ) -> Result<IbcBasicResponse, ContractError> {
+    // the packets of a broadcast are reported together
+    if let Some((attributes, callback)) =
+        record_broadcast_result(deps.storage, &caller, sequence, msg.clone())?
+    {
+        let res = IbcBasicResponse::new()
+            .add_attribute("action", "acknowledge_broadcast")
+            .add_attributes(attributes)
+            .add_submessages(callback);
+        return Ok(res);
+    }
+
     let res = IbcBasicResponse::new().add_attribute("action", "acknowledge_dispatch");
     match callback_id {
         Some(id) => {
-            let msg: StdAck = from_slice(&ack.acknowledgement.data)?;
             // Send IBC packet ack message to another contract
+            let packet = callback_packet(deps.storage, &caller, Some(sequence), kind)?;
+            let callback = ReceiveIcaResponseMsg::new(id.clone(), msg, packet);
             let res = res
-                .add_attribute("callback_id", &id)
-                //  In production, you will want to think about gas limits for this callback.
-                .add_message(ReceiveIcaResponseMsg { id, msg }.into_cosmos_msg(sender)?);
+                .add_attribute("callback_id", id)
+                .add_submessage(callback_msg(deps.storage, sender, callback)?);
             Ok(res)
         }
         None => Ok(res),
@@ -166,15 +272,29 @@ fn acknowledge_query(
             response: msg.clone(),
         },
     )?;
+    let mut res = IbcBasicResponse::new().add_attribute("action", "acknowledge_ibc_query");
+
+    // and with the request, if we know which one it was
+    let sequence = ack.original_packet.sequence;
+    if let Some(request_id) = PENDING_QUERIES.may_load(deps.storage, (&caller, sequence))? {
+        PENDING_QUERIES.remove(deps.storage, (&caller, sequence));
+        // the result may have been pruned already
+        if let Some(mut result) = QUERY_RESULTS.may_load(deps.storage, request_id)? {
+            result.response = Some(msg.clone());
+            result.received_time = Some(env.block.time);
+            QUERY_RESULTS.save(deps.storage, request_id, &result)?;
+        }
+        res = res.add_attribute("request_id", request_id.to_string());
+    }
+
     match callback_id {
         Some(id) => {
             // Send IBC packet ack message to another contract
-            let msg = ReceiveIcaResponseMsg { id, msg }.into_cosmos_msg(sender)?;
-            Ok(IbcBasicResponse::new()
-                .add_attribute("action", "acknowledge_ibc_query")
-                .add_message(msg))
+            let packet = callback_packet(deps.storage, &caller, Some(sequence), PacketKind::Query)?;
+            let callback = ReceiveIcaResponseMsg::new(id, msg, packet);
+            Ok(res.add_submessage(callback_msg(deps.storage, sender, callback)?))
         }
-        None => Ok(IbcBasicResponse::new().add_attribute("action", "acknowledge_ibc_query")),
+        None => Ok(res),
     }
 }
 
@@ -200,7 +320,7 @@ fn acknowledge_who_am_i(
             Some(mut acct) => {
                 // set the account the first time
                 if acct.remote_addr.is_none() {
-                    acct.remote_addr = Some(account);
+                    acct.remote_addr = Some(account.clone());
                 }
                 Ok(acct)
             }
@@ -208,7 +328,25 @@ fn acknowledge_who_am_i(
         }
     })?;
 
-    Ok(IbcBasicResponse::new().add_attribute("action", "acknowledge_who_am_i"))
+    let res = IbcBasicResponse::new().add_attribute("action", "acknowledge_who_am_i");
+    recover_account(deps, res, &account)
+}
+
+// if the host reattached an account of a closed channel, we let everyone know
+fn recover_account(
+    deps: DepsMut,
+    res: IbcBasicResponse,
+    remote_addr: &str,
+) -> Result<IbcBasicResponse, ContractError> {
+    match CLOSED_ACCOUNTS.may_load(deps.storage, remote_addr)? {
+        Some(closed) => {
+            CLOSED_ACCOUNTS.remove(deps.storage, remote_addr);
+            Ok(res
+                .add_attribute("recovered_account", remote_addr)
+                .add_attribute("recovered_from_channel", closed.channel_id))
+        }
+        None => Ok(res),
+    }
 }
 
 // receive PacketMsg::Balances response
@@ -216,6 +354,7 @@ fn acknowledge_balances(
     deps: DepsMut,
     env: Env,
     caller: String,
+    sequence: u64,
     ack: StdAck,
 ) -> Result<IbcBasicResponse, ContractError> {
     // ignore errors (but mention in log)
@@ -238,44 +377,201 @@ fn acknowledge_balances(
             Ok(AccountData {
                 last_update_time: env.block.time,
                 remote_addr: Some(account),
-                remote_balance: balances,
+                remote_balance: balances.clone(),
+                status: acct.status,
             })
         }
         None => Err(ContractError::UnregisteredChannel(caller.clone())),
     })?;
+    record_balances(deps.storage, &caller, env.block.time, sequence, &balances)?;
 
     Ok(IbcBasicResponse::new().add_attribute("action", "acknowledge_balances"))
 }
 
+// receive PacketMsg::ClaimOrphaned response, the funds are on the account now
+fn acknowledge_claim_orphaned(
+    orphaned_channel_id: String,
+    ack: StdAck,
+) -> Result<IbcBasicResponse, ContractError> {
+    let res = IbcBasicResponse::new()
+        .add_attribute("action", "acknowledge_claim_orphaned")
+        .add_attribute("orphaned_channel_id", orphaned_channel_id);
+    // ignore errors (but mention in log)
+    let ClaimOrphanedResponse { account, claimed } = match ack {
+        StdAck::Result(res) => from_slice(&res)?,
+        StdAck::Error(e) => return Ok(res.add_attribute("error", e)),
+    };
+    Ok(res
+        .add_attribute("account", account)
+        .add_attribute("claimed", format_coins(&claimed)))
+}
+
+// keeps the latest MAX_BALANCE_SNAPSHOTS balances of the channel
+fn record_balances(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    time: Timestamp,
+    sequence: u64,
+    balances: &[Coin],
+) -> StdResult<()> {
+    let key = (channel_id, time.nanos(), sequence);
+    BALANCE_HISTORY.save(storage, key, &balances.to_vec())?;
+    let expired = BALANCE_HISTORY
+        .sub_prefix(channel_id)
+        .keys(storage, None, None, Order::Descending)
+        .skip(MAX_BALANCE_SNAPSHOTS)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (nanos, sequence) in expired {
+        BALANCE_HISTORY.remove(storage, (channel_id, nanos, sequence));
+    }
+    Ok(())
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
-/// we just ignore these now. shall we store some info?
+/// we just ignore these now, apart from refunding the fees. shall we store some info?
 pub fn ibc_packet_timeout(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
-    _msg: IbcPacketTimeoutMsg,
+    msg: IbcPacketTimeoutMsg,
 ) -> StdResult<IbcBasicResponse> {
-    Ok(IbcBasicResponse::new().add_attribute("action", "ibc_packet_timeout"))
+    // only the timeout fee was earned
+    let packet = &msg.packet;
+    let channel_id = packet.src.channel_id.as_str();
+    let refund = refund_fee(
+        deps.storage,
+        channel_id,
+        packet.sequence,
+        IbcFee::refund_on_timeout,
+    )?;
+    record_timeout(deps.storage, channel_id, packet.sequence)?;
+    let mut res = IbcBasicResponse::new()
+        .add_attribute("action", "ibc_packet_timeout")
+        .add_attributes(refund);
+
+    // a query will never get its result
+    if let Some(request_id) =
+        PENDING_QUERIES.may_load(deps.storage, (channel_id, packet.sequence))?
+    {
+        PENDING_QUERIES.remove(deps.storage, (channel_id, packet.sequence));
+        if let Some(mut result) = QUERY_RESULTS.may_load(deps.storage, request_id)? {
+            result.timed_out = true;
+            QUERY_RESULTS.save(deps.storage, request_id, &result)?;
+        }
+        res = res.add_attribute("request_id", request_id.to_string());
+    }
+
+    let timeout = StdAck::error_with_code(ErrorCode::Timeout, "packet timed out");
+    if let Some((attributes, callback)) =
+        record_broadcast_result(deps.storage, channel_id, packet.sequence, timeout)?
+    {
+        res = res.add_attributes(attributes).add_submessages(callback);
+    }
+
+    // the sender waits for the result, so tell it there will be none
+    if let Some((sender, id)) = packet_callback(deps.storage, channel_id, &packet.data)? {
+        let msg = StdAck::error_with_code(ErrorCode::Timeout, "packet timed out");
+        let info = callback_packet(
+            deps.storage,
+            channel_id,
+            Some(packet.sequence),
+            PacketKind::Timeout,
+        )?;
+        let callback = ReceiveIcaResponseMsg::new(id.clone(), msg, info);
+        res = res
+            .add_attribute("callback_id", id)
+            .add_submessage(callback_msg(deps.storage, sender, callback)?);
+    }
+    Ok(res)
+}
+
+// who sent the packet, and the id it wants the callback with, if any.
+// Packets we can not decode have no callback, that must not block the timeout.
+fn packet_callback(
+    storage: &dyn Storage,
+    channel_id: &str,
+    data: &[u8],
+) -> StdResult<Option<(String, String)>> {
+    let version = load_version(storage, channel_id)?;
+    let (sender, callback_id) = match version.decode_packet(data) {
+        Ok(PacketMsg::Dispatch {
+            sender,
+            callback_id,
+            ..
+        })
+        | Ok(PacketMsg::ProvenanceDispatch {
+            sender,
+            callback_id,
+            ..
+        })
+        | Ok(PacketMsg::IbcQuery {
+            sender,
+            callback_id,
+            ..
+        })
+        | Ok(PacketMsg::ProvenanceQuery {
+            sender,
+            callback_id,
+            ..
+        })
+        | Ok(PacketMsg::Trade {
+            sender,
+            callback_id,
+            ..
+        }) => (sender, callback_id),
+        _ => return Ok(None),
+    };
+    Ok(callback_id.map(|id| (sender, id)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::contract::{execute, instantiate, query};
-    use crate::msg::{AccountResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
+    use crate::broadcast::BROADCAST_REPLY_ID;
+    use crate::callback::{callback_msg, CALLBACK_REPLY_ID};
+    use crate::contract::{execute, instantiate, query, reply, sudo};
+    use crate::msg::{
+        AccountResponse, BalanceHistoryResponse, BroadcastStatusResponse, ChannelStats,
+        ChannelStatsResponse, ChannelStatus, ExecuteMsg, FailedCallbacksResponse,
+        FeeRefundResponse, FinishedJobsResponse, InstantiateMsg, ListAccountsResponse,
+        ListClosedAccountsResponse, ListPacketFeesResponse, ListQueryResultsResponse,
+        PacketFeeResponse, PendingTransfersResponse, QueryMsg, QueryResultResponse, Schedule,
+        SudoMsg, Trade, TransferRefundResponse, UpcomingJobsResponse,
+    };
+    use crate::provenance::ProvenanceQueryMsg;
+    use crate::state::{
+        ADMINS, CHANNEL_PORTS, DEFAULT_BALANCE_REFRESH_INTERVAL, DEFAULT_CALLBACK_GAS_LIMIT,
+        DEFAULT_QUERY_RESULT_TTL,
+    };
+    use crate::transfer::TRANSFER_REPLY_ID;
 
     use cosmwasm_std::testing::{
-        mock_dependencies, mock_env, mock_ibc_channel_connect_ack, mock_ibc_channel_open_init,
-        mock_ibc_channel_open_try, mock_ibc_packet_ack, mock_info, MockApi, MockQuerier,
-        MockStorage,
+        mock_dependencies, mock_env, mock_ibc_channel_close_init, mock_ibc_channel_connect_ack,
+        mock_ibc_channel_open_init, mock_ibc_channel_open_try, mock_ibc_packet_ack,
+        mock_ibc_packet_timeout, mock_info, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+    };
+    use cosmwasm_std::{
+        coin, coins, to_binary, Addr, Attribute, BankMsg, Binary, CosmosMsg, Event,
+        IbcAcknowledgement, OwnedDeps, Reply, ReplyOn, Response, SubMsg, SubMsgResponse,
+        SubMsgResult, WasmMsg,
+    };
+    use serde::Deserialize;
+    use simple_ica::admin::{AdminExecuteMsg, AdminRole};
+    use simple_ica::ics20::{callback_memo, IbcLifecycleComplete, MsgTransfer};
+    use simple_ica::trade::TradeResponse;
+    use simple_ica::{BroadcastResponse, CallbackPacket, DispatchResponse, CALLBACK_VERSION};
+    use simple_ica::{
+        IcaVersion, PacketEnvelope, SimpleIcaError, APP_ORDER, BAD_APP_ORDER, IBC_APP_VERSION,
     };
-    use cosmwasm_std::{coin, coins, BankMsg, CosmosMsg, IbcAcknowledgement, OwnedDeps};
-    use simple_ica::{APP_ORDER, BAD_APP_ORDER, IBC_APP_VERSION};
 
     const CREATOR: &str = "creator";
 
     fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
         let mut deps = mock_dependencies();
-        let msg = InstantiateMsg {};
+        // so the transfers are followed
+        let msg = InstantiateMsg {
+            ibc_hooks: Some(true),
+            ..InstantiateMsg::default()
+        };
         let info = mock_info(CREATOR, &[]);
         let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
         assert_eq!(0, res.messages.len());
@@ -330,6 +626,47 @@ mod tests {
         ibc_channel_open(deps.as_mut(), mock_env(), valid_handshake).unwrap();
     }
 
+    #[test]
+    fn negotiate_version_in_handshake() {
+        let mut deps = setup();
+        let channel_id = "channel-3";
+
+        // without a version from the relayer, we propose all we support
+        let handshake = mock_ibc_channel_open_init(channel_id, APP_ORDER, "");
+        let res = ibc_channel_open(deps.as_mut(), mock_env(), handshake).unwrap();
+        assert_eq!(res.unwrap().version, "simple-ica-v3,simple-ica-v2");
+
+        // the host must pick a single version
+        let handshake =
+            mock_ibc_channel_connect_ack(channel_id, APP_ORDER, "simple-ica-v3,simple-ica-v2");
+        ibc_channel_connect(deps.as_mut(), mock_env(), handshake).unwrap_err();
+
+        // and we speak what it picked
+        let handshake = mock_ibc_channel_connect_ack(channel_id, APP_ORDER, "simple-ica-v3");
+        let res = ibc_channel_connect(deps.as_mut(), mock_env(), handshake).unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            o => panic!("Unexpected message: {:?}", o),
+        };
+        let packet: PacketEnvelope = from_slice(&data).unwrap();
+        assert_eq!(packet.version, IcaVersion::V3);
+        assert_eq!(packet.packet, PacketMsg::WhoAmI {});
+
+        // the ack is matched against the decoded packet
+        let ack = IbcAcknowledgement::new(StdAck::success(WhoAmIResponse {
+            account: "account-3".into(),
+        }));
+        let mut msg = mock_ibc_packet_ack(channel_id, &1u32, ack).unwrap();
+        msg.original_packet.data = data;
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        let q = QueryMsg::Account {
+            channel_id: channel_id.into(),
+        };
+        let r = query(deps.as_ref(), mock_env(), q).unwrap();
+        let acct: AccountResponse = from_slice(&r).unwrap();
+        assert_eq!(acct.remote_addr.unwrap(), "account-3");
+    }
+
     #[test]
     fn proper_handshake_flow() {
         // setup and connect handshake
@@ -362,6 +699,351 @@ mod tests {
         assert_eq!(0, acct.last_update_time.nanos());
     }
 
+    #[test]
+    fn recover_account_after_close() {
+        let mut deps = setup();
+        let remote_addr = "account-789";
+        connect(deps.as_mut(), "channel-1");
+        who_am_i_response(deps.as_mut(), "channel-1", remote_addr);
+
+        // closing the channel remembers the account
+        let close = mock_ibc_channel_close_init("channel-1", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_close(deps.as_mut(), mock_env(), close).unwrap();
+        let r = query(deps.as_ref(), mock_env(), QueryMsg::ListClosedAccounts {}).unwrap();
+        let closed: ListClosedAccountsResponse = from_slice(&r).unwrap();
+        assert_eq!(1, closed.accounts.len());
+        assert_eq!(closed.accounts[0].remote_addr, remote_addr);
+        assert_eq!(closed.accounts[0].channel_id, "channel-1");
+
+        // the host reattaches it to the next channel
+        connect(deps.as_mut(), "channel-2");
+        let packet = PacketMsg::WhoAmI {};
+        let ack = IbcAcknowledgement::new(StdAck::success(WhoAmIResponse {
+            account: remote_addr.into(),
+        }));
+        let msg = mock_ibc_packet_ack("channel-2", &packet, ack).unwrap();
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("recovered_from_channel", "channel-1")));
+
+        let r = query(deps.as_ref(), mock_env(), QueryMsg::ListClosedAccounts {}).unwrap();
+        let closed: ListClosedAccountsResponse = from_slice(&r).unwrap();
+        assert_eq!(0, closed.accounts.len());
+    }
+
+    #[test]
+    fn claim_orphaned_funds() {
+        let mut deps = setup();
+        connect(deps.as_mut(), "channel-1");
+        who_am_i_response(deps.as_mut(), "channel-1", "account-1");
+
+        // only admins may claim
+        let claim = ExecuteMsg::ClaimOrphaned {
+            channel_id: "channel-1".into(),
+            orphaned_channel_id: "channel-9".into(),
+        };
+        let info = mock_info("anyone", &[]);
+        execute(deps.as_mut(), mock_env(), info, claim.clone()).unwrap_err();
+        let info = mock_info(CREATOR, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, claim).unwrap();
+        let packet = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => {
+                from_slice::<PacketMsg>(data).unwrap()
+            }
+            msg => panic!("Unexpected message: {:?}", msg),
+        };
+        assert_eq!(
+            packet,
+            PacketMsg::ClaimOrphaned {
+                channel_id: "channel-9".into()
+            }
+        );
+
+        // the ack reports what was sent to the account
+        let ack = IbcAcknowledgement::new(StdAck::success(ClaimOrphanedResponse {
+            account: "account-1".into(),
+            claimed: coins(100, "uatom"),
+        }));
+        let msg = mock_ibc_packet_ack("channel-1", &packet, ack).unwrap();
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("claimed", "100uatom")));
+    }
+
+    #[test]
+    fn track_channel_stats() {
+        let mut deps = setup();
+        let channel_id = "channel-1";
+        connect(deps.as_mut(), channel_id);
+        who_am_i_response(deps.as_mut(), channel_id, "account-789");
+
+        // two dispatches, one fails on the host and one times out
+        let send = ExecuteMsg::SendMsgs {
+            channel_id: channel_id.into(),
+            msgs: vec![],
+            callback_id: None,
+            fee: None,
+        };
+        let info = mock_info(CREATOR, &[]);
+        let mut packets = vec![];
+        for _ in 0..2 {
+            let res = execute(deps.as_mut(), mock_env(), info.clone(), send.clone()).unwrap();
+            match &res.messages[0].msg {
+                CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => packets.push(data.clone()),
+                o => panic!("Unexpected message: {:?}", o),
+            }
+        }
+        let ack = IbcAcknowledgement::new(StdAck::fail("out of gas".into()));
+        let mut msg = mock_ibc_packet_ack(channel_id, &1u32, ack).unwrap();
+        msg.original_packet.data = packets[0].clone();
+        msg.original_packet.sequence = 2;
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        let mut msg = mock_ibc_packet_timeout(channel_id, &1u32).unwrap();
+        msg.packet.data = packets[1].clone();
+        msg.packet.sequence = 3;
+        ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let q = QueryMsg::ChannelStats {
+            channel_id: channel_id.into(),
+        };
+        let r: ChannelStatsResponse =
+            from_slice(&query(deps.as_ref(), mock_env(), q.clone()).unwrap()).unwrap();
+        assert_eq!(r.status, ChannelStatus::Open);
+        let expected = ChannelStats {
+            packets_sent: 3,
+            packets_acked: 1,
+            packets_errored: 1,
+            packets_timed_out: 1,
+            last_success_time: Some(mock_env().block.time),
+            last_error: Some("packet 3 timed out".into()),
+            closed_time: None,
+        };
+        assert_eq!(r.stats, expected);
+
+        // closed channels are kept, but nothing is sent on them anymore
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(60);
+        let close = mock_ibc_channel_close_init(channel_id, APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_close(deps.as_mut(), env.clone(), close).unwrap();
+        let r = query(deps.as_ref(), mock_env(), QueryMsg::ListAccounts {}).unwrap();
+        let accounts: ListAccountsResponse = from_slice(&r).unwrap();
+        assert_eq!(accounts.accounts.len(), 1);
+        assert_eq!(accounts.accounts[0].status, ChannelStatus::Closed);
+        assert_eq!(accounts.accounts[0].stats.closed_time, Some(env.block.time));
+        assert_eq!(accounts.accounts[0].stats.packets_sent, 3);
+        let err = execute(deps.as_mut(), mock_env(), info, send).unwrap_err();
+        assert_eq!(err, ContractError::ChannelClosed(channel_id.into()));
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info("anyone", &[]),
+            ExecuteMsg::RefreshBalances {},
+        )
+        .unwrap();
+        assert_eq!(0, res.messages.len());
+    }
+
+    #[test]
+    fn keep_query_results_per_request() {
+        let mut deps = setup();
+        let channel_id = "channel-1";
+        connect(deps.as_mut(), channel_id);
+        who_am_i_response(deps.as_mut(), channel_id, "account-1");
+
+        // each query gets its own request id
+        let query_msg = ExecuteMsg::IbcQuery {
+            channel_id: channel_id.into(),
+            msgs: vec![],
+            callback_id: None,
+            fee: None,
+        };
+        let mut packets = vec![];
+        for request_id in 1..=3 {
+            let info = mock_info("anyone", &[]);
+            let res = execute(deps.as_mut(), mock_env(), info, query_msg.clone()).unwrap();
+            let id = request_id.to_string();
+            assert!(res.attributes.contains(&Attribute::new("request_id", &id)));
+            assert_eq!(res.data, Some(to_binary(&request_id).unwrap()));
+            match &res.messages[0].msg {
+                CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => packets.push(data.clone()),
+                o => panic!("Unexpected message: {:?}", o),
+            }
+        }
+
+        // acks coming in out of order do not overwrite each other
+        for (sequence, result) in [(3, b"second"), (2, b"first!")] {
+            let ack = StdAck::Result(Binary::from(result));
+            let ack = IbcAcknowledgement::new(ack.ack());
+            let mut msg = mock_ibc_packet_ack(channel_id, &1u32, ack).unwrap();
+            msg.original_packet.data = packets[sequence as usize - 2].clone();
+            msg.original_packet.sequence = sequence;
+            ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        }
+        // and the third one times out
+        let mut msg = mock_ibc_packet_timeout(channel_id, &1u32).unwrap();
+        msg.packet.sequence = 4;
+        ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let q = QueryMsg::ListQueryResults {
+            start_after: None,
+            limit: None,
+        };
+        let r = query(deps.as_ref(), mock_env(), q).unwrap();
+        let list: ListQueryResultsResponse = from_slice(&r).unwrap();
+        assert_eq!(3, list.results.len());
+        assert_eq!(list.results[0].request_id, 1);
+        assert_eq!(
+            list.results[0].response,
+            Some(StdAck::Result(Binary::from(b"first!")))
+        );
+        assert_eq!(
+            list.results[1].response,
+            Some(StdAck::Result(Binary::from(b"second")))
+        );
+        assert_eq!(list.results[2].response, None);
+        assert!(list.results[2].timed_out);
+
+        let q = QueryMsg::ListQueryResults {
+            start_after: Some(1),
+            limit: Some(1),
+        };
+        let r = query(deps.as_ref(), mock_env(), q).unwrap();
+        let list: ListQueryResultsResponse = from_slice(&r).unwrap();
+        assert_eq!(1, list.results.len());
+        assert_eq!(list.results[0].request_id, 2);
+
+        // results older than the ttl are pruned with the next query
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(DEFAULT_QUERY_RESULT_TTL + 1);
+        let info = mock_info("anyone", &[]);
+        execute(deps.as_mut(), env.clone(), info, query_msg).unwrap();
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::QueryResult { request_id: 1 },
+        )
+        .unwrap_err();
+        let r = query(deps.as_ref(), env, QueryMsg::QueryResult { request_id: 4 }).unwrap();
+        let result: QueryResultResponse = from_slice(&r).unwrap();
+        assert_eq!(result.sender, "anyone");
+        assert_eq!(result.response, None);
+    }
+
+    #[test]
+    fn refresh_and_keep_balance_history() {
+        let mut deps = setup();
+        connect(deps.as_mut(), "channel-1");
+        connect(deps.as_mut(), "channel-2");
+        who_am_i_response(deps.as_mut(), "channel-1", "account-1");
+
+        // anyone may refresh all channels, but not too often
+        let info = mock_info("anyone", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::RefreshBalances {},
+        )
+        .unwrap();
+        assert_eq!(2, res.messages.len());
+        let mut env = mock_env();
+        env.block.time = env
+            .block
+            .time
+            .plus_seconds(DEFAULT_BALANCE_REFRESH_INTERVAL - 1);
+        let info = mock_info("anyone", &[]);
+        let err = execute(deps.as_mut(), env, info, ExecuteMsg::RefreshBalances {}).unwrap_err();
+        let next = mock_env()
+            .block
+            .time
+            .plus_seconds(DEFAULT_BALANCE_REFRESH_INTERVAL);
+        assert_eq!(err, ContractError::RefreshTooSoon { next });
+
+        // every ack is kept, up to a limit
+        let start = mock_env().block.time;
+        for i in 0..MAX_BALANCE_SNAPSHOTS as u64 + 2 {
+            let ack = IbcAcknowledgement::new(StdAck::success(BalancesResponse {
+                account: "account-1".into(),
+                balances: coins(i as u128, "uatom"),
+            }));
+            let msg = mock_ibc_packet_ack("channel-1", &PacketMsg::Balances {}, ack).unwrap();
+            let mut env = mock_env();
+            env.block.time = start.plus_seconds(i);
+            ibc_packet_ack(deps.as_mut(), env, msg).unwrap();
+        }
+
+        let q = QueryMsg::BalanceHistory {
+            channel_id: "channel-1".into(),
+            start_after: None,
+            end_at: None,
+            limit: None,
+        };
+        let r = query(deps.as_ref(), mock_env(), q).unwrap();
+        let history: BalanceHistoryResponse = from_slice(&r).unwrap();
+        // the two oldest were dropped
+        assert_eq!(history.snapshots[0].time, start.plus_seconds(2));
+        assert_eq!(history.snapshots[0].balances, coins(2, "uatom"));
+
+        // and we can page through a time range
+        let q = QueryMsg::BalanceHistory {
+            channel_id: "channel-1".into(),
+            start_after: Some(start.plus_seconds(10)),
+            end_at: Some(start.plus_seconds(12)),
+            limit: None,
+        };
+        let r = query(deps.as_ref(), mock_env(), q).unwrap();
+        let history: BalanceHistoryResponse = from_slice(&r).unwrap();
+        let times: Vec<_> = history.snapshots.iter().map(|s| s.time).collect();
+        assert_eq!(times, vec![start.plus_seconds(11), start.plus_seconds(12)]);
+
+        let q = QueryMsg::BalanceHistory {
+            channel_id: "channel-1".into(),
+            start_after: None,
+            end_at: None,
+            limit: Some(1000),
+        };
+        let r = query(deps.as_ref(), mock_env(), q).unwrap();
+        let history: BalanceHistoryResponse = from_slice(&r).unwrap();
+        assert_eq!(history.snapshots.len(), 30);
+
+        // two acks in one block are both kept
+        let last = start.plus_seconds(MAX_BALANCE_SNAPSHOTS as u64 + 10);
+        for sequence in [40, 41] {
+            let ack = IbcAcknowledgement::new(StdAck::success(BalancesResponse {
+                account: "account-1".into(),
+                balances: coins(sequence as u128, "uatom"),
+            }));
+            let mut msg = mock_ibc_packet_ack("channel-1", &PacketMsg::Balances {}, ack).unwrap();
+            msg.original_packet.sequence = sequence;
+            let mut env = mock_env();
+            env.block.time = last;
+            ibc_packet_ack(deps.as_mut(), env, msg).unwrap();
+        }
+        // and stay on one page
+        let q = QueryMsg::BalanceHistory {
+            channel_id: "channel-1".into(),
+            start_after: Some(start.plus_seconds(MAX_BALANCE_SNAPSHOTS as u64 + 1)),
+            end_at: None,
+            limit: Some(1),
+        };
+        let r = query(deps.as_ref(), mock_env(), q).unwrap();
+        let history: BalanceHistoryResponse = from_slice(&r).unwrap();
+        let snapshots: Vec<_> = history
+            .snapshots
+            .iter()
+            .map(|s| (s.time, s.sequence, s.balances.clone()))
+            .collect();
+        assert_eq!(
+            snapshots,
+            vec![
+                (last, 40, coins(40, "uatom")),
+                (last, 41, coins(41, "uatom"))
+            ]
+        );
+    }
+
     #[test]
     fn dispatch_message_send_and_ack() {
         let channel_id = "channel-1234";
@@ -384,6 +1066,7 @@ mod tests {
             channel_id: channel_id.into(),
             msgs: msgs_to_dispatch,
             callback_id: None,
+            fee: None,
         };
         let info = mock_info(CREATOR, &[]);
         let mut res = execute(deps.as_mut(), mock_env(), info, handle_msg).unwrap();
@@ -392,7 +1075,7 @@ mod tests {
             CosmosMsg::Ibc(IbcMsg::SendPacket {
                 channel_id, data, ..
             }) => {
-                let ack = IbcAcknowledgement::new(StdAck::success(&()));
+                let ack = IbcAcknowledgement::new(StdAck::success(()));
                 let mut msg = mock_ibc_packet_ack(&channel_id, &1u32, ack).unwrap();
                 msg.original_packet.data = data;
                 msg
@@ -405,6 +1088,475 @@ mod tests {
         assert_eq!(vec![("action", "acknowledge_dispatch")], res.attributes)
     }
 
+    #[test]
+    fn send_provenance_msgs() {
+        let channel_id = "channel-1234";
+        let remote_addr = "account-789";
+
+        let mut deps = setup();
+        connect(deps.as_mut(), channel_id);
+        who_am_i_response(deps.as_mut(), channel_id, remote_addr);
+
+        // the messages are passed on as they are, the host parses them
+        let msgs = vec![Binary::from(
+            br#"{"custom":{"route":"name","params":{}}}"#.as_slice(),
+        )];
+        let handle_msg = ExecuteMsg::SendProvenanceMsgs {
+            channel_id: channel_id.into(),
+            msgs: msgs.clone(),
+            callback_id: None,
+            fee: None,
+        };
+        let info = mock_info(CREATOR, &[]);
+        let mut res = execute(deps.as_mut(), mock_env(), info, handle_msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let data = match res.messages.swap_remove(0).msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data,
+            o => panic!("Unexpected message: {:?}", o),
+        };
+        assert_eq!(
+            IcaVersion::V2.decode_packet(&data).unwrap(),
+            PacketMsg::ProvenanceDispatch {
+                sender: CREATOR.into(),
+                msgs,
+                callback_id: None,
+            }
+        );
+
+        // and they are acknowledged like any dispatch
+        let ack = IbcAcknowledgement::new(StdAck::success(()));
+        let mut msg = mock_ibc_packet_ack(channel_id, &1u32, ack).unwrap();
+        msg.original_packet.data = data;
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(vec![("action", "acknowledge_dispatch")], res.attributes)
+    }
+
+    #[test]
+    fn provenance_query_results() {
+        let channel_id = "channel-1";
+        let mut deps = setup();
+        connect(deps.as_mut(), channel_id);
+        who_am_i_response(deps.as_mut(), channel_id, "account-1");
+
+        let lookup = ProvenanceQueryMsg::MarkerByDenom {
+            denom: "nhash".into(),
+        };
+        let query_msg = ExecuteMsg::ProvenanceQuery {
+            channel_id: channel_id.into(),
+            msgs: vec![lookup.clone()],
+            callback_id: None,
+            fee: None,
+        };
+        let info = mock_info("anyone", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, query_msg).unwrap();
+        assert!(res.attributes.contains(&Attribute::new("request_id", "1")));
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            o => panic!("Unexpected message: {:?}", o),
+        };
+        assert_eq!(
+            IcaVersion::V2.decode_packet(&data).unwrap(),
+            PacketMsg::ProvenanceQuery {
+                sender: "anyone".into(),
+                msgs: vec![lookup.to_request().unwrap()],
+                callback_id: None,
+            }
+        );
+
+        // the result is kept like the one of an IbcQuery
+        let ack = StdAck::Result(Binary::from(b"marker"));
+        let ack = IbcAcknowledgement::new(ack.ack());
+        let mut msg = mock_ibc_packet_ack(channel_id, &1u32, ack).unwrap();
+        msg.original_packet.data = data;
+        msg.original_packet.sequence = 2;
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        let q = QueryMsg::QueryResult { request_id: 1 };
+        let r = query(deps.as_ref(), mock_env(), q).unwrap();
+        let result: QueryResultResponse = from_slice(&r).unwrap();
+        assert_eq!(
+            result.response,
+            Some(StdAck::Result(Binary::from(b"marker")))
+        );
+    }
+
+    #[test]
+    fn keep_failed_callbacks_for_retry() {
+        let channel_id = "channel-1234";
+        let mut deps = setup();
+        connect(deps.as_mut(), channel_id);
+        who_am_i_response(deps.as_mut(), channel_id, "account-789");
+
+        let handle_msg = ExecuteMsg::SendMsgs {
+            channel_id: channel_id.into(),
+            msgs: vec![],
+            callback_id: Some("cb".into()),
+            fee: None,
+        };
+        let info = mock_info(CREATOR, &[]);
+        let mut res = execute(deps.as_mut(), mock_env(), info, handle_msg).unwrap();
+        let data = match res.messages.swap_remove(0).msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data,
+            o => panic!("Unexpected message: {:?}", o),
+        };
+        let ack = IbcAcknowledgement::new(StdAck::success(()));
+        let mut msg = mock_ibc_packet_ack(channel_id, &1u32, ack).unwrap();
+        msg.original_packet.data = data;
+        msg.original_packet.sequence = 2;
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+
+        // the callback can not use more than the configured gas, nor fail the ack
+        assert_eq!(1, res.messages.len());
+        let callback = &res.messages[0];
+        assert_eq!(callback.id, CALLBACK_REPLY_ID);
+        assert_eq!(callback.reply_on, ReplyOn::Always);
+        assert_eq!(callback.gas_limit, Some(DEFAULT_CALLBACK_GAS_LIMIT));
+        // and tells which packet it is about
+        let received = parse_callback(&callback.msg);
+        assert_eq!(received.version, CALLBACK_VERSION);
+        let expected = CallbackPacket {
+            channel_id: channel_id.into(),
+            sequence: Some(2),
+            kind: PacketKind::Dispatch,
+            remote_account: Some("account-789".into()),
+        };
+        assert_eq!(received.packet, Some(expected));
+
+        // if it fails, it is kept
+        let failure = Reply {
+            id: CALLBACK_REPLY_ID,
+            result: SubMsgResult::Err("out of gas".into()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), failure).unwrap();
+        assert_eq!(res.attributes[2], ("failure_id", "1"));
+        let query_msg = QueryMsg::FailedCallbacks {
+            address: CREATOR.into(),
+        };
+        let res: FailedCallbacksResponse =
+            from_slice(&query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap()).unwrap();
+        assert_eq!(1, res.callbacks.len());
+        assert_eq!(res.callbacks[0].callback.id, "cb");
+        assert_eq!(res.callbacks[0].error, "out of gas");
+
+        // only the receiver can retry it, without a gas limit
+        let retry = ExecuteMsg::RetryCallback { failure_id: 1 };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("someone", &[]),
+            retry.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::UnknownFailedCallback(1));
+        let res = execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), retry).unwrap();
+        assert_eq!(res.messages[0].msg, callback.msg);
+        assert_eq!(res.messages[0].gas_limit, None);
+        let res: FailedCallbacksResponse =
+            from_slice(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(res.callbacks, vec![]);
+
+        // the admin may change the gas limit
+        let msg = ExecuteMsg::UpdateCallbackGasLimit { gas_limit: 100_000 };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("someone", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), msg).unwrap();
+        let cfg = crate::state::CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(cfg.callback_gas_limit, 100_000);
+
+        // the sender is told about timeouts as well
+        let handle_msg = ExecuteMsg::SendMsgs {
+            channel_id: channel_id.into(),
+            msgs: vec![],
+            callback_id: Some("late".into()),
+            fee: None,
+        };
+        let info = mock_info(CREATOR, &[]);
+        let mut res = execute(deps.as_mut(), mock_env(), info, handle_msg).unwrap();
+        let data = match res.messages.swap_remove(0).msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data,
+            o => panic!("Unexpected message: {:?}", o),
+        };
+        let mut msg = mock_ibc_packet_timeout(channel_id, &1u32).unwrap();
+        msg.packet.data = data;
+        msg.packet.sequence = 3;
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let received = parse_callback(&res.messages[0].msg);
+        assert_eq!(received.id, "late");
+        assert_eq!(received.msg.error_code(), Some(ErrorCode::Timeout));
+        let packet = received.packet.unwrap();
+        assert_eq!(packet.kind, PacketKind::Timeout);
+        assert_eq!(packet.sequence, Some(3));
+    }
+
+    #[test]
+    fn timeout_after_close_keeps_the_version() {
+        let mut deps = setup();
+        let channel_id = "channel-3";
+        let handshake = mock_ibc_channel_open_init(channel_id, APP_ORDER, "");
+        ibc_channel_open(deps.as_mut(), mock_env(), handshake).unwrap();
+        let handshake = mock_ibc_channel_connect_ack(channel_id, APP_ORDER, "simple-ica-v3");
+        ibc_channel_connect(deps.as_mut(), mock_env(), handshake).unwrap();
+
+        // a v3 packet is in flight when the channel closes
+        let handle_msg = ExecuteMsg::SendMsgs {
+            channel_id: channel_id.into(),
+            msgs: vec![],
+            callback_id: Some("late".into()),
+            fee: None,
+        };
+        let info = mock_info(CREATOR, &[]);
+        let mut res = execute(deps.as_mut(), mock_env(), info, handle_msg).unwrap();
+        let data = match res.messages.swap_remove(0).msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data,
+            o => panic!("Unexpected message: {:?}", o),
+        };
+        let close = mock_ibc_channel_close_init(channel_id, APP_ORDER, "simple-ica-v3");
+        ibc_channel_close(deps.as_mut(), mock_env(), close).unwrap();
+
+        // it is still decoded as v3, so the sender hears about the timeout
+        let mut msg = mock_ibc_packet_timeout(channel_id, &1u32).unwrap();
+        msg.packet.data = data;
+        msg.packet.sequence = 2;
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let received = parse_callback(&res.messages[0].msg);
+        assert_eq!(received.id, "late");
+        assert_eq!(received.msg.error_code(), Some(ErrorCode::Timeout));
+    }
+
+    #[test]
+    fn each_callback_has_its_own_reply() {
+        let mut deps = setup();
+        let callback = |id: &str| ReceiveIcaResponseMsg {
+            id: id.into(),
+            msg: StdAck::Result(Binary::from(b"{}")),
+            version: CALLBACK_VERSION,
+            packet: None,
+        };
+        let first = callback_msg(deps.as_mut().storage, "alice".into(), callback("first")).unwrap();
+        let second = callback_msg(deps.as_mut().storage, "bob".into(), callback("second")).unwrap();
+        assert_eq!(first.reply_on, ReplyOn::Always);
+        assert_eq!(second.reply_on, ReplyOn::Always);
+
+        // replies come in order, so a failure of the second is kept as the second
+        let success = Reply {
+            id: CALLBACK_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        };
+        reply(deps.as_mut(), mock_env(), success).unwrap();
+        let failure = Reply {
+            id: CALLBACK_REPLY_ID,
+            result: SubMsgResult::Err("out of gas".into()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), failure.clone()).unwrap();
+        assert_eq!(res.attributes[1], ("receiver", "bob"));
+        let query_msg = QueryMsg::FailedCallbacks {
+            address: "bob".into(),
+        };
+        let res: FailedCallbacksResponse =
+            from_slice(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(res.callbacks[0].callback.id, "second");
+
+        // and nothing is left in flight
+        reply(deps.as_mut(), mock_env(), failure).unwrap_err();
+    }
+
+    fn parse_callback(msg: &CosmosMsg) -> ReceiveIcaResponseMsg {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        enum ReceiverMsg {
+            ReceiveIcaResponse(ReceiveIcaResponseMsg),
+        }
+        match msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+                let ReceiverMsg::ReceiveIcaResponse(callback) = from_slice(msg).unwrap();
+                callback
+            }
+            o => panic!("Unexpected message: {:?}", o),
+        }
+    }
+
+    #[test]
+    fn pay_relayer_fees() {
+        let mut deps = setup();
+        let channel_id = "channel-29";
+        let fee = IbcFee {
+            recv_fee: coins(100, "uatom"),
+            ack_fee: coins(50, "uatom"),
+            timeout_fee: coins(30, "uatom"),
+        };
+
+        // the WhoAmI is the first packet on the channel
+        connect(deps.as_mut(), channel_id);
+        let send_msgs = |channel_id: &str| ExecuteMsg::SendMsgs {
+            channel_id: channel_id.into(),
+            msgs: vec![],
+            callback_id: None,
+            fee: Some(fee.clone()),
+        };
+
+        // fees must be paid in full
+        let info = mock_info(CREATOR, &coins(150, "uatom"));
+        let err = execute(deps.as_mut(), mock_env(), info, send_msgs(channel_id)).unwrap_err();
+        assert_eq!(err, ContractError::FeeFundsMismatch("180uatom".into()));
+
+        // the fee is paid right before the packet
+        let info = mock_info(CREATOR, &coins(180, "uatom"));
+        let res = execute(deps.as_mut(), mock_env(), info, send_msgs(channel_id)).unwrap();
+        assert_eq!(2, res.messages.len());
+        let contract = mock_env().contract.address;
+        let pay_fee = fee.pay_packet_fee_msg("my_port", channel_id, contract.as_str());
+        assert_eq!(res.messages[0].msg, pay_fee);
+        assert!(matches!(
+            res.messages[1].msg,
+            CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
+        ));
+        assert!(res.attributes.contains(&Attribute::new("sequence", "2")));
+        let q = QueryMsg::PacketFee {
+            channel_id: channel_id.into(),
+            sequence: 2,
+        };
+        let r = query(deps.as_ref(), mock_env(), q).unwrap();
+        let escrow: PacketFeeResponse = from_slice(&r).unwrap();
+        assert_eq!(escrow.payer, CREATOR);
+        assert_eq!(escrow.fee, fee);
+
+        // anyone may pay for their queries
+        let query_msg = ExecuteMsg::IbcQuery {
+            channel_id: channel_id.into(),
+            msgs: vec![],
+            callback_id: None,
+            fee: Some(fee.clone()),
+        };
+        let info = mock_info("anyone", &coins(180, "uatom"));
+        execute(deps.as_mut(), mock_env(), info.clone(), query_msg.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), info, query_msg).unwrap();
+        let q = QueryMsg::ListPacketFees {
+            channel_id: channel_id.into(),
+        };
+        let r = query(deps.as_ref(), mock_env(), q.clone()).unwrap();
+        let escrows: ListPacketFeesResponse = from_slice(&r).unwrap();
+        let sequences: Vec<_> = escrows.fees.iter().map(|f| f.sequence).collect();
+        assert_eq!(sequences, vec![2, 3, 4]);
+
+        // an ack returns the timeout fee
+        let packet = PacketMsg::Dispatch {
+            sender: CREATOR.into(),
+            msgs: vec![],
+            callback_id: None,
+        };
+        let ack = IbcAcknowledgement::new(StdAck::success(()));
+        let mut msg = mock_ibc_packet_ack(channel_id, &packet, ack).unwrap();
+        msg.original_packet.sequence = 2;
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("fee_refund", "30uatom")));
+        // a timeout the receive and ack fees
+        let mut msg = mock_ibc_packet_timeout(channel_id, &PacketMsg::Balances {}).unwrap();
+        msg.packet.sequence = 3;
+        ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        // and closing the channel all of them
+        let close = mock_ibc_channel_close_init(channel_id, APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_close(deps.as_mut(), mock_env(), close).unwrap();
+        let r = query(deps.as_ref(), mock_env(), q).unwrap();
+        let escrows: ListPacketFeesResponse = from_slice(&r).unwrap();
+        assert!(escrows.fees.is_empty());
+
+        let q = QueryMsg::FeeRefund {
+            address: "anyone".into(),
+        };
+        let r = query(deps.as_ref(), mock_env(), q).unwrap();
+        let refund: FeeRefundResponse = from_slice(&r).unwrap();
+        assert_eq!(refund.amount, coins(330, "uatom"));
+
+        // which the payer can claim
+        let info = mock_info(CREATOR, &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::ClaimFeeRefund {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: CREATOR.into(),
+                amount: coins(30, "uatom"),
+            }
+            .into()
+        );
+        let info = mock_info(CREATOR, &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::ClaimFeeRefund {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NoFeeRefund);
+    }
+
+    #[test]
+    fn send_funds_without_ibc_hooks() {
+        let mut deps = mock_dependencies();
+        let info = mock_info(CREATOR, &[]);
+        instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg::default()).unwrap();
+        connect(deps.as_mut(), "channel-1");
+        who_am_i_response(deps.as_mut(), "channel-1", "account-1");
+
+        // the transfers are sent, but not followed
+        let msg = ExecuteMsg::SendFunds {
+            ica_channel_id: "channel-1".into(),
+            transfer_channel_id: "transfer-2".into(),
+        };
+        let info = mock_info("someone", &coins(5, "uatom"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(IbcMsg::Transfer {
+                channel_id: "transfer-2".into(),
+                to_address: "account-1".into(),
+                amount: coin(5, "uatom"),
+                timeout: mock_env().block.time.plus_seconds(PACKET_LIFETIME).into(),
+            })]
+        );
+
+        // so nothing may wait for them
+        let msg = ExecuteMsg::SendFundsAndMsgs {
+            ica_channel_id: "channel-1".into(),
+            transfer_channel_id: "transfer-2".into(),
+            msgs: vec![],
+            callback_id: None,
+        };
+        let info = mock_info(CREATOR, &coins(5, "uatom"));
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::IbcHooksDisabled);
+
+        // unless an owner enables ibc-hooks
+        let enable = ExecuteMsg::UpdateIbcHooks { enabled: true };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("someone", &[]),
+            enable.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, SimpleIcaError::NotOwner("someone".into()).into());
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), enable).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(res.messages[0].id, TRANSFER_REPLY_ID);
+    }
+
     #[test]
     fn send_remote_funds() {
         let reflect_channel_id = "channel-1234";
@@ -442,20 +1594,676 @@ mod tests {
         let info = mock_info(CREATOR, &coins(12344, "utrgd"));
         let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
         assert_eq!(1, res.messages.len());
+        let transfer = MsgTransfer {
+            source_channel: transfer_channel_id.into(),
+            token: coin(12344, "utrgd"),
+            sender: MOCK_CONTRACT_ADDR.into(),
+            receiver: remote_addr.into(),
+            timeout_timestamp: mock_env().block.time.plus_seconds(PACKET_LIFETIME).nanos(),
+            memo: callback_memo(MOCK_CONTRACT_ADDR),
+        };
+        assert_eq!(
+            res.messages[0],
+            SubMsg::reply_on_success(transfer.to_msg(), TRANSFER_REPLY_ID)
+        );
+        transfer_reply(deps.as_mut(), 1, true);
+
+        // every coin is sent with its own transfer
+        let msg = ExecuteMsg::SendFunds {
+            ica_channel_id: reflect_channel_id.into(),
+            transfer_channel_id: transfer_channel_id.into(),
+        };
+        let info = mock_info("someone", &[coin(5, "uatom"), coin(7, "utrgd")]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(2, res.messages.len());
+        transfer_reply(deps.as_mut(), 2, true);
+        transfer_reply(deps.as_mut(), 3, true);
+        let pending = QueryMsg::PendingTransfers {
+            address: "someone".into(),
+        };
+        let res: PendingTransfersResponse =
+            from_slice(&query(deps.as_ref(), mock_env(), pending.clone()).unwrap()).unwrap();
+        let amounts: Vec<_> = res.transfers.iter().map(|t| t.amount.clone()).collect();
+        assert_eq!(amounts, vec![coin(5, "uatom"), coin(7, "utrgd")]);
+        assert_eq!(res.transfers[1].sequence, 3);
+
+        // the funds of failed transfers can be claimed by their sender
+        transfer_ack(deps.as_mut(), 1, true);
+        transfer_ack(deps.as_mut(), 2, false);
+        let timeout = SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcTimeout {
+            channel: transfer_channel_id.into(),
+            sequence: 3,
+        });
+        sudo(deps.as_mut(), mock_env(), timeout).unwrap();
+        let res: PendingTransfersResponse =
+            from_slice(&query(deps.as_ref(), mock_env(), pending).unwrap()).unwrap();
+        assert_eq!(res.transfers, vec![]);
+        let refund = QueryMsg::TransferRefund {
+            address: "someone".into(),
+        };
+        let res: TransferRefundResponse =
+            from_slice(&query(deps.as_ref(), mock_env(), refund.clone()).unwrap()).unwrap();
+        assert_eq!(res.amount, vec![coin(5, "uatom"), coin(7, "utrgd")]);
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CREATOR, &[]),
+            ExecuteMsg::ClaimRefund {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NoTransferRefund);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("someone", &[]),
+            ExecuteMsg::ClaimRefund {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "someone".into(),
+                amount: vec![coin(5, "uatom"), coin(7, "utrgd")],
+            })
+        );
+        let res: TransferRefundResponse =
+            from_slice(&query(deps.as_ref(), mock_env(), refund).unwrap()).unwrap();
+        assert_eq!(res.amount, vec![]);
+    }
+
+    // the reply to a transfer, with the sequence in the data or the events
+    fn transfer_reply(deps: DepsMut, sequence: u64, in_data: bool) {
+        let mut response = SubMsgResponse {
+            events: vec![],
+            data: None,
+        };
+        if in_data {
+            response.data = Some(Binary::from(vec![0x08, sequence as u8]));
+        } else {
+            response.events =
+                vec![Event::new("send_packet")
+                    .add_attribute("packet_sequence", sequence.to_string())];
+        }
+        let msg = Reply {
+            id: TRANSFER_REPLY_ID,
+            result: SubMsgResult::Ok(response),
+        };
+        reply(deps, mock_env(), msg).unwrap();
+    }
+
+    fn transfer_ack(deps: DepsMut, sequence: u64, success: bool) -> Response {
+        let msg = SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcAck {
+            channel: "transfer-2".into(),
+            sequence,
+            ack: "".into(),
+            success,
+        });
+        sudo(deps, mock_env(), msg).unwrap()
+    }
+
+    #[test]
+    fn send_funds_and_msgs() {
+        let channel_id = "channel-1234";
+        let remote_addr = "account-789";
+
+        let mut deps = setup();
+        connect(deps.as_mut(), channel_id);
+        who_am_i_response(deps.as_mut(), channel_id, remote_addr);
+
+        let msgs: Vec<CosmosMsg> = vec![BankMsg::Send {
+            to_address: "my-friend".into(),
+            amount: coins(100, "uatom"),
+        }
+        .into()];
+        let funds = vec![coin(100, "uatom"), coin(5, "uosmo")];
+        let msg = ExecuteMsg::SendFundsAndMsgs {
+            ica_channel_id: channel_id.into(),
+            transfer_channel_id: "transfer-2".into(),
+            msgs: msgs.clone(),
+            callback_id: Some("fund".into()),
+        };
+
+        // only the admin may dispatch messages
+        let info = mock_info("someone", &funds);
+        execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+
+        let info = mock_info(CREATOR, &funds);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        assert_eq!(res.data, Some(to_binary(&1u64).unwrap()));
+        assert_eq!(2, res.messages.len());
+        for msg in &res.messages {
+            assert_eq!(msg.id, TRANSFER_REPLY_ID);
+            assert_eq!(msg.reply_on, ReplyOn::Success);
+            match &msg.msg {
+                CosmosMsg::Stargate { type_url, .. } => {
+                    assert_eq!(type_url, "/ibc.applications.transfer.v1.MsgTransfer")
+                }
+                o => panic!("unexpected message: {:?}", o),
+            }
+        }
+        transfer_reply(deps.as_mut(), 7, true);
+        transfer_reply(deps.as_mut(), 8, false);
+
+        // transfers of others are ignored
+        let res = transfer_ack(deps.as_mut(), 3, true);
+        assert_eq!(0, res.messages.len());
+        // the messages are only sent once both transfers arrived
+        let res = transfer_ack(deps.as_mut(), 7, true);
+        assert_eq!(0, res.messages.len());
+        let res = transfer_ack(deps.as_mut(), 8, true);
+        assert_eq!(1, res.messages.len());
+        match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket {
+                channel_id: packet_channel,
+                data,
+                ..
+            }) => {
+                assert_eq!(packet_channel, channel_id);
+                let packet: PacketMsg = from_slice(data).unwrap();
+                assert_eq!(
+                    packet,
+                    PacketMsg::Dispatch {
+                        sender: CREATOR.into(),
+                        msgs: msgs.clone(),
+                        callback_id: Some("fund".into()),
+                    }
+                );
+            }
+            o => panic!("unexpected message: {:?}", o),
+        }
+        assert_eq!(LAST_SEQUENCES.load(&deps.storage, channel_id).unwrap(), 2);
+
+        // if a transfer fails, the messages are dropped and the sender told so
+        let info = mock_info(CREATOR, &funds);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(res.data, Some(to_binary(&2u64).unwrap()));
+        transfer_reply(deps.as_mut(), 9, true);
+        transfer_reply(deps.as_mut(), 10, true);
+        let res = transfer_ack(deps.as_mut(), 9, true);
+        assert_eq!(0, res.messages.len());
+        let timeout = SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcTimeout {
+            channel: "transfer-2".into(),
+            sequence: 10,
+        });
+        let res = sudo(deps.as_mut(), mock_env(), timeout).unwrap();
+        assert_eq!(1, res.messages.len());
         match &res.messages[0].msg {
-            CosmosMsg::Ibc(IbcMsg::Transfer {
-                channel_id,
-                to_address,
-                amount,
-                timeout,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
             }) => {
-                assert_eq!(transfer_channel_id, channel_id.as_str());
-                assert_eq!(remote_addr, to_address.as_str());
-                assert_eq!(&coin(12344, "utrgd"), amount);
-                assert!(timeout.block().is_none());
-                assert!(timeout.timestamp().is_some());
+                assert_eq!(contract_addr, CREATOR);
+                #[derive(Deserialize)]
+                #[serde(rename_all = "snake_case")]
+                enum ReceiverMsg {
+                    ReceiveIcaResponse(ReceiveIcaResponseMsg),
+                }
+                let ReceiverMsg::ReceiveIcaResponse(callback) = from_slice(msg).unwrap();
+                assert_eq!(callback.id, "fund");
+                assert_eq!(callback.msg.error_code(), Some(ErrorCode::TransferFailed));
+                assert_eq!(
+                    callback.msg,
+                    StdAck::Error("transfer_failed: transfer of 5uosmo failed".into())
+                );
             }
             o => panic!("unexpected message: {:?}", o),
         }
+        assert_eq!(LAST_SEQUENCES.load(&deps.storage, channel_id).unwrap(), 2);
+    }
+
+    #[test]
+    fn remote_trade_after_transfer() {
+        let channel_id = "channel-1234";
+        let remote_addr = "account-789";
+
+        let mut deps = setup();
+        connect(deps.as_mut(), channel_id);
+        who_am_i_response(deps.as_mut(), channel_id, remote_addr);
+
+        let trade = Trade {
+            exchange: "exchange".into(),
+            offer: coin(100, "ibc/27394FB092D2ECCD"),
+        };
+        let msg = ExecuteMsg::RemoteTrade {
+            ica_channel_id: channel_id.into(),
+            transfer_channel_id: Some("transfer-2".into()),
+            trade: trade.clone(),
+            callback_id: Some("swap".into()),
+        };
+        let info = mock_info(CREATOR, &coins(100, "uatom"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(res.data, Some(to_binary(&1u64).unwrap()));
+        assert_eq!(1, res.messages.len());
+        transfer_reply(deps.as_mut(), 7, true);
+
+        // the trade is sent once the funds arrived
+        let res = transfer_ack(deps.as_mut(), 7, true);
+        assert_eq!(1, res.messages.len());
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            o => panic!("unexpected message: {:?}", o),
+        };
+        let packet: PacketMsg = from_slice(&data).unwrap();
+        assert_eq!(
+            packet,
+            PacketMsg::Trade {
+                sender: CREATOR.into(),
+                exchange: "exchange".into(),
+                offer: trade.offer.clone(),
+                callback_id: Some("swap".into()),
+            }
+        );
+
+        // and the callback gets what was traded
+        let response = TradeResponse {
+            sent: trade.offer.clone(),
+            received: coin(100, "nhash"),
+        };
+        let ack = IbcAcknowledgement::new(StdAck::success(&response));
+        let mut msg = mock_ibc_packet_ack(channel_id, &1u32, ack).unwrap();
+        msg.original_packet.data = data;
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+                #[derive(Deserialize)]
+                #[serde(rename_all = "snake_case")]
+                enum ReceiverMsg {
+                    ReceiveIcaResponse(ReceiveIcaResponseMsg),
+                }
+                let ReceiverMsg::ReceiveIcaResponse(callback) = from_slice(msg).unwrap();
+                assert_eq!(callback.id, "swap");
+                assert_eq!(callback.packet.unwrap().kind, PacketKind::Trade);
+                let traded: TradeResponse = callback.msg.unwrap_into();
+                assert_eq!(traded, response);
+            }
+            o => panic!("unexpected message: {:?}", o),
+        }
+
+        // funds the account already holds are traded right away, nothing may be sent along
+        let msg = ExecuteMsg::RemoteTrade {
+            ica_channel_id: channel_id.into(),
+            transfer_channel_id: None,
+            trade,
+            callback_id: None,
+        };
+        let info = mock_info(CREATOR, &coins(100, "uatom"));
+        execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        let info = mock_info(CREATOR, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        assert!(matches!(
+            res.messages[0].msg,
+            CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
+        ));
+    }
+
+    #[test]
+    fn broadcast_with_one_callback() {
+        let mut deps = setup();
+        for channel_id in ["channel-1", "channel-2"] {
+            connect(deps.as_mut(), channel_id);
+            who_am_i_response(deps.as_mut(), channel_id, "account");
+        }
+
+        let msgs = vec![BankMsg::Send {
+            to_address: "my-friend".into(),
+            amount: coins(7, "uatom"),
+        }
+        .into()];
+        let broadcast = |channel_ids: &[&str]| ExecuteMsg::BroadcastMsgs {
+            channel_ids: channel_ids.iter().map(|id| id.to_string()).collect(),
+            msgs: msgs.clone(),
+            callback_id: Some("everywhere".into()),
+        };
+        // the channels must be known and listed once
+        let info = mock_info(CREATOR, &[]);
+        let msg = broadcast(&["channel-1", "channel-1"]);
+        execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        let msg = broadcast(&["channel-1", "channel-9"]);
+        execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        let msg = broadcast(&[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        // only the admin may broadcast
+        let msg = broadcast(&["channel-1", "channel-2"]);
+        execute(deps.as_mut(), mock_env(), mock_info("someone", &[]), msg).unwrap_err();
+
+        // unit tests keep the state of failed calls, so take the sequences from the query
+        let msg = broadcast(&["channel-1", "channel-2"]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let data: Vec<Binary> = res
+            .messages
+            .iter()
+            .map(|msg| match &msg.msg {
+                CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+                o => panic!("Unexpected message: {:?}", o),
+            })
+            .collect();
+        assert_eq!(2, data.len());
+        let batch_id: u64 = from_slice(&res.data.unwrap()).unwrap();
+        let r = query(deps.as_ref(), mock_env(), QueryMsg::Broadcast { batch_id }).unwrap();
+        let status: BroadcastStatusResponse = from_slice(&r).unwrap();
+        assert_eq!(status.packets.len(), 2);
+        assert!(status.packets.iter().all(|packet| packet.result.is_none()));
+
+        // the first channel acks, no callback yet
+        let packet = &status.packets[0];
+        let ack = IbcAcknowledgement::new(StdAck::success(DispatchResponse { results: vec![] }));
+        let mut msg = mock_ibc_packet_ack(&packet.channel_id, &1u32, ack).unwrap();
+        msg.original_packet.data = data[0].clone();
+        msg.original_packet.sequence = packet.sequence;
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(0, res.messages.len());
+        assert_eq!(res.attributes[0], ("action", "acknowledge_broadcast"));
+        let r = query(deps.as_ref(), mock_env(), QueryMsg::Broadcast { batch_id }).unwrap();
+        let partial: BroadcastStatusResponse = from_slice(&r).unwrap();
+        assert!(partial.packets[0].result.is_some());
+        assert!(partial.packets[1].result.is_none());
+
+        // the second times out, which completes the broadcast
+        let packet = &status.packets[1];
+        let mut msg = mock_ibc_packet_timeout(&packet.channel_id, &1u32).unwrap();
+        msg.packet.data = data[1].clone();
+        msg.packet.sequence = packet.sequence;
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let received = parse_callback(&res.messages[0].msg);
+        assert_eq!(received.id, "everywhere");
+        assert_eq!(received.packet, None);
+        let response: BroadcastResponse = from_slice(&received.msg.unwrap()).unwrap();
+        assert_eq!(response.results.len(), 2);
+        assert_eq!(response.results[0].channel_id, "channel-1");
+        assert!(matches!(response.results[0].result, StdAck::Result(_)));
+        assert_eq!(response.results[1].channel_id, "channel-2");
+        assert_eq!(
+            response.results[1].result.error_code(),
+            Some(ErrorCode::Timeout)
+        );
+
+        // finished broadcasts are removed
+        query(deps.as_ref(), mock_env(), QueryMsg::Broadcast { batch_id }).unwrap_err();
+    }
+
+    #[test]
+    fn broadcast_learns_unknown_sequences() {
+        let mut deps = setup();
+        let channel_id = "channel-1";
+        connect(deps.as_mut(), channel_id);
+        who_am_i_response(deps.as_mut(), channel_id, "account");
+        // like a channel connected by an older version
+        LAST_SEQUENCES.remove(deps.as_mut().storage, channel_id);
+        CHANNEL_PORTS.remove(deps.as_mut().storage, channel_id);
+
+        let msg = ExecuteMsg::BroadcastMsgs {
+            channel_ids: vec![channel_id.into()],
+            msgs: vec![],
+            callback_id: Some("everywhere".into()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        assert_eq!(res.messages[0].id, BROADCAST_REPLY_ID);
+        assert_eq!(res.messages[0].reply_on, ReplyOn::Success);
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            o => panic!("Unexpected message: {:?}", o),
+        };
+        let batch_id: u64 = from_slice(&res.data.unwrap()).unwrap();
+
+        // the reply tells the sequence, and the channel counts its packets from then on
+        let sent = Event::new("send_packet")
+            .add_attribute("packet_src_port", "wasm.contract")
+            .add_attribute("packet_src_channel", channel_id)
+            .add_attribute("packet_sequence", "5");
+        let sent = Reply {
+            id: BROADCAST_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![sent],
+                data: None,
+            }),
+        };
+        reply(deps.as_mut(), mock_env(), sent).unwrap();
+        assert_eq!(LAST_SEQUENCES.load(&deps.storage, channel_id).unwrap(), 5);
+        assert_eq!(
+            CHANNEL_PORTS.load(&deps.storage, channel_id).unwrap(),
+            "wasm.contract"
+        );
+        let r = query(deps.as_ref(), mock_env(), QueryMsg::Broadcast { batch_id }).unwrap();
+        let status: BroadcastStatusResponse = from_slice(&r).unwrap();
+        assert_eq!(status.packets[0].sequence, 5);
+
+        // so the ack completes the broadcast
+        let ack = IbcAcknowledgement::new(StdAck::success(DispatchResponse { results: vec![] }));
+        let mut msg = mock_ibc_packet_ack(channel_id, &1u32, ack).unwrap();
+        msg.original_packet.data = data;
+        msg.original_packet.sequence = 5;
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let received = parse_callback(&res.messages[0].msg);
+        let response: BroadcastResponse = from_slice(&received.msg.unwrap()).unwrap();
+        assert_eq!(response.results[0].sequence, 5);
+    }
+
+    #[test]
+    fn run_scheduled_jobs_on_crank() {
+        let mut deps = setup();
+        for channel_id in ["channel-1", "channel-2"] {
+            connect(deps.as_mut(), channel_id);
+            who_am_i_response(deps.as_mut(), channel_id, "account");
+        }
+        let msg = ExecuteMsg::UpdateCrankReward {
+            reward: coins(10, "ucrank"),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("someone", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), msg).unwrap();
+
+        let now = mock_env().block.time;
+        let schedule = |channel_id: &str| ExecuteMsg::ScheduleMsgs {
+            channel_id: channel_id.into(),
+            msgs: vec![BankMsg::Send {
+                to_address: "payee".into(),
+                amount: coins(100, "uatom"),
+            }
+            .into()],
+            callback_id: None,
+            schedule: Schedule {
+                start: now.plus_seconds(100),
+                interval: 60,
+                repeat: 2,
+            },
+        };
+        // the reward of both runs must be paid up front
+        let info = mock_info(CREATOR, &coins(10, "ucrank"));
+        let err = execute(deps.as_mut(), mock_env(), info, schedule("channel-1")).unwrap_err();
+        assert_eq!(err, ContractError::RewardFundsMismatch("20ucrank".into()));
+        let info = mock_info(CREATOR, &coins(20, "ucrank"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            schedule("channel-1"),
+        )
+        .unwrap();
+        execute(deps.as_mut(), mock_env(), info, schedule("channel-2")).unwrap();
+
+        // nothing is due yet
+        let cranker = mock_info("cranker", &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            cranker.clone(),
+            ExecuteMsg::Crank {},
+        );
+        assert_eq!(err.unwrap_err(), ContractError::NoJobsDue);
+
+        // the first runs are sent, and the cranker paid for both
+        let mut env = mock_env();
+        env.block.time = now.plus_seconds(100);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            cranker.clone(),
+            ExecuteMsg::Crank {},
+        )
+        .unwrap();
+        assert_eq!(3, res.messages.len());
+        assert!(matches!(
+            res.messages[0].msg,
+            CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
+        ));
+        assert_eq!(
+            res.messages[2].msg,
+            BankMsg::Send {
+                to_address: "cranker".into(),
+                amount: coins(20, "ucrank"),
+            }
+            .into()
+        );
+        let q = QueryMsg::UpcomingJobs { limit: None };
+        let upcoming: UpcomingJobsResponse =
+            from_slice(&query(deps.as_ref(), env.clone(), q).unwrap()).unwrap();
+        assert_eq!(upcoming.jobs.len(), 2);
+        assert_eq!(upcoming.jobs[0].next_run, now.plus_seconds(160));
+        assert_eq!(upcoming.jobs[0].runs, 1);
+        assert_eq!(upcoming.jobs[0].runs_left, 1);
+
+        // the second channel closes before the last run, which refunds its reward
+        let close = mock_ibc_channel_close_init("channel-2", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_close(deps.as_mut(), mock_env(), close).unwrap();
+        env.block.time = now.plus_seconds(500);
+        let res = execute(deps.as_mut(), env.clone(), cranker, ExecuteMsg::Crank {}).unwrap();
+        assert_eq!(3, res.messages.len());
+        assert_eq!(
+            res.messages[1].msg,
+            BankMsg::Send {
+                to_address: CREATOR.into(),
+                amount: coins(10, "ucrank"),
+            }
+            .into()
+        );
+        assert!(res.attributes.contains(&Attribute::new("failed_job", "2")));
+
+        let q = QueryMsg::UpcomingJobs { limit: None };
+        let upcoming: UpcomingJobsResponse =
+            from_slice(&query(deps.as_ref(), env.clone(), q).unwrap()).unwrap();
+        assert_eq!(upcoming.jobs.len(), 0);
+        let q = QueryMsg::FinishedJobs {
+            start_after: None,
+            limit: None,
+        };
+        let finished: FinishedJobsResponse =
+            from_slice(&query(deps.as_ref(), env, q).unwrap()).unwrap();
+        assert_eq!(finished.jobs.len(), 2);
+        assert_eq!(finished.jobs[0].runs, 2);
+        assert_eq!(finished.jobs[0].error, None);
+        assert_eq!(finished.jobs[1].runs, 1);
+        assert_eq!(
+            finished.jobs[1].error,
+            Some("Channel channel-2 was closed".into())
+        );
+    }
+
+    #[test]
+    fn jobs_stop_without_an_admin_sender() {
+        let mut deps = setup();
+        let channel_id = "channel-1";
+        connect(deps.as_mut(), channel_id);
+        who_am_i_response(deps.as_mut(), channel_id, "account");
+        let msg = ExecuteMsg::UpdateCrankReward {
+            reward: coins(10, "ucrank"),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), msg).unwrap();
+        let operator = Addr::unchecked("operator");
+        ADMINS
+            .set(deps.as_mut().storage, &operator, AdminRole::Operator)
+            .unwrap();
+
+        let now = mock_env().block.time;
+        let schedule = ExecuteMsg::ScheduleMsgs {
+            channel_id: channel_id.into(),
+            msgs: vec![],
+            callback_id: None,
+            schedule: Schedule {
+                start: now.plus_seconds(100),
+                interval: 60,
+                repeat: 2,
+            },
+        };
+        let info = mock_info(operator.as_str(), &coins(20, "ucrank"));
+        execute(deps.as_mut(), mock_env(), info.clone(), schedule.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), info, schedule).unwrap();
+
+        // only the sender and the owners may cancel a job
+        let cancel = ExecuteMsg::CancelJob { job_id: 1 };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("someone", &[]),
+            cancel.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::SimpleIca(SimpleIcaError::NotOwner("someone".into()))
+        );
+        let res = execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), cancel).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: operator.to_string(),
+                amount: coins(20, "ucrank"),
+            }
+            .into()
+        );
+        let cancel = ExecuteMsg::CancelJob { job_id: 1 };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), cancel).unwrap_err();
+        assert_eq!(err, ContractError::UnknownJob(1));
+
+        // a sender who is no admin anymore can not send, the job ends with a refund
+        let renounce = ExecuteMsg::Admin(AdminExecuteMsg::RenounceAdmin {});
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(operator.as_str(), &[]),
+            renounce,
+        )
+        .unwrap();
+        let mut env = mock_env();
+        env.block.time = now.plus_seconds(100);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("cranker", &[]),
+            ExecuteMsg::Crank {},
+        )
+        .unwrap();
+        assert_eq!(1, res.messages.len());
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: operator.to_string(),
+                amount: coins(20, "ucrank"),
+            }
+            .into()
+        );
+        assert!(res.attributes.contains(&Attribute::new("failed_job", "2")));
+
+        let q = QueryMsg::FinishedJobs {
+            start_after: None,
+            limit: None,
+        };
+        let finished: FinishedJobsResponse =
+            from_slice(&query(deps.as_ref(), env, q).unwrap()).unwrap();
+        assert_eq!(finished.jobs.len(), 2);
+        assert_eq!(finished.jobs[0].error, Some("Cancelled by creator".into()));
+        assert_eq!(
+            finished.jobs[1].error,
+            Some("Only admins may do this, not operator".into())
+        );
     }
 }
@@ -1,5 +1,14 @@
+pub mod broadcast;
+pub mod callback;
 pub mod contract;
 pub mod error;
+pub mod fee;
 pub mod ibc;
+pub mod migrate;
 pub mod msg;
+pub mod provenance;
+pub mod schedule;
 pub mod state;
+pub mod stats;
+pub mod trade;
+pub mod transfer;
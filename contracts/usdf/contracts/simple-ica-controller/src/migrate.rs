@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Env, Order, StdResult, Storage, SubMsg};
+use cw_storage_plus::Item;
+
+use simple_ica::admin::AdminRole;
+use simple_ica::migrate::{is_older, Version};
+
+use crate::contract::{balances_packet, load_version};
+use crate::fee::SEQUENCE_REPLY_ID;
+use crate::msg::ChannelStatus;
+use crate::state::{ACCOUNTS, ADMINS, CONFIG, LAST_SEQUENCES};
+
+/// Up to 0.2, the config had one admin and no settings
+#[derive(Serialize, Deserialize)]
+struct ConfigV0_2 {
+    admin: Option<Addr>,
+}
+
+const CONFIG_V0_2: Item<ConfigV0_2> = Item::new("config");
+
+/// Moves the state of the previous version to the current layout.
+/// Deployments from before the version was stored are 0.2 or older.
+pub fn migrate_state(storage: &mut dyn Storage, previous: Option<&Version>) -> StdResult<()> {
+    if is_older(previous, &Version::new(0, 3, 0)) {
+        migrate_from_v0_2(storage)?;
+    }
+    Ok(())
+}
+
+/// Channels connected before we counted packets get a `Balances` packet, whose reply tells
+/// the sequence to count from
+pub fn sequence_probes(storage: &mut dyn Storage, env: &Env) -> StdResult<Vec<SubMsg>> {
+    let channels = ACCOUNTS
+        .range(storage, None, None, Order::Ascending)
+        .filter_map(|r| match r {
+            Ok((_, account)) if account.status == ChannelStatus::Closed => None,
+            r => Some(r.map(|(channel_id, _)| channel_id)),
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut probes = vec![];
+    for channel_id in channels {
+        if LAST_SEQUENCES.has(storage, &channel_id) {
+            continue;
+        }
+        let version = load_version(storage, &channel_id)?;
+        let msg = balances_packet(storage, env, channel_id, version)?;
+        probes.push(SubMsg::reply_on_success(msg, SEQUENCE_REPLY_ID));
+    }
+    Ok(probes)
+}
+
+fn migrate_from_v0_2(storage: &mut dyn Storage) -> StdResult<()> {
+    // the admin becomes an owner, and the settings get their defaults
+    if let Some(admin) = CONFIG_V0_2.load(storage)?.admin {
+        ADMINS.set(storage, &admin, AdminRole::Owner)?;
+    }
+    let cfg = CONFIG.load(storage)?;
+    CONFIG.save(storage, &cfg)?;
+
+    // accounts get the fields added since, like the channel status
+    let accounts = ACCOUNTS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (channel_id, account) in accounts {
+        ACCOUNTS.save(storage, &channel_id, &account)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{migrate, query, reply};
+    use crate::error::ContractError;
+    use crate::msg::{AccountResponse, LatestQueryResponse, MigrateMsg, QueryMsg};
+    use crate::state::{Config, CHANNEL_PORTS, DEFAULT_CALLBACK_GAS_LIMIT, LATEST_QUERIES};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{
+        from_binary, Binary, Event, Reply, SubMsgResponse, SubMsgResult, Timestamp,
+    };
+    use cw2::{get_contract_version, set_contract_version};
+    use simple_ica::{SimpleIcaError, StdAck};
+
+    #[test]
+    fn migrate_state_of_v0_2() {
+        let mut deps = mock_dependencies();
+        // 0.2 stored no contract version, and its state was written with the old layout
+        deps.storage.set(b"config", br#"{"admin":"creator"}"#);
+        deps.storage.set(
+            &ACCOUNTS.key("channel-1"),
+            br#"{"last_update_time":"0","remote_addr":"remote","remote_balance":[]}"#,
+        );
+        let latest = LatestQueryResponse {
+            last_update_time: Timestamp::from_seconds(7),
+            response: StdAck::Result(Binary::from(b"[]")),
+        };
+        LATEST_QUERIES
+            .save(&mut deps.storage, "channel-1", &latest)
+            .unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(res.attributes[1].value, "none");
+        // the channel was connected before we counted packets, so a packet finds its sequence
+        assert_eq!(1, res.messages.len());
+        assert_eq!(res.messages[0].id, SEQUENCE_REPLY_ID);
+        let sent = Event::new("send_packet")
+            .add_attribute("packet_src_port", "wasm.contract")
+            .add_attribute("packet_src_channel", "channel-1")
+            .add_attribute("packet_sequence", "12");
+        let sent = Reply {
+            id: SEQUENCE_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![sent],
+                data: None,
+            }),
+        };
+        reply(deps.as_mut(), mock_env(), sent).unwrap();
+        assert_eq!(LAST_SEQUENCES.load(&deps.storage, "channel-1").unwrap(), 12);
+        assert_eq!(
+            CHANNEL_PORTS.load(&deps.storage, "channel-1").unwrap(),
+            "wasm.contract"
+        );
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+
+        let creator = Addr::unchecked("creator");
+        let role = ADMINS.role(&deps.storage, &creator).unwrap();
+        assert_eq!(role, Some(AdminRole::Owner));
+        let raw = deps.storage.get(b"config").unwrap();
+        let cfg: Config = cosmwasm_std::from_slice(&raw).unwrap();
+        assert_eq!(cfg.callback_gas_limit, DEFAULT_CALLBACK_GAS_LIMIT);
+        assert!(!String::from_utf8(raw).unwrap().contains("admin"));
+
+        let raw = deps.storage.get(&ACCOUNTS.key("channel-1")).unwrap();
+        assert!(String::from_utf8(raw)
+            .unwrap()
+            .contains(r#""status":"open""#));
+        let msg = QueryMsg::Account {
+            channel_id: "channel-1".into(),
+        };
+        let account: AccountResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(account.remote_addr, Some("remote".into()));
+        assert_eq!(account.status, ChannelStatus::Open);
+
+        let kept = LATEST_QUERIES.load(&deps.storage, "channel-1").unwrap();
+        assert_eq!(kept, latest);
+
+        // migrating again changes nothing
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(res.attributes[1].value, env!("CARGO_PKG_VERSION"));
+        assert_eq!(0, res.messages.len());
+
+        // but other contracts are not migrated
+        set_contract_version(&mut deps.storage, "crates.io:cw20-base", "0.2.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        let expected = SimpleIcaError::CannotMigrate("crates.io:cw20-base 0.2.0".into());
+        assert_eq!(err, ContractError::SimpleIca(expected));
+    }
+}
@@ -1,41 +1,83 @@
-use cosmwasm_std::{Coin, CosmosMsg, Empty, QueryRequest, Timestamp};
+use cosmwasm_std::{Binary, Coin, CosmosMsg, Empty, QueryRequest, Timestamp};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use simple_ica::StdAck;
+use simple_ica::admin::AdminExecuteMsg;
+use simple_ica::fee::IbcFee;
+use simple_ica::ics20::IbcLifecycleComplete;
+use simple_ica::{ReceiveIcaResponseMsg, StdAck};
 
-use crate::state::AccountData;
+use crate::provenance::ProvenanceQueryMsg;
+use crate::state::{AccountData, QueryResult};
 
-/// This needs no info. Owner of the contract is whoever signed the InstantiateMsg.
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+/// The first owner of the contract is whoever signed the InstantiateMsg.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub struct InstantiateMsg {}
+pub struct InstantiateMsg {
+    /// Seconds after which `IbcQuery` results may be pruned, one day if unset
+    pub query_result_ttl: Option<u64>,
+    /// Seconds between two `RefreshBalances`, one hour if unset
+    pub balance_refresh_interval: Option<u64>,
+    /// The gas a callback may use, 500k if unset
+    pub callback_gas_limit: Option<u64>,
+    /// Whether our transfer channels have the ibc-hooks middleware, false if unset.
+    /// Transfers are only followed with it.
+    pub ibc_hooks: Option<bool>,
+    /// Paid to the caller of `Crank` for every scheduled run, nothing if unset
+    pub crank_reward: Option<Vec<Coin>>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    /// Changes the admin
-    UpdateAdmin {
-        admin: String,
-    },
+    /// Manages the admins. Owners change the config and the admins, operators may only
+    /// send packets.
+    Admin(AdminExecuteMsg),
     SendMsgs {
         channel_id: String,
         /// Note: we don't handle custom messages on remote chains
         msgs: Vec<CosmosMsg<Empty>>,
         /// If set, the original caller will get a callback with of the result, along with this id
         callback_id: Option<String>,
+        /// If set, these relayer fees are paid for the packet. The funds sent along must be
+        /// exactly their total. Requires the fee middleware on the channel.
+        fee: Option<IbcFee>,
+    },
+    /// Like `SendMsgs`, for hosts on Provenance (simple-ica channels only). Each message is
+    /// the JSON of a `CosmosMsg<ProvenanceMsg>`.
+    SendProvenanceMsgs {
+        channel_id: String,
+        msgs: Vec<Binary>,
+        callback_id: Option<String>,
+        fee: Option<IbcFee>,
     },
     CheckRemoteBalance {
         channel_id: String,
     },
+    /// Asks the host to send the funds it swept from the account of our closed channel
+    /// `orphaned_channel_id` (the host's channel id) to the account of `channel_id` (admin only)
+    ClaimOrphaned {
+        channel_id: String,
+        orphaned_channel_id: String,
+    },
     IbcQuery {
         channel_id: String,
         msgs: Vec<QueryRequest<Empty>>,
         /// If set, the original caller will get a callback with of the result, along with this id
         callback_id: Option<String>,
+        /// Relayer fees for the packet, like in `SendMsgs`
+        fee: Option<IbcFee>,
+    },
+    /// Like `IbcQuery`, for hosts on Provenance (simple-ica channels only)
+    ProvenanceQuery {
+        channel_id: String,
+        msgs: Vec<ProvenanceQueryMsg>,
+        callback_id: Option<String>,
+        fee: Option<IbcFee>,
     },
     /// If you sent funds to this contract, it will attempt to ibc transfer them
-    /// to the account on the remote side of this channel.
-    /// If we don't have the address yet, this fails.
+    /// to the account on the remote side of this channel, one transfer per coin.
+    /// If we don't have the address yet, this fails. With ibc-hooks, the funds of
+    /// failed transfers can be claimed with `ClaimRefund`.
     SendFunds {
         /// The channel id we use above to send the simple-ica query on
         ica_channel_id: String,
@@ -44,24 +86,194 @@ pub enum ExecuteMsg {
         /// It should connect to the same chain as the ica_channel_id does
         transfer_channel_id: String,
     },
+    /// Like `SendFunds`, then dispatches the messages once every transfer was acknowledged,
+    /// so they can use the funds (admin only). Requires `ibc_hooks` to learn about the acks.
+    SendFundsAndMsgs {
+        ica_channel_id: String,
+        transfer_channel_id: String,
+        msgs: Vec<CosmosMsg<Empty>>,
+        /// If set, the original caller will get a callback with of the result, along with this id.
+        /// If a transfer fails, the messages are not sent and the callback has the error.
+        callback_id: Option<String>,
+    },
+    /// Trades on the `exchange` contract of the remote chain with the account (admin only).
+    /// With a transfer channel, the funds sent along are transferred to the account first,
+    /// like in `SendFundsAndMsgs`, and the trade waits for them. The offer is not checked
+    /// against them, the caller names their `ibc/` denom on the remote chain. The callback gets
+    /// a `TradeResponse` with the coins sent and received.
+    RemoteTrade {
+        ica_channel_id: String,
+        transfer_channel_id: Option<String>,
+        trade: Trade,
+        callback_id: Option<String>,
+    },
+    /// Sends the messages to the accounts of all channels, one packet each (admin only).
+    /// The packets are tracked together under a batch id, which is in the `batch_id`
+    /// attribute and the data of the response.
+    BroadcastMsgs {
+        channel_ids: Vec<String>,
+        msgs: Vec<CosmosMsg<Empty>>,
+        /// If set, the original caller will get one callback with a `BroadcastResponse`
+        /// once all packets were acknowledged or timed out, instead of one per packet
+        callback_id: Option<String>,
+    },
+    /// Stores the messages to be sent by `Crank` on the schedule (admin only). The funds sent
+    /// along must be exactly the crank reward of all runs. The `job_id` attribute identifies it.
+    ScheduleMsgs {
+        channel_id: String,
+        msgs: Vec<CosmosMsg<Empty>>,
+        /// If set, the original caller will get a callback with the result of every run
+        callback_id: Option<String>,
+        schedule: Schedule,
+    },
+    /// Sends the scheduled messages that are due. Anyone may call this, and gets the crank
+    /// reward of each job it runs. Fails if nothing is due.
+    Crank {},
+    /// Stops a scheduled job and sends its sender back the rewards of the remaining runs
+    /// (job sender or owners only)
+    CancelJob {
+        job_id: u64,
+    },
+    /// Sends the sender the relayer fees that were not used by their packets
+    ClaimFeeRefund {},
+    /// Sends the sender the funds of their transfers that failed or timed out
+    ClaimRefund {},
+    /// Sends a callback to the sender again, which failed when the ack arrived
+    RetryCallback {
+        /// As listed by `FailedCallbacks`
+        failure_id: u64,
+    },
+    /// Changes the gas callbacks may use (admin only)
+    UpdateCallbackGasLimit {
+        gas_limit: u64,
+    },
+    /// Changes the crank reward of jobs scheduled from now on (admin only)
+    UpdateCrankReward {
+        reward: Vec<Coin>,
+    },
+    /// Changes how many seconds `IbcQuery` results are kept (admin only)
+    UpdateQueryResultTtl {
+        ttl: u64,
+    },
+    /// Changes how many seconds must pass between two `RefreshBalances` (admin only)
+    UpdateBalanceRefreshInterval {
+        interval: u64,
+    },
+    /// Sets whether our transfer channels have the ibc-hooks middleware (admin only)
+    UpdateIbcHooks {
+        enabled: bool,
+    },
+    /// Sends a `Balances` packet on every open simple-ica channel. Anyone may call this,
+    /// but only once per `balance_refresh_interval`.
+    RefreshBalances {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    // Returns current admin
-    Admin {},
-    // Shows all open accounts (incl. remote info)
+    // Returns the admins and the roles proposed to others
+    Admins {},
+    // Shows all accounts (incl. remote info and channel stats), also of closed channels
     ListAccounts {},
     // Get account for one channel
-    Account { channel_id: String },
+    Account {
+        channel_id: String,
+    },
+    // Get how the packets of a channel went
+    ChannelStats {
+        channel_id: String,
+    },
     // Get latest query
-    LatestQueryResult { channel_id: String },
+    LatestQueryResult {
+        channel_id: String,
+    },
+    // Shows the balance snapshots of a channel, oldest first.
+    // Only snapshots taken after `start_after` and up to `end_at` are listed.
+    BalanceHistory {
+        channel_id: String,
+        start_after: Option<Timestamp>,
+        end_at: Option<Timestamp>,
+        limit: Option<u32>,
+    },
+    // Get the result of one IbcQuery, by the request id from the events
+    QueryResult {
+        request_id: u64,
+    },
+    // Shows the results of IbcQuerys, oldest first
+    ListQueryResults {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Shows the accounts of closed channels, which the host may reattach to a new channel
+    ListClosedAccounts {},
+    // Get the relayer fee escrowed for a packet in flight
+    PacketFee {
+        channel_id: String,
+        sequence: u64,
+    },
+    // Shows the relayer fees of all packets in flight on a channel
+    ListPacketFees {
+        channel_id: String,
+    },
+    // Get the unused relayer fees the address can claim
+    FeeRefund {
+        address: String,
+    },
+    // Get the packets of a broadcast in flight, finished ones are removed
+    Broadcast {
+        batch_id: u64,
+    },
+    // Shows the scheduled jobs, the next to run first
+    UpcomingJobs {
+        limit: Option<u32>,
+    },
+    // Shows the jobs that ran for the last time or failed, by job id
+    FinishedJobs {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Shows the transfers of the address still in flight
+    PendingTransfers {
+        address: String,
+    },
+    // Shows the callbacks to the address that failed, which it can retry
+    FailedCallbacks {
+        address: String,
+    },
+    // Get the funds of failed transfers the address can claim
+    TransferRefund {
+        address: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Schedule {
+    /// The time of the first run
+    pub start: Timestamp,
+    /// Seconds between two runs
+    pub interval: u64,
+    /// How many times the messages are sent
+    pub repeat: u32,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct AdminResponse {
-    pub admin: String,
+pub struct Trade {
+    /// The address of the exchange on the remote chain
+    pub exchange: String,
+    /// The coin the account gives, in its denom on the remote chain. For transferred coins that
+    /// is `ibc/` and the upper case hex SHA-256 of `transfer/{remote channel id}/{denom}`.
+    pub offer: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
+/// Called by the chain, not by users
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    /// The outcome of an ICS-20 transfer, from the ibc-hooks middleware
+    IbcLifecycleComplete(IbcLifecycleComplete),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -76,6 +288,44 @@ pub struct LatestQueryResponse {
     pub response: StdAck,
 }
 
+/// Closed channels keep their account, but no packets are sent on them
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelStatus {
+    Open,
+    Closed,
+}
+
+// `#[default]` needs Rust 1.62
+#[allow(clippy::derivable_impls)]
+impl Default for ChannelStatus {
+    fn default() -> Self {
+        ChannelStatus::Open
+    }
+}
+
+/// How the packets of a channel went
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct ChannelStats {
+    pub packets_sent: u64,
+    /// Acknowledged with a result
+    pub packets_acked: u64,
+    /// Acknowledged with an error
+    pub packets_errored: u64,
+    pub packets_timed_out: u64,
+    /// When the last ack with a result arrived
+    pub last_success_time: Option<Timestamp>,
+    /// The error of the last packet that failed or timed out
+    pub last_error: Option<String>,
+    pub closed_time: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ChannelStatsResponse {
+    pub status: ChannelStatus,
+    pub stats: ChannelStats,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct AccountInfo {
     pub channel_id: String,
@@ -85,19 +335,194 @@ pub struct AccountInfo {
     /// the channel and making a query and in that time it is empty
     pub remote_addr: Option<String>,
     pub remote_balance: Vec<Coin>,
+    pub status: ChannelStatus,
+    pub stats: ChannelStats,
 }
 
 impl AccountInfo {
-    pub fn convert(channel_id: String, input: AccountData) -> Self {
+    pub fn convert(channel_id: String, input: AccountData, stats: ChannelStats) -> Self {
         AccountInfo {
             channel_id,
             last_update_time: input.last_update_time,
             remote_addr: input.remote_addr,
             remote_balance: input.remote_balance,
+            status: input.status,
+            stats,
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListClosedAccountsResponse {
+    pub accounts: Vec<ClosedAccountInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClosedAccountInfo {
+    pub remote_addr: String,
+    /// The channel that was closed
+    pub channel_id: String,
+    /// last block balance was updated (0 is never)
+    pub last_update_time: Timestamp,
+    pub remote_balance: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BalanceHistoryResponse {
+    pub snapshots: Vec<BalanceSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BalanceSnapshot {
+    /// When we got the balances, which is some time after the host queried them
+    pub time: Timestamp,
+    /// The sequence of the `Balances` packet
+    pub sequence: u64,
+    pub balances: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QueryResultResponse {
+    pub request_id: u64,
+    pub channel_id: String,
+    pub sender: String,
+    pub callback_id: Option<String>,
+    pub sent_time: Timestamp,
+    /// The ack of the host, unset while the query is in flight
+    pub response: Option<StdAck>,
+    pub received_time: Option<Timestamp>,
+    /// Set if the packet timed out, there will be no response then
+    pub timed_out: bool,
+}
+
+impl QueryResultResponse {
+    pub fn convert(request_id: u64, input: QueryResult) -> Self {
+        QueryResultResponse {
+            request_id,
+            channel_id: input.channel_id,
+            sender: input.sender.into(),
+            callback_id: input.callback_id,
+            sent_time: input.sent_time,
+            response: input.response,
+            received_time: input.received_time,
+            timed_out: input.timed_out,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListQueryResultsResponse {
+    pub results: Vec<QueryResultResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PacketFeeResponse {
+    pub payer: String,
+    pub fee: IbcFee,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListPacketFeesResponse {
+    pub fees: Vec<PacketFeeInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PacketFeeInfo {
+    pub sequence: u64,
+    pub payer: String,
+    pub fee: IbcFee,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeRefundResponse {
+    pub amount: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UpcomingJobsResponse {
+    pub jobs: Vec<ScheduledJobInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ScheduledJobInfo {
+    pub job_id: u64,
+    pub sender: String,
+    pub channel_id: String,
+    pub msgs: Vec<CosmosMsg<Empty>>,
+    pub callback_id: Option<String>,
+    pub next_run: Timestamp,
+    pub interval: u64,
+    pub runs: u32,
+    pub runs_left: u32,
+    /// Paid for each run
+    pub reward: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FinishedJobsResponse {
+    pub jobs: Vec<FinishedJobInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FinishedJobInfo {
+    pub job_id: u64,
+    pub sender: String,
+    pub channel_id: String,
+    pub callback_id: Option<String>,
+    pub runs: u32,
+    pub finished_time: Timestamp,
+    /// Why the job stopped before its last run
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BroadcastStatusResponse {
+    pub sender: String,
+    pub callback_id: Option<String>,
+    pub packets: Vec<BroadcastPacketInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BroadcastPacketInfo {
+    pub channel_id: String,
+    pub sequence: u64,
+    /// The ack, or a timeout error, unset while the packet is in flight
+    pub result: Option<StdAck>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingTransfersResponse {
+    pub transfers: Vec<PendingTransferInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingTransferInfo {
+    pub transfer_channel_id: String,
+    pub sequence: u64,
+    pub amount: Coin,
+    /// Set if this funds a `SendFundsAndMsgs`
+    pub batch_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TransferRefundResponse {
+    pub amount: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FailedCallbacksResponse {
+    pub callbacks: Vec<FailedCallbackInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FailedCallbackInfo {
+    pub failure_id: u64,
+    pub callback: ReceiveIcaResponseMsg,
+    /// Why the callback failed
+    pub error: String,
+    pub failed_time: Timestamp,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct AccountResponse {
     /// last block balance was updated (0 is never)
@@ -106,6 +531,7 @@ pub struct AccountResponse {
     /// the channel and making a query and in that time it is empty
     pub remote_addr: Option<String>,
     pub remote_balance: Vec<Coin>,
+    pub status: ChannelStatus,
 }
 
 impl From<AccountData> for AccountResponse {
@@ -114,6 +540,7 @@ impl From<AccountData> for AccountResponse {
             last_update_time: input.last_update_time,
             remote_addr: input.remote_addr,
             remote_balance: input.remote_balance,
+            status: input.status,
         }
     }
 }
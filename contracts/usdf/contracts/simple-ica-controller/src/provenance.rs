@@ -0,0 +1,228 @@
+use cosmwasm_std::{to_binary, Binary, StdResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The data format version of Provenance custom queries
+const QUERY_DATAFMT_VERSION: &str = "2.0.0";
+
+/// A query for a host on Provenance. Common lookups are built here, so we need not link the
+/// Provenance bindings, which would tie this contract to Provenance.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvenanceQueryMsg {
+    /// The marker of the denom, answered with a `Marker`
+    MarkerByDenom { denom: String },
+    /// The marker at the address, answered with a `Marker`
+    MarkerByAddress { address: String },
+    /// The attributes of the account with the name, answered with `Attributes`
+    Attributes { address: String, name: String },
+    /// All attributes of the account, answered with `Attributes`
+    AllAttributes { address: String },
+    /// The address bound to the name, answered with `Names`
+    ResolveName { name: String },
+    /// The metadata scope, answered with a `Scope`
+    Scope { scope_id: String },
+    /// Any other query, as the JSON of a `QueryRequest<ProvenanceQuery>`
+    Raw(Binary),
+}
+
+impl ProvenanceQueryMsg {
+    /// The JSON of the `QueryRequest<ProvenanceQuery>` the host runs
+    pub fn to_request(&self) -> StdResult<Binary> {
+        let (route, params) = match self.clone() {
+            ProvenanceQueryMsg::MarkerByDenom { denom } => (
+                Route::Marker,
+                QueryParams::Marker(MarkerParams::GetMarkerByDenom { denom }),
+            ),
+            ProvenanceQueryMsg::MarkerByAddress { address } => (
+                Route::Marker,
+                QueryParams::Marker(MarkerParams::GetMarkerByAddress { address }),
+            ),
+            ProvenanceQueryMsg::Attributes { address, name } => (
+                Route::Attribute,
+                QueryParams::Attribute(AttributeParams::GetAttributes { address, name }),
+            ),
+            ProvenanceQueryMsg::AllAttributes { address } => (
+                Route::Attribute,
+                QueryParams::Attribute(AttributeParams::GetAllAttributes { address }),
+            ),
+            ProvenanceQueryMsg::ResolveName { name } => {
+                (Route::Name, QueryParams::Name(NameParams::Resolve { name }))
+            }
+            ProvenanceQueryMsg::Scope { scope_id } => (
+                Route::Metadata,
+                QueryParams::Metadata(MetadataParams::GetScope { scope_id }),
+            ),
+            ProvenanceQueryMsg::Raw(request) => return Ok(request),
+        };
+        to_binary(&Request::Custom(ProvenanceQuery {
+            route,
+            params,
+            version: QUERY_DATAFMT_VERSION,
+        }))
+    }
+}
+
+// The parts of provwasm_std::ProvenanceQuery we build, serialized the same way, which the
+// tests check against provwasm-std
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Request {
+    Custom(ProvenanceQuery),
+}
+
+#[derive(Serialize)]
+struct ProvenanceQuery {
+    route: Route,
+    params: QueryParams,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Route {
+    Attribute,
+    Marker,
+    Name,
+    Metadata,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum QueryParams {
+    Name(NameParams),
+    Attribute(AttributeParams),
+    Marker(MarkerParams),
+    Metadata(MetadataParams),
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum NameParams {
+    Resolve { name: String },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum AttributeParams {
+    GetAttributes { address: String, name: String },
+    GetAllAttributes { address: String },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum MarkerParams {
+    GetMarkerByAddress { address: String },
+    GetMarkerByDenom { denom: String },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum MetadataParams {
+    GetScope { scope_id: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_provenance_queries() {
+        let query = ProvenanceQueryMsg::MarkerByDenom {
+            denom: "nhash".into(),
+        };
+        assert_eq!(
+            query.to_request().unwrap().as_slice(),
+            br#"{"custom":{"route":"marker","params":{"marker":{"get_marker_by_denom":{"denom":"nhash"}}},"version":"2.0.0"}}"#
+        );
+        let query = ProvenanceQueryMsg::Attributes {
+            address: "tp1account".into(),
+            name: "kyc.pb".into(),
+        };
+        assert_eq!(
+            query.to_request().unwrap().as_slice(),
+            br#"{"custom":{"route":"attribute","params":{"attribute":{"get_attributes":{"address":"tp1account","name":"kyc.pb"}}},"version":"2.0.0"}}"#
+        );
+        // raw queries are passed on as they are
+        let raw = Binary::from(br#"{"custom":{}}"#.as_slice());
+        let query = ProvenanceQueryMsg::Raw(raw.clone());
+        assert_eq!(query.to_request().unwrap(), raw);
+    }
+
+    #[test]
+    fn queries_match_the_provenance_bindings() {
+        use cosmwasm_std::{Addr, QueryRequest};
+        use provwasm_std::{
+            AttributeQueryParams, MarkerQueryParams, MetadataQueryParams, NameQueryParams,
+            ProvenanceQuery, ProvenanceQueryParams, ProvenanceRoute,
+        };
+
+        let address = Addr::unchecked("tp1account");
+        let cases = [
+            (
+                ProvenanceQueryMsg::MarkerByDenom {
+                    denom: "nhash".into(),
+                },
+                ProvenanceRoute::Marker,
+                ProvenanceQueryParams::Marker(MarkerQueryParams::GetMarkerByDenom {
+                    denom: "nhash".into(),
+                }),
+            ),
+            (
+                ProvenanceQueryMsg::MarkerByAddress {
+                    address: address.to_string(),
+                },
+                ProvenanceRoute::Marker,
+                ProvenanceQueryParams::Marker(MarkerQueryParams::GetMarkerByAddress {
+                    address: address.clone(),
+                }),
+            ),
+            (
+                ProvenanceQueryMsg::Attributes {
+                    address: address.to_string(),
+                    name: "kyc.pb".into(),
+                },
+                ProvenanceRoute::Attribute,
+                ProvenanceQueryParams::Attribute(AttributeQueryParams::GetAttributes {
+                    address: address.clone(),
+                    name: "kyc.pb".into(),
+                }),
+            ),
+            (
+                ProvenanceQueryMsg::AllAttributes {
+                    address: address.to_string(),
+                },
+                ProvenanceRoute::Attribute,
+                ProvenanceQueryParams::Attribute(AttributeQueryParams::GetAllAttributes {
+                    address,
+                }),
+            ),
+            (
+                ProvenanceQueryMsg::ResolveName {
+                    name: "acct.pb".into(),
+                },
+                ProvenanceRoute::Name,
+                ProvenanceQueryParams::Name(NameQueryParams::Resolve {
+                    name: "acct.pb".into(),
+                }),
+            ),
+            (
+                ProvenanceQueryMsg::Scope {
+                    scope_id: "scope1".into(),
+                },
+                ProvenanceRoute::Metadata,
+                ProvenanceQueryParams::Metadata(MetadataQueryParams::GetScope {
+                    scope_id: "scope1".into(),
+                }),
+            ),
+        ];
+        for (query, route, params) in cases {
+            let expected = QueryRequest::Custom(ProvenanceQuery {
+                route,
+                params,
+                version: QUERY_DATAFMT_VERSION.into(),
+            });
+            assert_eq!(query.to_request().unwrap(), to_binary(&expected).unwrap());
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use cosmwasm_std::{
+    BankMsg, Coin, CosmosMsg, DepsMut, Empty, Env, IbcMsg, MessageInfo, Order, Response, StdError,
+    StdResult, Storage, Uint128,
+};
+use cw_storage_plus::Bound;
+
+use simple_ica::fee::add_coins;
+
+use crate::contract::{dispatch_data, open_account};
+use crate::error::ContractError;
+use crate::fee::{format_coins, next_sequence};
+use crate::ibc::PACKET_LIFETIME;
+use crate::msg::Schedule;
+use crate::state::{
+    FinishedJob, ScheduledJob, ADMINS, CONFIG, FINISHED_JOBS, JOB_QUEUE, NEXT_JOB_ID,
+    SCHEDULED_JOBS,
+};
+
+/// How many due jobs one `Crank` runs at most, the others wait for the next one
+pub const MAX_JOBS_PER_CRANK: usize = 10;
+
+/// Stores the messages to be sent by `Crank` on the schedule.
+/// The sender pays the crank reward of all runs up front.
+pub fn execute_schedule_msgs(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+    msgs: Vec<CosmosMsg>,
+    callback_id: Option<String>,
+    schedule: Schedule,
+) -> Result<Response, ContractError> {
+    let Schedule {
+        start,
+        interval,
+        repeat,
+    } = schedule;
+    // auth check
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    if repeat == 0 || (repeat > 1 && interval == 0) {
+        return Err(StdError::generic_err("Jobs must run at least once, at distinct times").into());
+    }
+    // ensure the channel exists (not found if not registered) and is open
+    open_account(deps.storage, &channel_id)?;
+    // fail now rather than on every crank if the channel can not carry the messages
+    dispatch_data(
+        deps.storage,
+        &channel_id,
+        info.sender.to_string(),
+        msgs.clone(),
+        callback_id.clone(),
+    )?;
+    let total = reward_total(&cfg.crank_reward, repeat);
+    if add_coins(&info.funds, &[]) != total {
+        return Err(ContractError::RewardFundsMismatch(format_coins(&total)));
+    }
+
+    let job_id = NEXT_JOB_ID.may_load(deps.storage)?.unwrap_or(1);
+    NEXT_JOB_ID.save(deps.storage, &(job_id + 1))?;
+    let job = ScheduledJob {
+        sender: info.sender,
+        channel_id,
+        msgs,
+        callback_id,
+        next_run: start,
+        interval,
+        runs: 0,
+        runs_left: repeat,
+        reward: cfg.crank_reward,
+    };
+    SCHEDULED_JOBS.save(deps.storage, job_id, &job)?;
+    JOB_QUEUE.save(deps.storage, (start.nanos(), job_id), &Empty {})?;
+
+    Ok(Response::new()
+        .add_attribute("action", "handle_schedule_msgs")
+        .add_attribute("job_id", job_id.to_string()))
+}
+
+/// Sends the packets of the jobs due at the block time, and pays the caller their rewards.
+/// Jobs that can not run anymore, like when their channel was closed or their sender is no
+/// admin anymore, are finished and the sender gets back the rewards of the remaining runs.
+pub fn execute_crank(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    // intentionally no auth check, the caller is paid for the jobs it runs
+    let end = Bound::inclusive((env.block.time.nanos(), u64::MAX));
+    let due = JOB_QUEUE
+        .keys(deps.storage, None, Some(end), Order::Ascending)
+        .take(MAX_JOBS_PER_CRANK)
+        .collect::<StdResult<Vec<_>>>()?;
+    if due.is_empty() {
+        return Err(ContractError::NoJobsDue);
+    }
+
+    let mut res = Response::new().add_attribute("action", "handle_crank");
+    let mut reward = vec![];
+    for (run_time, job_id) in due {
+        JOB_QUEUE.remove(deps.storage, (run_time, job_id));
+        let mut job = SCHEDULED_JOBS.load(deps.storage, job_id)?;
+        // the job sends as its sender, who must still be allowed to
+        let data = ADMINS
+            .assert_admin(deps.storage, &job.sender)
+            .map_err(ContractError::from)
+            .and_then(|_| open_account(deps.storage, &job.channel_id))
+            .and_then(|_| {
+                dispatch_data(
+                    deps.storage,
+                    &job.channel_id,
+                    job.sender.to_string(),
+                    job.msgs.clone(),
+                    job.callback_id.clone(),
+                )
+            })
+            .map_err(|err| err.to_string());
+        let data = match data {
+            Ok(data) => data,
+            Err(error) => {
+                if let Some(refund) = refund_msg(&job) {
+                    res = res.add_message(refund);
+                }
+                res = res.add_attribute("failed_job", job_id.to_string());
+                finish_job(deps.storage, &env, job_id, job, Some(error))?;
+                continue;
+            }
+        };
+
+        next_sequence(deps.storage, &job.channel_id)?;
+        res = res
+            .add_message(IbcMsg::SendPacket {
+                channel_id: job.channel_id.clone(),
+                data,
+                timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
+            })
+            .add_attribute("job_id", job_id.to_string());
+        reward = add_coins(&reward, &job.reward);
+
+        job.runs += 1;
+        job.runs_left -= 1;
+        if job.runs_left == 0 {
+            finish_job(deps.storage, &env, job_id, job, None)?;
+        } else {
+            // a late crank does not skip runs, the next one is due right away then
+            job.next_run = job.next_run.plus_seconds(job.interval);
+            JOB_QUEUE.save(deps.storage, (job.next_run.nanos(), job_id), &Empty {})?;
+            SCHEDULED_JOBS.save(deps.storage, job_id, &job)?;
+        }
+    }
+
+    if !reward.is_empty() {
+        res = res
+            .add_attribute("reward", format_coins(&reward))
+            .add_message(BankMsg::Send {
+                to_address: info.sender.into(),
+                amount: reward,
+            });
+    }
+    Ok(res)
+}
+
+/// Stops a job before its remaining runs (sender or owners only), and sends the sender
+/// back their rewards.
+pub fn execute_cancel_job(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    job_id: u64,
+) -> Result<Response, ContractError> {
+    let job = SCHEDULED_JOBS
+        .may_load(deps.storage, job_id)?
+        .ok_or(ContractError::UnknownJob(job_id))?;
+    // auth check
+    if info.sender != job.sender {
+        ADMINS.assert_owner(deps.storage, &info.sender)?;
+    }
+
+    JOB_QUEUE.remove(deps.storage, (job.next_run.nanos(), job_id));
+    let mut res = Response::new()
+        .add_attribute("action", "handle_cancel_job")
+        .add_attribute("job_id", job_id.to_string());
+    if let Some(refund) = refund_msg(&job) {
+        res = res.add_message(refund);
+    }
+    let error = format!("Cancelled by {}", info.sender);
+    finish_job(deps.storage, &env, job_id, job, Some(error))?;
+    Ok(res)
+}
+
+/// Sends the sender the rewards of the runs a job will not make
+fn refund_msg(job: &ScheduledJob) -> Option<BankMsg> {
+    let refund = reward_total(&job.reward, job.runs_left);
+    if refund.is_empty() {
+        return None;
+    }
+    Some(BankMsg::Send {
+        to_address: job.sender.to_string(),
+        amount: refund,
+    })
+}
+
+/// The crank reward of all runs of a job
+fn reward_total(reward: &[Coin], runs: u32) -> Vec<Coin> {
+    let total: Vec<_> = reward
+        .iter()
+        .map(|coin| Coin {
+            denom: coin.denom.clone(),
+            amount: coin.amount * Uint128::from(runs),
+        })
+        .collect();
+    add_coins(&total, &[])
+}
+
+fn finish_job(
+    storage: &mut dyn Storage,
+    env: &Env,
+    job_id: u64,
+    job: ScheduledJob,
+    error: Option<String>,
+) -> StdResult<()> {
+    SCHEDULED_JOBS.remove(storage, job_id);
+    let finished = FinishedJob {
+        sender: job.sender,
+        channel_id: job.channel_id,
+        callback_id: job.callback_id,
+        runs: job.runs,
+        finished_time: env.block.time,
+        error,
+    };
+    FINISHED_JOBS.save(storage, job_id, &finished)
+}
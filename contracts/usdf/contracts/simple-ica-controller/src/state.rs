@@ -1,12 +1,50 @@
 use serde::{Deserialize, Serialize};
 
-use crate::msg::LatestQueryResponse;
-use cosmwasm_std::{Addr, Coin, Timestamp};
+use crate::msg::{ChannelStats, ChannelStatus, LatestQueryResponse, Trade};
+use cosmwasm_std::{Addr, Coin, CosmosMsg, Empty, Timestamp};
 use cw_storage_plus::{Item, Map};
+use simple_ica::admin::Admins;
+use simple_ica::fee::IbcFee;
+use simple_ica::{IcaVersion, ReceiveIcaResponseMsg, StdAck};
+
+/// Query results are kept for one day, unless configured otherwise
+pub const DEFAULT_QUERY_RESULT_TTL: u64 = 24 * 60 * 60;
+/// Anyone may refresh the balances once an hour, unless configured otherwise
+pub const DEFAULT_BALANCE_REFRESH_INTERVAL: u64 = 60 * 60;
+/// Callbacks may use this much gas, unless configured otherwise
+pub const DEFAULT_CALLBACK_GAS_LIMIT: u64 = 500_000;
+/// The number of balance snapshots kept per channel, older ones are dropped
+pub const MAX_BALANCE_SNAPSHOTS: usize = 100;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Config {
-    pub admin: Addr,
+    /// How many seconds after sending an `IbcQuery` its result may be pruned
+    #[serde(default = "default_query_result_ttl")]
+    pub query_result_ttl: u64,
+    /// How many seconds must pass between two `RefreshBalances`
+    #[serde(default = "default_balance_refresh_interval")]
+    pub balance_refresh_interval: u64,
+    /// The gas a callback may use. If it uses more or fails, it is kept for a retry.
+    #[serde(default = "default_callback_gas_limit")]
+    pub callback_gas_limit: u64,
+    /// Whether ibc-hooks tells us the outcome of our transfers, so we can follow them
+    #[serde(default)]
+    pub ibc_hooks: bool,
+    /// Paid to the caller of `Crank` for every scheduled run it sends
+    #[serde(default)]
+    pub crank_reward: Vec<Coin>,
+}
+
+fn default_query_result_ttl() -> u64 {
+    DEFAULT_QUERY_RESULT_TTL
+}
+
+fn default_balance_refresh_interval() -> u64 {
+    DEFAULT_BALANCE_REFRESH_INTERVAL
+}
+
+fn default_callback_gas_limit() -> u64 {
+    DEFAULT_CALLBACK_GAS_LIMIT
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
@@ -20,8 +58,186 @@ pub struct AccountData {
     /// must not be of type `Addr`.
     pub remote_addr: Option<String>,
     pub remote_balance: Vec<Coin>,
+    /// Accounts of closed channels are kept, before they were removed
+    #[serde(default)]
+    pub status: ChannelStatus,
+}
+
+/// An account whose channel was closed. The host keeps it and reattaches
+/// it to our next channel, which we notice by the address.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ClosedAccount {
+    /// The channel that was closed
+    pub channel_id: String,
+    pub last_update_time: Timestamp,
+    pub remote_balance: Vec<Coin>,
+}
+
+/// Relayer fees paid for a packet in flight
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EscrowedFee {
+    /// Who paid the fee, and gets back what the relayers do not earn
+    pub payer: Addr,
+    pub fee: IbcFee,
+}
+
+/// An `IbcQuery`, by the request id we assigned when sending it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QueryResult {
+    pub channel_id: String,
+    pub sender: Addr,
+    pub callback_id: Option<String>,
+    pub sent_time: Timestamp,
+    /// The ack of the host, once we got it
+    pub response: Option<StdAck>,
+    pub received_time: Option<Timestamp>,
+    pub timed_out: bool,
+}
+
+/// An ICS-20 transfer we sent and wait for the outcome of
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingTransfer {
+    pub sender: Addr,
+    pub amount: Coin,
+    /// The `SendFundsAndMsgs` waiting for this transfer, if any
+    pub batch_id: Option<u64>,
+}
+
+/// Messages of a `SendFundsAndMsgs` or the trade of a `RemoteTrade`, sent once all its
+/// transfers arrived
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FundsAndMsgs {
+    pub sender: Addr,
+    pub ica_channel_id: String,
+    pub msgs: Vec<CosmosMsg>,
+    /// Set for a `RemoteTrade`, which sends the trade instead of the messages
+    #[serde(default)]
+    pub trade: Option<Trade>,
+    pub callback_id: Option<String>,
+    /// The number of transfers still in flight
+    pub pending: u32,
+    /// The coins whose transfer failed or timed out
+    pub failed: Vec<Coin>,
+}
+
+/// A callback being sent, to keep it if it fails
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingCallback {
+    pub receiver: String,
+    pub callback: ReceiveIcaResponseMsg,
+}
+
+/// A callback the receiver failed to process, which it may retry
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FailedCallback {
+    pub callback: ReceiveIcaResponseMsg,
+    pub error: String,
+    pub failed_time: Timestamp,
+}
+
+/// Messages sent by `Crank` at regular times
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduledJob {
+    pub sender: Addr,
+    pub channel_id: String,
+    pub msgs: Vec<CosmosMsg>,
+    pub callback_id: Option<String>,
+    pub next_run: Timestamp,
+    /// Seconds between two runs
+    pub interval: u64,
+    /// How often it ran so far
+    pub runs: u32,
+    pub runs_left: u32,
+    /// The crank reward of each run, as configured when it was scheduled
+    pub reward: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FinishedJob {
+    pub sender: Addr,
+    pub channel_id: String,
+    pub callback_id: Option<String>,
+    pub runs: u32,
+    pub finished_time: Timestamp,
+    /// Why the job stopped before its last run
+    pub error: Option<String>,
+}
+
+/// A `BroadcastMsgs` waiting for the acks of its packets
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Broadcast {
+    pub sender: Addr,
+    pub callback_id: Option<String>,
+    pub packets: Vec<BroadcastPacket>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BroadcastPacket {
+    pub channel_id: String,
+    pub sequence: u64,
+    /// The ack, or a timeout error, once we got it
+    pub result: Option<StdAck>,
 }
 
+pub const ADMINS: Admins = Admins::new("admins", "admin_proposals");
 pub const CONFIG: Item<Config> = Item::new("config");
 pub const ACCOUNTS: Map<&str, AccountData> = Map::new("accounts");
+pub const CHANNEL_STATS: Map<&str, ChannelStats> = Map::new("channel_stats");
+/// The protocol version negotiated for each channel.
+/// Channels opened by older versions have none, they speak v2.
+pub const CHANNEL_VERSIONS: Map<&str, IcaVersion> = Map::new("channel_versions");
+/// Closed accounts by remote address. Accounts whose address we never learned are not kept.
+pub const CLOSED_ACCOUNTS: Map<&str, ClosedAccount> = Map::new("closed_accounts");
+/// The result of the last `IbcQuery` per channel
 pub const LATEST_QUERIES: Map<&str, LatestQueryResponse> = Map::new("querys");
+/// The remote balances by channel, the time (in nanos) we got them and the sequence of the
+/// packet, as several acks may arrive in one block
+pub const BALANCE_HISTORY: Map<(&str, u64, u64), Vec<Coin>> = Map::new("balance_history");
+/// When `RefreshBalances` was last called
+pub const LAST_BALANCE_REFRESH: Item<Timestamp> = Item::new("last_balance_refresh");
+/// Our port of each channel, as fees are paid by port and channel
+pub const CHANNEL_PORTS: Map<&str, String> = Map::new("channel_ports");
+/// The sequence of the last packet sent on each channel, counted from the connect.
+/// Channels connected by older versions have none until the reply to a packet tells it, like
+/// the one the migration sends, so no fees can be paid on them until then.
+pub const LAST_SEQUENCES: Map<&str, u64> = Map::new("last_sequences");
+/// Escrowed fees by channel and packet sequence
+pub const FEE_ESCROWS: Map<(&str, u64), EscrowedFee> = Map::new("fee_escrows");
+/// Unused fees the fee middleware returned to us, by payer
+pub const FEE_REFUNDS: Map<&Addr, Vec<Coin>> = Map::new("fee_refunds");
+/// The request id of the next `IbcQuery`
+pub const NEXT_REQUEST_ID: Item<u64> = Item::new("next_request_id");
+pub const QUERY_RESULTS: Map<u64, QueryResult> = Map::new("query_results");
+/// The request ids of queries in flight, by channel and packet sequence
+pub const PENDING_QUERIES: Map<(&str, u64), u64> = Map::new("pending_queries");
+/// Transfers sent in this transaction, by transfer channel, until their reply tells the sequence
+pub const TRANSFERS_AWAITING_SEQUENCE: Item<Vec<(String, PendingTransfer)>> =
+    Item::new("transfers_awaiting_sequence");
+/// Transfers in flight by transfer channel and packet sequence
+pub const PENDING_TRANSFERS: Map<(&str, u64), PendingTransfer> = Map::new("pending_transfers");
+/// The keys of `PENDING_TRANSFERS` by sender
+pub const TRANSFERS_BY_SENDER: Map<(&Addr, &str, u64), Empty> = Map::new("transfers_by_sender");
+/// Funds of failed transfers, which came back to us, by sender
+pub const TRANSFER_REFUNDS: Map<&Addr, Vec<Coin>> = Map::new("transfer_refunds");
+/// The batch id of the next `SendFundsAndMsgs` or `BroadcastMsgs`
+pub const NEXT_BATCH_ID: Item<u64> = Item::new("next_batch_id");
+pub const FUNDS_AND_MSGS: Map<u64, FundsAndMsgs> = Map::new("funds_and_msgs");
+pub const NEXT_JOB_ID: Item<u64> = Item::new("next_job_id");
+pub const SCHEDULED_JOBS: Map<u64, ScheduledJob> = Map::new("scheduled_jobs");
+/// The ids of the scheduled jobs by their next run in nanoseconds, to find the due ones
+pub const JOB_QUEUE: Map<(u64, u64), Empty> = Map::new("job_queue");
+pub const FINISHED_JOBS: Map<u64, FinishedJob> = Map::new("finished_jobs");
+/// Broadcasts in flight by batch id
+pub const BROADCASTS: Map<u64, Broadcast> = Map::new("broadcasts");
+/// The batch id of each broadcast packet in flight, by channel and packet sequence
+pub const BROADCAST_PACKETS: Map<(&str, u64), u64> = Map::new("broadcast_packets");
+/// Broadcast packets sent in this transaction on channels whose sequence we do not know yet,
+/// by channel and batch id, until their reply tells the sequence
+pub const BROADCASTS_AWAITING_SEQUENCE: Item<Vec<(String, u64)>> =
+    Item::new("broadcasts_awaiting_sequence");
+/// The callbacks sent in this transaction, in the order their replies come
+pub const CALLBACKS_IN_FLIGHT: Item<Vec<PendingCallback>> = Item::new("callbacks_in_flight");
+/// The id of the next failed callback
+pub const NEXT_FAILURE_ID: Item<u64> = Item::new("next_failure_id");
+/// Failed callbacks by receiver and failure id
+pub const FAILED_CALLBACKS: Map<(&str, u64), FailedCallback> = Map::new("failed_callbacks");
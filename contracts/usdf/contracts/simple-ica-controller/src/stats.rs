@@ -0,0 +1,54 @@
+use cosmwasm_std::{StdResult, Storage, Timestamp};
+
+use simple_ica::StdAck;
+
+use crate::msg::ChannelStats;
+use crate::state::CHANNEL_STATS;
+
+/// Counts a packet sent on the channel
+pub fn record_sent(storage: &mut dyn Storage, channel_id: &str) -> StdResult<()> {
+    update_stats(storage, channel_id, |stats| stats.packets_sent += 1)
+}
+
+/// Counts an ack of the host, which may be an error
+pub fn record_ack(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    ack: &StdAck,
+    time: Timestamp,
+) -> StdResult<()> {
+    update_stats(storage, channel_id, |stats| match ack {
+        StdAck::Result(_) => {
+            stats.packets_acked += 1;
+            stats.last_success_time = Some(time);
+        }
+        StdAck::Error(err) => {
+            stats.packets_errored += 1;
+            stats.last_error = Some(err.clone());
+        }
+    })
+}
+
+pub fn record_timeout(storage: &mut dyn Storage, channel_id: &str, sequence: u64) -> StdResult<()> {
+    update_stats(storage, channel_id, |stats| {
+        stats.packets_timed_out += 1;
+        stats.last_error = Some(format!("packet {} timed out", sequence));
+    })
+}
+
+pub fn record_close(storage: &mut dyn Storage, channel_id: &str, time: Timestamp) -> StdResult<()> {
+    update_stats(storage, channel_id, |stats| stats.closed_time = Some(time))
+}
+
+// channels connected before we kept stats start from zero
+fn update_stats(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    update: impl FnOnce(&mut ChannelStats),
+) -> StdResult<()> {
+    let mut stats = CHANNEL_STATS
+        .may_load(storage, channel_id)?
+        .unwrap_or_default();
+    update(&mut stats);
+    CHANNEL_STATS.save(storage, channel_id, &stats)
+}
@@ -0,0 +1,111 @@
+use cosmwasm_std::{
+    to_binary, Binary, DepsMut, Env, IbcMsg, MessageInfo, Response, StdError, Storage,
+};
+
+use simple_ica::PacketMsg;
+
+use crate::contract::{load_version, open_account, remote_address};
+use crate::error::ContractError;
+use crate::fee::next_sequence;
+use crate::ibc::PACKET_LIFETIME;
+use crate::msg::Trade;
+use crate::state::{FundsAndMsgs, ADMINS, FUNDS_AND_MSGS, NEXT_BATCH_ID};
+use crate::transfer::tracked_transfers;
+
+/// Has the account trade on the remote exchange. With a transfer channel the funds sent along
+/// are transferred first, and the trade is sent by `transfer_complete` once they arrived.
+pub fn execute_remote_trade(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    ica_channel_id: String,
+    transfer_channel_id: Option<String>,
+    trade: Trade,
+    callback_id: Option<String>,
+) -> Result<Response, ContractError> {
+    // auth check
+    ADMINS.assert_admin(deps.storage, &info.sender)?;
+    // fail now if the channel can not carry the trade, rather than once the funds arrived
+    let data = trade_data(
+        deps.storage,
+        &ica_channel_id,
+        info.sender.to_string(),
+        trade.clone(),
+        callback_id.clone(),
+    )?;
+
+    // the offer is not checked against the funds, its voucher denom is up to the caller
+    let transfer_channel_id = match transfer_channel_id {
+        Some(channel_id) => channel_id,
+        None => {
+            if !info.funds.is_empty() {
+                return Err(StdError::generic_err(
+                    "funds are only taken along with a transfer channel",
+                )
+                .into());
+            }
+            next_sequence(deps.storage, &ica_channel_id)?;
+            let msg = IbcMsg::SendPacket {
+                channel_id: ica_channel_id,
+                data,
+                timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
+            };
+            return Ok(Response::new()
+                .add_message(msg)
+                .add_attribute("action", "handle_remote_trade"));
+        }
+    };
+    if info.funds.is_empty() {
+        return Err(
+            StdError::generic_err("you must send the coins you wish to ibc transfer").into(),
+        );
+    }
+    let remote_addr = remote_address(deps.storage, &ica_channel_id)?;
+
+    let batch_id = NEXT_BATCH_ID.may_load(deps.storage)?.unwrap_or(1);
+    NEXT_BATCH_ID.save(deps.storage, &(batch_id + 1))?;
+    let batch = FundsAndMsgs {
+        sender: info.sender.clone(),
+        ica_channel_id,
+        msgs: vec![],
+        trade: Some(trade),
+        callback_id,
+        pending: info.funds.len() as u32,
+        failed: vec![],
+    };
+    FUNDS_AND_MSGS.save(deps.storage, batch_id, &batch)?;
+
+    let transfers = tracked_transfers(
+        deps.storage,
+        &env,
+        &info.sender,
+        &transfer_channel_id,
+        &remote_addr,
+        info.funds,
+        Some(batch_id),
+    )?;
+    Ok(Response::new()
+        .add_submessages(transfers)
+        .add_attribute("action", "handle_remote_trade")
+        .add_attribute("batch_id", batch_id.to_string())
+        .set_data(to_binary(&batch_id)?))
+}
+
+/// Encodes the trade, which only simple-ica channels carry
+pub(crate) fn trade_data(
+    storage: &dyn Storage,
+    channel_id: &str,
+    sender: String,
+    trade: Trade,
+    callback_id: Option<String>,
+) -> Result<Binary, ContractError> {
+    open_account(storage, channel_id)?;
+    let version = load_version(storage, channel_id)?;
+    let packet = PacketMsg::Trade {
+        sender,
+        exchange: trade.exchange,
+        offer: trade.offer,
+        callback_id,
+    };
+    Ok(version.encode_packet(&packet)?)
+}
@@ -0,0 +1,213 @@
+use std::slice;
+
+use cosmwasm_std::{
+    Addr, Coin, DepsMut, Empty, Env, IbcMsg, Reply, Response, StdError, StdResult, Storage, SubMsg,
+};
+
+use simple_ica::fee::add_coins;
+use simple_ica::ics20::{
+    callback_memo, decode_transfer_response, IbcLifecycleComplete, MsgTransfer,
+};
+use simple_ica::{ErrorCode, PacketKind, ReceiveIcaResponseMsg, StdAck};
+
+use crate::callback::{callback_msg, callback_packet};
+use crate::contract::{dispatch_data, open_account};
+use crate::error::ContractError;
+use crate::fee::{next_sequence, sent_sequence};
+use crate::ibc::PACKET_LIFETIME;
+use crate::state::{
+    PendingTransfer, CONFIG, FUNDS_AND_MSGS, PENDING_TRANSFERS, TRANSFERS_AWAITING_SEQUENCE,
+    TRANSFERS_BY_SENDER, TRANSFER_REFUNDS,
+};
+use crate::trade::trade_data;
+
+pub const TRANSFER_REPLY_ID: u64 = 1;
+
+/// Sends every coin with its own transfer, asking ibc-hooks for a callback with the outcome.
+/// The transfers are remembered until then, the replies tell us their sequence.
+/// Fails if ibc-hooks is not enabled, the callback would never come.
+pub fn tracked_transfers(
+    storage: &mut dyn Storage,
+    env: &Env,
+    sender: &Addr,
+    transfer_channel_id: &str,
+    receiver: &str,
+    funds: Vec<Coin>,
+    batch_id: Option<u64>,
+) -> Result<Vec<SubMsg>, ContractError> {
+    if !CONFIG.load(storage)?.ibc_hooks {
+        return Err(ContractError::IbcHooksDisabled);
+    }
+    let mut awaiting = TRANSFERS_AWAITING_SEQUENCE
+        .may_load(storage)?
+        .unwrap_or_default();
+    let contract = env.contract.address.to_string();
+    let timeout = env.block.time.plus_seconds(PACKET_LIFETIME);
+
+    let mut msgs = vec![];
+    for amount in funds {
+        let transfer = MsgTransfer {
+            source_channel: transfer_channel_id.to_string(),
+            token: amount.clone(),
+            sender: contract.clone(),
+            receiver: receiver.to_string(),
+            timeout_timestamp: timeout.nanos(),
+            memo: callback_memo(&contract),
+        };
+        msgs.push(SubMsg::reply_on_success(
+            transfer.to_msg(),
+            TRANSFER_REPLY_ID,
+        ));
+        let pending = PendingTransfer {
+            sender: sender.clone(),
+            amount,
+            batch_id,
+        };
+        awaiting.push((transfer_channel_id.to_string(), pending));
+    }
+    TRANSFERS_AWAITING_SEQUENCE.save(storage, &awaiting)?;
+    Ok(msgs)
+}
+
+/// Replies come in the order of the transfers, so the first one waiting gets the sequence
+pub fn reply_transfer(deps: DepsMut, reply: Reply) -> Result<Response, ContractError> {
+    let response = reply.result.into_result().map_err(StdError::generic_err)?;
+    let sequence = match response.data {
+        Some(data) => decode_transfer_response(&data)?,
+        // older transfer modules do not return the sequence, but it is in the events
+        None => sent_sequence(&response.events)
+            .ok_or_else(|| StdError::generic_err("no sequence for the transfer"))?,
+    };
+
+    let mut awaiting = TRANSFERS_AWAITING_SEQUENCE
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    if awaiting.is_empty() {
+        return Err(StdError::generic_err("no transfer awaiting its sequence").into());
+    }
+    let (channel_id, pending) = awaiting.remove(0);
+    TRANSFERS_AWAITING_SEQUENCE.save(deps.storage, &awaiting)?;
+    PENDING_TRANSFERS.save(deps.storage, (&channel_id, sequence), &pending)?;
+    TRANSFERS_BY_SENDER.save(
+        deps.storage,
+        (&pending.sender, &channel_id, sequence),
+        &Empty {},
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("transfer_channel_id", channel_id)
+        .add_attribute("transfer_sequence", sequence.to_string()))
+}
+
+/// Called by ibc-hooks once a transfer was acknowledged or timed out. If it failed, the funds
+/// came back to us and the sender can claim them. Transfers of a `SendFundsAndMsgs` dispatch
+/// its messages once all arrived, or report the failed coins.
+pub fn transfer_complete(
+    deps: DepsMut,
+    env: Env,
+    callback: IbcLifecycleComplete,
+) -> Result<Response, ContractError> {
+    let (channel_id, sequence, success) = match callback {
+        IbcLifecycleComplete::IbcAck {
+            channel,
+            sequence,
+            success,
+            ..
+        } => (channel, sequence, success),
+        IbcLifecycleComplete::IbcTimeout { channel, sequence } => (channel, sequence, false),
+    };
+    let res = Response::new()
+        .add_attribute("action", "ibc_lifecycle_complete")
+        .add_attribute("transfer_channel_id", &channel_id)
+        .add_attribute("transfer_sequence", sequence.to_string())
+        .add_attribute("success", success.to_string());
+
+    // not one of ours, or tracked by an older version
+    let pending = match PENDING_TRANSFERS.may_load(deps.storage, (&channel_id, sequence))? {
+        Some(pending) => pending,
+        None => return Ok(res),
+    };
+    PENDING_TRANSFERS.remove(deps.storage, (&channel_id, sequence));
+    TRANSFERS_BY_SENDER.remove(deps.storage, (&pending.sender, &channel_id, sequence));
+    let res = res.add_attribute("sender", &pending.sender);
+    let res = if success {
+        res
+    } else {
+        TRANSFER_REFUNDS.update(deps.storage, &pending.sender, |old| -> StdResult<_> {
+            Ok(add_coins(
+                &old.unwrap_or_default(),
+                slice::from_ref(&pending.amount),
+            ))
+        })?;
+        res.add_attribute("refund", pending.amount.to_string())
+    };
+
+    let batch_id = match pending.batch_id {
+        Some(batch_id) => batch_id,
+        None => return Ok(res),
+    };
+    let mut batch = FUNDS_AND_MSGS.load(deps.storage, batch_id)?;
+    batch.pending -= 1;
+    if !success {
+        batch.failed.push(pending.amount);
+    }
+    let res = res.add_attribute("batch_id", batch_id.to_string());
+    if batch.pending > 0 {
+        FUNDS_AND_MSGS.save(deps.storage, batch_id, &batch)?;
+        return Ok(res);
+    }
+    FUNDS_AND_MSGS.remove(deps.storage, batch_id);
+
+    // all funds arrived, so we can act with them
+    let kind = match batch.trade {
+        Some(_) => PacketKind::Trade,
+        None => PacketKind::Dispatch,
+    };
+    if batch.failed.is_empty() && open_account(deps.storage, &batch.ica_channel_id).is_ok() {
+        let sender = batch.sender.to_string();
+        let data = match batch.trade {
+            Some(trade) => trade_data(
+                deps.storage,
+                &batch.ica_channel_id,
+                sender,
+                trade,
+                batch.callback_id,
+            )?,
+            None => dispatch_data(
+                deps.storage,
+                &batch.ica_channel_id,
+                sender,
+                batch.msgs,
+                batch.callback_id,
+            )?,
+        };
+        next_sequence(deps.storage, &batch.ica_channel_id)?;
+        let msg = IbcMsg::SendPacket {
+            channel_id: batch.ica_channel_id,
+            data,
+            timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
+        };
+        return Ok(res.add_message(msg).add_attribute("batch", "dispatched"));
+    }
+
+    // otherwise the messages are dropped, and the sender told why
+    let err = if batch.failed.is_empty() {
+        format!("channel {} was closed", batch.ica_channel_id)
+    } else {
+        let failed: Vec<_> = batch.failed.iter().map(Coin::to_string).collect();
+        format!("transfer of {} failed", failed.join(","))
+    };
+    let res = res
+        .add_attribute("batch", "failed")
+        .add_attribute("error", &err);
+    match batch.callback_id {
+        Some(id) => {
+            let msg = StdAck::error_with_code(ErrorCode::TransferFailed, err);
+            let packet = callback_packet(deps.storage, &batch.ica_channel_id, None, kind)?;
+            let callback = ReceiveIcaResponseMsg::new(id, msg, packet);
+            let callback = callback_msg(deps.storage, batch.sender.into(), callback)?;
+            Ok(res.add_submessage(callback))
+        }
+        None => Ok(res),
+    }
+}
@@ -0,0 +1,197 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{to_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, StdResult, WasmMsg};
+#[cfg(feature = "provenance")]
+use provwasm_std::ProvenanceMsg;
+
+#[cfg(feature = "provenance")]
+use crate::error::ContractError;
+
+/// The contract the remote accounts run. It is chosen at instantiation, as existing
+/// accounts could not be driven by another one.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountBackend {
+    /// cw1-whitelist with the host as its only admin
+    Cw1Whitelist,
+    /// The demo `reflect` contract, owned by the host as it instantiates it
+    Reflect,
+    /// `provenance-account`, cw1-whitelist with `ProvenanceMsg` as its custom message
+    #[cfg(feature = "provenance")]
+    Provenance,
+}
+
+// hosts on Provenance default to accounts that run Provenance messages
+#[allow(clippy::derivable_impls)]
+impl Default for AccountBackend {
+    #[cfg(not(feature = "provenance"))]
+    fn default() -> Self {
+        AccountBackend::Cw1Whitelist
+    }
+
+    #[cfg(feature = "provenance")]
+    fn default() -> Self {
+        AccountBackend::Provenance
+    }
+}
+
+/// The messages of the demo `reflect` contract we send
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReflectExecuteMsg {
+    ReflectMsg {
+        msgs: Vec<CosmosMsg>,
+    },
+    /// Only the Provenance build of `reflect` has it
+    #[cfg(feature = "provenance")]
+    ProvenanceReflectMsg {
+        msg: CosmosMsg<ProvenanceMsg>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReflectInstantiateMsg {}
+
+impl AccountBackend {
+    /// Creates an account controlled by the host
+    pub fn instantiate_msg(&self, host: &Addr) -> StdResult<Binary> {
+        match self {
+            AccountBackend::Cw1Whitelist => cw1_instantiate_msg(host),
+            #[cfg(feature = "provenance")]
+            AccountBackend::Provenance => cw1_instantiate_msg(host),
+            AccountBackend::Reflect => to_binary(&ReflectInstantiateMsg {}),
+        }
+    }
+
+    /// Makes the account dispatch the messages
+    pub fn execute_msg(&self, account: &Addr, msgs: Vec<CosmosMsg>) -> StdResult<WasmMsg> {
+        let msg = match self {
+            AccountBackend::Cw1Whitelist => {
+                to_binary(&cw1_whitelist::msg::ExecuteMsg::Execute { msgs })?
+            }
+            // plain messages are Provenance messages without the custom ones
+            #[cfg(feature = "provenance")]
+            AccountBackend::Provenance => {
+                to_binary(&cw1_whitelist::msg::ExecuteMsg::Execute { msgs })?
+            }
+            AccountBackend::Reflect => to_binary(&ReflectExecuteMsg::ReflectMsg { msgs })?,
+        };
+        Ok(WasmMsg::Execute {
+            contract_addr: account.into(),
+            msg,
+            funds: vec![],
+        })
+    }
+
+    /// Makes the account dispatch Provenance messages. cw1-whitelist accounts can not, and
+    /// `reflect` runs one per packet so it stays atomic.
+    #[cfg(feature = "provenance")]
+    pub fn provenance_execute_msg(
+        &self,
+        account: &Addr,
+        mut msgs: Vec<CosmosMsg<ProvenanceMsg>>,
+    ) -> Result<WasmMsg, ContractError> {
+        let msg = match self {
+            AccountBackend::Cw1Whitelist => {
+                return Err(ContractError::ProvenanceUnsupported(
+                    "cw1_whitelist accounts can not run them".into(),
+                ))
+            }
+            AccountBackend::Provenance => {
+                to_binary(&cw1_whitelist::msg::ExecuteMsg::Execute { msgs })?
+            }
+            AccountBackend::Reflect => {
+                if msgs.len() != 1 {
+                    return Err(ContractError::ProvenanceUnsupported(
+                        "reflect accounts run one per packet".into(),
+                    ));
+                }
+                let msg = msgs.remove(0);
+                to_binary(&ReflectExecuteMsg::ProvenanceReflectMsg { msg })?
+            }
+        };
+        Ok(WasmMsg::Execute {
+            contract_addr: account.into(),
+            msg,
+            funds: vec![],
+        })
+    }
+
+    /// Makes the account send the funds to the recipient
+    pub fn sweep_msg(
+        &self,
+        account: &Addr,
+        recipient: &Addr,
+        amount: Vec<Coin>,
+    ) -> StdResult<WasmMsg> {
+        let send = BankMsg::Send {
+            to_address: recipient.into(),
+            amount,
+        };
+        self.execute_msg(account, vec![send.into()])
+    }
+}
+
+fn cw1_instantiate_msg(host: &Addr) -> StdResult<Binary> {
+    to_binary(&cw1_whitelist::msg::InstantiateMsg {
+        admins: vec![host.into()],
+        mutable: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{coins, from_binary};
+
+    #[test]
+    fn backends_build_their_messages() {
+        let host = Addr::unchecked("host");
+        let account = Addr::unchecked("account");
+        let send: CosmosMsg = BankMsg::Send {
+            to_address: "host".into(),
+            amount: coins(5, "uatom"),
+        }
+        .into();
+
+        let backends = [
+            AccountBackend::Cw1Whitelist,
+            #[cfg(feature = "provenance")]
+            AccountBackend::Provenance,
+        ];
+        for backend in backends {
+            let init: cw1_whitelist::msg::InstantiateMsg =
+                from_binary(&backend.instantiate_msg(&host).unwrap()).unwrap();
+            assert_eq!(init.admins, vec!["host".to_string()]);
+            assert!(!init.mutable);
+            let sweep = backend
+                .sweep_msg(&account, &host, coins(5, "uatom"))
+                .unwrap();
+            let expected = cw1_whitelist::msg::ExecuteMsg::Execute {
+                msgs: vec![send.clone()],
+            };
+            assert_eq!(
+                sweep,
+                WasmMsg::Execute {
+                    contract_addr: "account".into(),
+                    msg: to_binary(&expected).unwrap(),
+                    funds: vec![],
+                }
+            );
+        }
+
+        let backend = AccountBackend::Reflect;
+        let init = backend.instantiate_msg(&host).unwrap();
+        assert_eq!(init.as_slice(), b"{}");
+        let sweep = backend
+            .sweep_msg(&account, &host, coins(5, "uatom"))
+            .unwrap();
+        if let WasmMsg::Execute { msg, .. } = sweep {
+            let msg: ReflectExecuteMsg = from_binary(&msg).unwrap();
+            assert_eq!(msg, ReflectExecuteMsg::ReflectMsg { msgs: vec![send] });
+        } else {
+            panic!("invalid sweep message: {:?}", sweep);
+        }
+    }
+}
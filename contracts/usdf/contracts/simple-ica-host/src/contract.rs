@@ -1,47 +1,282 @@
+use std::slice;
+
+#[cfg(feature = "provenance")]
+use cosmwasm_std::from_slice;
 use cosmwasm_std::{
-    entry_point, from_slice, to_binary, to_vec, wasm_execute, BankMsg, Binary, ContractResult,
-    CosmosMsg, Deps, DepsMut, Empty, Env, Event, Ibc3ChannelOpenResponse, IbcBasicResponse,
-    IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcChannelOpenResponse,
-    IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, MessageInfo,
-    Order, QuerierWrapper, QueryRequest, QueryResponse, Reply, Response, StdError, StdResult,
-    SubMsg, SystemResult, WasmMsg,
+    entry_point, to_binary, to_vec, Addr, BankMsg, Binary, Coin, ContractInfoResponse,
+    ContractResult, CosmosMsg, Deps, DepsMut, Empty, Env, Event, Ibc3ChannelOpenResponse,
+    IbcBasicResponse, IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg,
+    IbcChannelOpenResponse, IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg,
+    IbcReceiveResponse, MessageInfo, Order, QuerierWrapper, QueryRequest, QueryResponse, Reply,
+    Response, StdError, StdResult, SubMsg, SubMsgResult, SystemResult, WasmMsg, WasmQuery,
 };
+use cw2::set_contract_version;
+use cw_storage_plus::Bound;
 use cw_utils::parse_reply_instantiate_data;
+use serde::Serialize;
+use simple_ica::admin::AdminRole;
+use simple_ica::migrate::{is_older, previous_version, Version};
+use simple_ica::trade::{ExchangeExecuteMsg, TradeResponse};
 use simple_ica::{
-    check_order, check_version, BalancesResponse, DispatchResponse, IbcQueryResponse, PacketMsg,
-    StdAck, WhoAmIResponse, IBC_APP_VERSION,
+    check_channel, BalancesResponse, ClaimOrphanedResponse, DispatchResponse, ErrorCode,
+    IbcQueryResponse, IcaVersion, PacketMsg, StdAck, WhoAmIResponse,
 };
 
 use crate::error::ContractError;
 use crate::msg::{
-    AccountInfo, AccountResponse, InstantiateMsg, ListAccountsResponse, QueryMsg, ReflectExecuteMsg,
+    AccountInfo, AccountResponse, ChannelDenylistResponse, ExecuteMsg, InstantiateMsg,
+    ListAccountsResponse, MigrateMsg, OrphanedAccountInfo, OrphanedAccountsResponse,
+    PolicyResponse, QueryMsg, SudoMsg,
+};
+use crate::policy::{ChannelDenylist, Policy};
+use crate::state::{
+    Config, OrphanedAccount, ACCOUNTS, ADMINS, CHANNEL_CONNECTIONS, CHANNEL_DENYLISTS,
+    CHANNEL_VERSIONS, CONFIG, ORPHANED_ACCOUNTS, PENDING, POLICY, RESULTS,
 };
-use crate::state::{Config, ACCOUNTS, CONFIG, PENDING, RESULTS};
+
+// version info for migration info
+const CONTRACT_NAME: &str = "crates.io:simple-ica-host";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub const RECEIVE_DISPATCH_ID: u64 = 1234;
+pub const RECEIVE_TRADE_ID: u64 = 1236;
 pub const INIT_CALLBACK_ID: u64 = 7890;
 
+// pagination of account migrations
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
 #[entry_point]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
     msg: InstantiateMsg,
 ) -> StdResult<Response> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    ADMINS.set(deps.storage, &info.sender, AdminRole::Owner)?;
     // we store the reflect_id for creating accounts later
     let cfg = Config {
         cw1_code_id: msg.cw1_code_id,
+        account_backend: msg.account_backend.unwrap_or_default(),
     };
     CONFIG.save(deps.storage, &cfg)?;
+    POLICY.save(deps.storage, &msg.policy.unwrap_or_default())?;
 
     Ok(Response::new())
 }
 
+#[entry_point]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let previous = previous_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    // deployments from before the version was stored are 0.2 or older, which had no policy
+    // and no admins
+    if is_older(previous.as_ref(), &Version::new(0, 3, 0))
+        && POLICY.may_load(deps.storage)?.is_none()
+    {
+        POLICY.save(deps.storage, &Policy::default())?;
+    }
+    // without an owner, the account code could never be upgraded
+    if !ADMINS.has_owner(deps.storage)? {
+        let owner = msg.owner.ok_or(ContractError::OwnerRequired)?;
+        let owner = deps.api.addr_validate(&owner)?;
+        ADMINS.set(deps.storage, &owner, AdminRole::Owner)?;
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let from_version = previous.map_or_else(|| "none".to_string(), |v| v.to_string());
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", from_version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
+#[entry_point]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::Admin(msg) => Ok(ADMINS.execute(deps, &info, msg)?),
+        ExecuteMsg::UpdateCw1CodeId { code_id } => execute_update_cw1_code_id(deps, info, code_id),
+        ExecuteMsg::MigrateAccounts {
+            code_id,
+            msg,
+            start_after,
+            limit,
+            orphaned: false,
+        } => execute_migrate_accounts(deps, env, info, code_id, msg, start_after, limit),
+        ExecuteMsg::MigrateAccounts {
+            code_id,
+            msg,
+            start_after,
+            limit,
+            orphaned: true,
+        } => execute_migrate_orphaned_accounts(deps, env, info, code_id, msg, start_after, limit),
+    }
+}
+
+pub fn execute_update_cw1_code_id(
+    deps: DepsMut,
+    info: MessageInfo,
+    code_id: u64,
+) -> Result<Response, ContractError> {
+    ADMINS.assert_owner(deps.storage, &info.sender)?;
+    CONFIG.update(deps.storage, |mut cfg| -> StdResult<_> {
+        cfg.cw1_code_id = code_id;
+        Ok(cfg)
+    })?;
+    Ok(Response::new()
+        .add_attribute("action", "update_cw1_code_id")
+        .add_attribute("code_id", code_id.to_string()))
+}
+
+pub fn execute_migrate_accounts(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    code_id: u64,
+    msg: Binary,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    ADMINS.assert_owner(deps.storage, &info.sender)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+    let accounts = ACCOUNTS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    migrate_page(deps.as_ref(), &env, code_id, msg, accounts, limit)
+}
+
+/// Like `execute_migrate_accounts`, for the orphaned accounts. They are named by
+/// `connection_id/port_id/channel_id`, as IBC identifiers have no `/`.
+pub fn execute_migrate_orphaned_accounts(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    code_id: u64,
+    msg: Binary,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    ADMINS.assert_owner(deps.storage, &info.sender)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = match start_after
+        .as_deref()
+        .map(|key| key.split('/').collect::<Vec<_>>())
+    {
+        Some(parts) => match parts[..] {
+            [connection_id, port_id, channel_id] => Some((connection_id, port_id, channel_id)),
+            _ => return Err(StdError::generic_err("Invalid orphaned account key").into()),
+        },
+        None => None,
+    };
+    let accounts = ORPHANED_ACCOUNTS
+        .range(
+            deps.storage,
+            start.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| {
+            let ((connection_id, port_id, channel_id), orphan) = item?;
+            let key = format!("{}/{}/{}", connection_id, port_id, channel_id);
+            Ok((key, orphan.account))
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    migrate_page(deps.as_ref(), &env, code_id, msg, accounts, limit)
+}
+
+/// Migrates the accounts of a page, named by their key
+fn migrate_page(
+    deps: Deps,
+    env: &Env,
+    code_id: u64,
+    msg: Binary,
+    accounts: Vec<(String, Addr)>,
+    limit: usize,
+) -> Result<Response, ContractError> {
+    let mut res = Response::new().add_attribute("action", "migrate_accounts");
+    for (key, account) in &accounts {
+        let query = WasmQuery::ContractInfo {
+            contract_addr: account.into(),
+        };
+        let info: ContractInfoResponse = deps.querier.query(&query.into())?;
+        // accounts created before the host was their admin can not be migrated
+        let migrate =
+            info.admin.as_deref() == Some(env.contract.address.as_str()) && info.code_id != code_id;
+        if !migrate {
+            res = res.add_attribute("skipped", key);
+            continue;
+        }
+        res = res
+            .add_message(WasmMsg::Migrate {
+                contract_addr: account.into(),
+                new_code_id: code_id,
+                msg: msg.clone(),
+            })
+            .add_attribute("migrated", key);
+    }
+    // a full page may be followed by more
+    if accounts.len() == limit {
+        if let Some((key, _)) = accounts.last() {
+            res = res.add_attribute("next_start_after", key);
+        }
+    }
+    Ok(res)
+}
+
+#[entry_point]
+pub fn sudo(deps: DepsMut, _env: Env, msg: SudoMsg) -> StdResult<Response> {
+    match msg {
+        SudoMsg::UpdatePolicy { policy } => sudo_update_policy(deps, policy),
+        SudoMsg::UpdateChannelDenylist {
+            channel_id,
+            denylist,
+        } => sudo_update_channel_denylist(deps, channel_id, denylist),
+    }
+}
+
+pub fn sudo_update_policy(deps: DepsMut, policy: Policy) -> StdResult<Response> {
+    POLICY.save(deps.storage, &policy)?;
+    Ok(Response::new().add_attribute("action", "update_policy"))
+}
+
+pub fn sudo_update_channel_denylist(
+    deps: DepsMut,
+    channel_id: String,
+    denylist: ChannelDenylist,
+) -> StdResult<Response> {
+    if denylist.is_empty() {
+        CHANNEL_DENYLISTS.remove(deps.storage, &channel_id);
+    } else {
+        CHANNEL_DENYLISTS.save(deps.storage, &channel_id, &denylist)?;
+    }
+    Ok(Response::new()
+        .add_attribute("action", "update_channel_denylist")
+        .add_attribute("channel_id", channel_id))
+}
+
 #[entry_point]
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
     match msg {
         QueryMsg::Account { channel_id } => to_binary(&query_account(deps, channel_id)?),
         QueryMsg::ListAccounts {} => to_binary(&query_list_accounts(deps)?),
+        QueryMsg::Config {} => to_binary(&CONFIG.load(deps.storage)?),
+        QueryMsg::Admins {} => to_binary(&ADMINS.query(deps.storage)?),
+        QueryMsg::Policy {} => to_binary(&query_policy(deps)?),
+        QueryMsg::ChannelDenylist { channel_id } => {
+            to_binary(&query_channel_denylist(deps, channel_id)?)
+        }
+        QueryMsg::OrphanedAccounts {
+            connection_id,
+            port_id,
+        } => to_binary(&query_orphaned_accounts(deps, connection_id, port_id)?),
     }
 }
 
@@ -66,6 +301,41 @@ pub fn query_list_accounts(deps: Deps) -> StdResult<ListAccountsResponse> {
     Ok(ListAccountsResponse { accounts })
 }
 
+pub fn query_orphaned_accounts(
+    deps: Deps,
+    connection_id: String,
+    port_id: String,
+) -> StdResult<OrphanedAccountsResponse> {
+    let accounts = ORPHANED_ACCOUNTS
+        .prefix((&connection_id, &port_id))
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (channel_id, orphan) = item?;
+            Ok(OrphanedAccountInfo {
+                account: orphan.account.into(),
+                channel_id,
+                claimable: orphan.claimable,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(OrphanedAccountsResponse { accounts })
+}
+
+pub fn query_policy(deps: Deps) -> StdResult<PolicyResponse> {
+    let policy = POLICY.may_load(deps.storage)?.unwrap_or_default();
+    Ok(PolicyResponse { policy })
+}
+
+pub fn query_channel_denylist(
+    deps: Deps,
+    channel_id: String,
+) -> StdResult<ChannelDenylistResponse> {
+    let denylist = CHANNEL_DENYLISTS
+        .may_load(deps.storage, &channel_id)?
+        .unwrap_or_default();
+    Ok(ChannelDenylistResponse { denylist })
+}
+
 #[entry_point]
 /// enforces ordering and versioing constraints
 pub fn ibc_channel_open(
@@ -75,16 +345,14 @@ pub fn ibc_channel_open(
 ) -> Result<IbcChannelOpenResponse, ContractError> {
     let channel = msg.channel();
 
-    check_order(&channel.order)?;
-    // In ibcv3 we don't check the version string passed in the message
-    // and only check the counterparty version.
-    if let Some(counter_version) = msg.counterparty_version() {
-        check_version(counter_version)?;
-    }
+    // In ibcv3 we check the counterparty version if we have one, as the version
+    // string passed in the message may be empty
+    let version = msg.counterparty_version().unwrap_or(&channel.version);
+    let app_version = check_channel(&channel.order, version)?;
 
     // We return the version we need (which could be different than the counterparty version)
     Ok(Some(Ibc3ChannelOpenResponse {
-        version: IBC_APP_VERSION.to_string(),
+        version: app_version.to_string(),
     }))
 }
 
@@ -94,19 +362,35 @@ pub fn ibc_channel_connect(
     deps: DepsMut,
     env: Env,
     msg: IbcChannelConnectMsg,
-) -> StdResult<IbcBasicResponse> {
+) -> Result<IbcBasicResponse, ContractError> {
     let channel = msg.channel();
     let cfg = CONFIG.load(deps.storage)?;
     let chan_id = &channel.endpoint.channel_id;
 
-    let init_msg = cw1_whitelist::msg::InstantiateMsg {
-        admins: vec![env.contract.address.into_string()],
-        mutable: false,
-    };
+    // remember which version to speak on this channel
+    let version = check_channel(&channel.order, &channel.version)?;
+    CHANNEL_VERSIONS.save(deps.storage, chan_id, &version)?;
+    CHANNEL_CONNECTIONS.save(deps.storage, chan_id, &channel.connection_id)?;
+
+    // a controller gets back the account of a channel it lost before creating a new one
+    let identity = (
+        channel.connection_id.as_str(),
+        channel.counterparty_endpoint.port_id.as_str(),
+    );
+    let orphan = ORPHANED_ACCOUNTS
+        .prefix(identity)
+        .range(deps.storage, None, None, Order::Ascending)
+        .next()
+        .transpose()?;
+    if let Some((old_channel_id, orphan)) = orphan {
+        return reattach_account(deps, chan_id, identity, old_channel_id, orphan);
+    }
+
+    // the host is the admin, so it can migrate the account to new code
     let msg = WasmMsg::Instantiate {
-        admin: None,
+        admin: Some(env.contract.address.to_string()),
         code_id: cfg.cw1_code_id,
-        msg: to_binary(&init_msg)?,
+        msg: cfg.account_backend.instantiate_msg(&env.contract.address)?,
         funds: vec![],
         label: format!("ibc-reflect-{}", chan_id),
     };
@@ -122,9 +406,36 @@ pub fn ibc_channel_connect(
         .add_event(Event::new("ibc").add_attribute("channel", "connect")))
 }
 
+// connects an orphaned account to the new channel and returns its swept funds
+fn reattach_account(
+    deps: DepsMut,
+    chan_id: &str,
+    (connection_id, port_id): (&str, &str),
+    old_channel_id: String,
+    orphan: OrphanedAccount,
+) -> Result<IbcBasicResponse, ContractError> {
+    ORPHANED_ACCOUNTS.remove(deps.storage, (connection_id, port_id, &old_channel_id));
+    ACCOUNTS.save(deps.storage, chan_id, &orphan.account)?;
+
+    let mut res = IbcBasicResponse::new()
+        .add_attribute("action", "ibc_connect")
+        .add_attribute("channel_id", chan_id)
+        .add_attribute("recovered_account", &orphan.account)
+        .add_attribute("recovered_from_channel", old_channel_id)
+        .add_event(Event::new("ibc").add_attribute("channel", "connect"));
+    if !orphan.claimable.is_empty() {
+        res = res.add_message(BankMsg::Send {
+            to_address: orphan.account.into(),
+            amount: orphan.claimable,
+        });
+    }
+    Ok(res)
+}
+
 #[entry_point]
 /// On closed channel, we take all tokens from reflect contract to this contract.
-/// We also delete the channel entry from accounts.
+/// The account is kept, so the controller can reattach it from a new channel
+/// and get the funds back.
 pub fn ibc_channel_close(
     deps: DepsMut,
     env: Env,
@@ -135,24 +446,34 @@ pub fn ibc_channel_close(
     let channel_id = channel.endpoint.channel_id.as_str();
     let reflect_addr = ACCOUNTS.load(deps.storage, channel_id)?;
     ACCOUNTS.remove(deps.storage, channel_id);
+    CHANNEL_VERSIONS.remove(deps.storage, channel_id);
+    CHANNEL_CONNECTIONS.remove(deps.storage, channel_id);
 
     // transfer current balance if any to this host contract
     let amount = deps.querier.query_all_balances(&reflect_addr)?;
     let messages: Vec<SubMsg<Empty>> = if !amount.is_empty() {
-        let bank_msg = BankMsg::Send {
-            to_address: env.contract.address.into(),
-            amount,
-        };
-        let reflect_msg = ReflectExecuteMsg::ReflectMsg {
-            msgs: vec![bank_msg.into()],
-        };
-        let wasm_msg = wasm_execute(reflect_addr, &reflect_msg, vec![])?;
+        let cfg = CONFIG.load(deps.storage)?;
+        let wasm_msg =
+            cfg.account_backend
+                .sweep_msg(&reflect_addr, &env.contract.address, amount.clone())?;
         vec![SubMsg::new(wasm_msg)]
     } else {
         vec![]
     };
     let rescue_funds = !messages.is_empty();
 
+    // remember the account and what we took from it for the controller
+    let key = (
+        channel.connection_id.as_str(),
+        channel.counterparty_endpoint.port_id.as_str(),
+        channel_id,
+    );
+    let orphan = OrphanedAccount {
+        account: reflect_addr,
+        claimable: amount,
+    };
+    ORPHANED_ACCOUNTS.save(deps.storage, key, &orphan)?;
+
     Ok(IbcBasicResponse::new()
         .add_submessages(messages)
         .add_attribute("action", "ibc_close")
@@ -164,15 +485,25 @@ pub fn ibc_channel_close(
 pub fn reply(deps: DepsMut, _env: Env, reply: Reply) -> Result<Response, ContractError> {
     match reply.id {
         RECEIVE_DISPATCH_ID => reply_dispatch_callback(deps, reply),
+        RECEIVE_TRADE_ID => reply_trade_callback(reply),
         INIT_CALLBACK_ID => reply_init_callback(deps, reply),
         _ => Err(ContractError::InvalidReplyId),
     }
 }
 
 pub fn reply_dispatch_callback(deps: DepsMut, reply: Reply) -> Result<Response, ContractError> {
+    // the dispatched messages were reverted, overwrite the ack with an error
+    let response = match reply.result {
+        SubMsgResult::Ok(response) => response,
+        SubMsgResult::Err(err) => {
+            let data = StdAck::fail_with_code(ErrorCode::ExecutionFailed, err);
+            return Ok(Response::new().set_data(data));
+        }
+    };
+
     // add the new result to the current tracker
     let mut results = RESULTS.load(deps.storage)?;
-    results.push(reply.result.unwrap().data.unwrap_or_default());
+    results.push(response.data.unwrap_or_default());
     RESULTS.save(deps.storage, &results)?;
 
     // update result data if this is the last
@@ -180,6 +511,18 @@ pub fn reply_dispatch_callback(deps: DepsMut, reply: Reply) -> Result<Response,
     Ok(Response::new().set_data(data))
 }
 
+/// The ack of a trade is what the exchange reported, or why it failed
+pub fn reply_trade_callback(reply: Reply) -> Result<Response, ContractError> {
+    let data = match reply.result {
+        SubMsgResult::Ok(response) => match TradeResponse::from_events(&response.events) {
+            Some(trade) => StdAck::success(&trade),
+            None => StdAck::fail_with_code(ErrorCode::Internal, "No trade in the events"),
+        },
+        SubMsgResult::Err(err) => StdAck::fail_with_code(ErrorCode::ExecutionFailed, err),
+    };
+    Ok(Response::new().set_data(data))
+}
+
 pub fn reply_init_callback(deps: DepsMut, reply: Reply) -> Result<Response, ContractError> {
     // we use storage to pass info from the caller to the reply
     let id = PENDING.load(deps.storage)?;
@@ -208,39 +551,92 @@ pub fn ibc_packet_receive(
     _env: Env,
     msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let packet = msg.packet;
-    // which local channel did this packet come on
-    let caller = packet.dest.channel_id;
-    let msg: PacketMsg = from_slice(&packet.data)?;
-    match msg {
-        PacketMsg::Dispatch { msgs, .. } => receive_dispatch(deps, caller, msgs),
-        PacketMsg::IbcQuery { msgs, .. } => receive_query(deps.as_ref(), msgs),
-        PacketMsg::WhoAmI {} => receive_who_am_i(deps, caller),
-        PacketMsg::Balances {} => receive_balances(deps, caller),
-    }
+    // put this in a closure so we can convert all error responses into acknowledgements
+    (|| {
+        let packet = msg.packet;
+        // which local channel did this packet come on
+        let caller = packet.dest.channel_id;
+        let controller_port = packet.src.port_id;
+        let version = CHANNEL_VERSIONS
+            .may_load(deps.storage, &caller)?
+            .unwrap_or(IcaVersion::V2);
+        let msg = version
+            .decode_packet(&packet.data)
+            .map_err(|err| ContractError::InvalidPacket(err.to_string()))?;
+        match msg {
+            PacketMsg::Dispatch { msgs, .. } => receive_dispatch(deps, caller, msgs),
+            PacketMsg::ProvenanceDispatch { msgs, .. } => {
+                receive_provenance_dispatch(deps, caller, msgs)
+            }
+            PacketMsg::IbcQuery { msgs, .. } => receive_query(deps.as_ref(), caller, msgs),
+            PacketMsg::ProvenanceQuery { msgs, .. } => {
+                receive_provenance_query(deps.as_ref(), caller, msgs)
+            }
+            PacketMsg::Trade {
+                exchange, offer, ..
+            } => receive_trade(deps, caller, exchange, offer),
+            PacketMsg::WhoAmI {} => receive_who_am_i(deps, caller),
+            PacketMsg::Balances {} => receive_balances(deps, caller),
+            PacketMsg::ClaimOrphaned { channel_id } => {
+                receive_claim_orphaned(deps, caller, controller_port, channel_id)
+            }
+        }
+    })()
+    .or_else(|err| {
+        // we try to capture all app-level errors and convert them into
+        // acknowledgement packets that contain an error code.
+        let acknowledgement = StdAck::fail_with_code(err.code(), &err);
+        Ok(IbcReceiveResponse::new()
+            .set_ack(acknowledgement)
+            .add_attribute("action", "receive_error")
+            .add_attribute("error_code", err.code().as_str())
+            .add_attribute("error", err.to_string()))
+    })
 }
 
-fn unparsed_query(
+// finds the account registered for the channel the packet came in on
+fn load_account(deps: Deps, caller: &str) -> Result<Addr, ContractError> {
+    ACCOUNTS
+        .may_load(deps.storage, caller)?
+        .ok_or_else(|| ContractError::UnknownChannel(caller.to_string()))
+}
+
+// loads the host policy and the denylist of the channel the packet came in on
+fn load_policy(deps: Deps, caller: &str) -> StdResult<(Policy, ChannelDenylist)> {
+    let policy = POLICY.may_load(deps.storage)?.unwrap_or_default();
+    let denylist = CHANNEL_DENYLISTS
+        .may_load(deps.storage, caller)?
+        .unwrap_or_default();
+    Ok((policy, denylist))
+}
+
+fn unparsed_query<C: Serialize>(
     querier: QuerierWrapper<'_, Empty>,
-    request: &QueryRequest<Empty>,
+    request: &QueryRequest<C>,
 ) -> Result<Binary, ContractError> {
     let raw = to_vec(request)?;
     match querier.raw_query(&raw) {
-        SystemResult::Err(system_err) => {
-            Err(StdError::generic_err(format!("Querier system error: {}", system_err)).into())
-        }
-        SystemResult::Ok(ContractResult::Err(contract_err)) => {
-            Err(StdError::generic_err(format!("Querier contract error: {}", contract_err)).into())
-        }
+        SystemResult::Err(system_err) => Err(ContractError::QueryFailed(format!(
+            "Querier system error: {}",
+            system_err
+        ))),
+        SystemResult::Ok(ContractResult::Err(contract_err)) => Err(ContractError::QueryFailed(
+            format!("Querier contract error: {}", contract_err),
+        )),
         SystemResult::Ok(ContractResult::Ok(value)) => Ok(value),
     }
 }
 
-// processes IBC query
-fn receive_query(
+// processes IBC query. The chain runs custom queries, so they are only sent where the chain
+// knows them
+fn receive_query<C: Serialize>(
     deps: Deps,
-    msgs: Vec<QueryRequest<Empty>>,
+    caller: String,
+    msgs: Vec<QueryRequest<C>>,
 ) -> Result<IbcReceiveResponse, ContractError> {
+    let (policy, denylist) = load_policy(deps, &caller)?;
+    policy.check_queries(&denylist, &msgs)?;
+
     let mut results = vec![];
 
     for query in msgs {
@@ -255,9 +651,36 @@ fn receive_query(
         .add_attribute("action", "receive_ibc_query"))
 }
 
+// processes PacketMsg::ProvenanceQuery variant, where each query is the json of a
+// QueryRequest<ProvenanceQuery>
+#[cfg(feature = "provenance")]
+fn receive_provenance_query(
+    deps: Deps,
+    caller: String,
+    msgs: Vec<Binary>,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let msgs = msgs
+        .iter()
+        .map(|msg| from_slice::<QueryRequest<provwasm_std::ProvenanceQuery>>(msg))
+        .collect::<StdResult<Vec<_>>>()
+        .map_err(|err| ContractError::InvalidPacket(err.to_string()))?;
+    receive_query(deps, caller, msgs)
+}
+
+#[cfg(not(feature = "provenance"))]
+fn receive_provenance_query(
+    _deps: Deps,
+    _caller: String,
+    _msgs: Vec<Binary>,
+) -> Result<IbcReceiveResponse, ContractError> {
+    Err(ContractError::ProvenanceUnsupported(
+        "this host is not built for Provenance".into(),
+    ))
+}
+
 // processes PacketMsg::WhoAmI variant
 fn receive_who_am_i(deps: DepsMut, caller: String) -> Result<IbcReceiveResponse, ContractError> {
-    let account = ACCOUNTS.load(deps.storage, &caller)?;
+    let account = load_account(deps.as_ref(), &caller)?;
     let response = WhoAmIResponse {
         account: account.into(),
     };
@@ -270,7 +693,7 @@ fn receive_who_am_i(deps: DepsMut, caller: String) -> Result<IbcReceiveResponse,
 
 // processes PacketMsg::Balances variant
 fn receive_balances(deps: DepsMut, caller: String) -> Result<IbcReceiveResponse, ContractError> {
-    let account = ACCOUNTS.load(deps.storage, &caller)?;
+    let account = load_account(deps.as_ref(), &caller)?;
     let balances = deps.querier.query_all_balances(&account)?;
     let response = BalancesResponse {
         account: account.into(),
@@ -283,24 +706,113 @@ fn receive_balances(deps: DepsMut, caller: String) -> Result<IbcReceiveResponse,
         .add_attribute("action", "receive_balances"))
 }
 
+// processes PacketMsg::ClaimOrphaned variant. Only the controller of the orphaned
+// account, on the same connection and port, may claim its funds.
+fn receive_claim_orphaned(
+    deps: DepsMut,
+    caller: String,
+    controller_port: String,
+    orphaned_channel_id: String,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let account = load_account(deps.as_ref(), &caller)?;
+    let connection_id = CHANNEL_CONNECTIONS
+        .may_load(deps.storage, &caller)?
+        .ok_or_else(|| ContractError::UnknownOrphan(orphaned_channel_id.clone()))?;
+    let key = (
+        connection_id.as_str(),
+        controller_port.as_str(),
+        orphaned_channel_id.as_str(),
+    );
+    let mut orphan = ORPHANED_ACCOUNTS
+        .may_load(deps.storage, key)?
+        .ok_or_else(|| ContractError::UnknownOrphan(orphaned_channel_id.clone()))?;
+
+    // the account stays orphaned, so it can still be reattached
+    let claimed = std::mem::take(&mut orphan.claimable);
+    ORPHANED_ACCOUNTS.save(deps.storage, key, &orphan)?;
+
+    let mut res = IbcReceiveResponse::new()
+        .add_attribute("action", "receive_claim_orphaned")
+        .add_attribute("orphaned_channel_id", orphaned_channel_id);
+    if !claimed.is_empty() {
+        res = res.add_message(BankMsg::Send {
+            to_address: account.to_string(),
+            amount: claimed.clone(),
+        });
+    }
+    let response = ClaimOrphanedResponse {
+        account: account.into(),
+        claimed,
+    };
+    Ok(res.set_ack(StdAck::success(&response)))
+}
+
 // processes PacketMsg::Dispatch variant
 fn receive_dispatch(
     deps: DepsMut,
     caller: String,
     msgs: Vec<CosmosMsg>,
 ) -> Result<IbcReceiveResponse, ContractError> {
+    let (policy, denylist) = load_policy(deps.as_ref(), &caller)?;
+    policy.check_msgs(&denylist, &msgs)?;
+
     // what is the reflect contract here
-    let reflect_addr = ACCOUNTS.load(deps.storage, &caller)?;
+    let reflect_addr = load_account(deps.as_ref(), &caller)?;
+
+    // create the message to re-dispatch to the reflect contract
+    let cfg = CONFIG.load(deps.storage)?;
+    let wasm_msg = cfg.account_backend.execute_msg(&reflect_addr, msgs)?;
+    dispatch_to_account(deps, wasm_msg, "receive_dispatch")
+}
+
+// processes PacketMsg::ProvenanceDispatch variant, where each message is the json of a
+// CosmosMsg<ProvenanceMsg>
+#[cfg(feature = "provenance")]
+fn receive_provenance_dispatch(
+    deps: DepsMut,
+    caller: String,
+    msgs: Vec<Binary>,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let msgs = msgs
+        .iter()
+        .map(|msg| from_slice::<CosmosMsg<provwasm_std::ProvenanceMsg>>(msg))
+        .collect::<StdResult<Vec<_>>>()
+        .map_err(|err| ContractError::InvalidPacket(err.to_string()))?;
+    let (policy, denylist) = load_policy(deps.as_ref(), &caller)?;
+    policy.check_msgs(&denylist, &msgs)?;
+
+    let reflect_addr = load_account(deps.as_ref(), &caller)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let wasm_msg = cfg
+        .account_backend
+        .provenance_execute_msg(&reflect_addr, msgs)?;
+    dispatch_to_account(deps, wasm_msg, "receive_provenance_dispatch")
+}
 
+// without the provenance feature the host can not even parse them
+#[cfg(not(feature = "provenance"))]
+fn receive_provenance_dispatch(
+    _deps: DepsMut,
+    _caller: String,
+    _msgs: Vec<Binary>,
+) -> Result<IbcReceiveResponse, ContractError> {
+    Err(ContractError::ProvenanceUnsupported(
+        "this host is not built for Provenance".into(),
+    ))
+}
+
+// runs the message on the account in a submessage, whose reply sets the results
+fn dispatch_to_account(
+    deps: DepsMut,
+    wasm_msg: WasmMsg,
+    action: &str,
+) -> Result<IbcReceiveResponse, ContractError> {
     // let them know we're fine
     let response = DispatchResponse { results: vec![] };
     let acknowledgement = StdAck::success(&response);
-    // create the message to re-dispatch to the reflect contract
-    let reflect_msg = cw1_whitelist::msg::ExecuteMsg::Execute { msgs };
-    let wasm_msg = wasm_execute(reflect_addr, &reflect_msg, vec![])?;
 
-    // we wrap it in a submessage to properly report results
-    let msg = SubMsg::reply_on_success(wasm_msg, RECEIVE_DISPATCH_ID);
+    // we wrap it in a submessage to properly report results and errors
+    let msg = SubMsg::reply_always(wasm_msg, RECEIVE_DISPATCH_ID);
 
     // reset the data field
     RESULTS.save(deps.storage, &vec![])?;
@@ -308,7 +820,36 @@ fn receive_dispatch(
     Ok(IbcReceiveResponse::new()
         .set_ack(acknowledgement)
         .add_submessage(msg)
-        .add_attribute("action", "receive_dispatch"))
+        .add_attribute("action", action))
+}
+
+// processes PacketMsg::Trade variant, the account trades the offer on the exchange
+fn receive_trade(
+    deps: DepsMut,
+    caller: String,
+    exchange: String,
+    offer: Coin,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let trade: CosmosMsg = WasmMsg::Execute {
+        contract_addr: exchange,
+        msg: to_binary(&ExchangeExecuteMsg::Trade {})?,
+        funds: vec![offer],
+    }
+    .into();
+    let (policy, denylist) = load_policy(deps.as_ref(), &caller)?;
+    policy.check_msgs(&denylist, slice::from_ref(&trade))?;
+
+    let reflect_addr = load_account(deps.as_ref(), &caller)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let wasm_msg = cfg
+        .account_backend
+        .execute_msg(&reflect_addr, vec![trade])?;
+
+    // the reply sets the ack, with the result of the trade
+    let msg = SubMsg::reply_always(wasm_msg, RECEIVE_TRADE_ID);
+    Ok(IbcReceiveResponse::new()
+        .add_submessage(msg)
+        .add_attribute("action", "receive_trade"))
 }
 
 #[entry_point]
@@ -335,15 +876,22 @@ pub fn ibc_packet_timeout(
 mod tests {
     use super::*;
     use cosmwasm_std::testing::{
-        mock_dependencies, mock_env, mock_ibc_channel_close_init, mock_ibc_channel_connect_ack,
-        mock_ibc_channel_open_init, mock_ibc_channel_open_try, mock_ibc_packet_recv, mock_info,
-        mock_wasmd_attr, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+        mock_dependencies, mock_env, mock_ibc_channel, mock_ibc_channel_close_init,
+        mock_ibc_channel_connect_ack, mock_ibc_channel_open_init, mock_ibc_channel_open_try,
+        mock_ibc_packet_recv, mock_info, mock_wasmd_attr, MockApi, MockQuerier, MockStorage,
+        MOCK_CONTRACT_ADDR,
     };
     use cosmwasm_std::{
-        attr, coin, coins, from_slice, BankMsg, Binary, OwnedDeps, SubMsgResponse, SubMsgResult,
-        WasmMsg,
+        attr, coin, coins, from_slice, BankMsg, Binary, OwnedDeps, ReplyOn, Storage,
+        SubMsgResponse, WasmMsg, WasmQuery,
     };
-    use simple_ica::{APP_ORDER, BAD_APP_ORDER};
+    use simple_ica::admin::AdminExecuteMsg;
+    use simple_ica::trade::TRADE_ACTION;
+    use simple_ica::{
+        IcaVersion, PacketEnvelope, SimpleIcaError, APP_ORDER, BAD_APP_ORDER, IBC_APP_VERSION,
+    };
+
+    use crate::policy::{MsgKind, QueryKind};
 
     const CREATOR: &str = "creator";
     // code id of the reflect contract
@@ -355,6 +903,8 @@ mod tests {
         let mut deps = mock_dependencies();
         let msg = InstantiateMsg {
             cw1_code_id: REFLECT_ID,
+            policy: None,
+            account_backend: None,
         };
         let info = mock_info(CREATOR, &[]);
         let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
@@ -380,16 +930,25 @@ mod tests {
 
     // connect will run through the entire handshake to set up a proper connect and
     // save the account (tested in detail in `proper_handshake_flow`)
-    fn connect(mut deps: DepsMut, channel_id: &str, account: impl Into<String>) {
+    fn connect(deps: DepsMut, channel_id: &str, account: impl Into<String>) {
+        connect_with(deps, channel_id, IBC_APP_VERSION, account)
+    }
+
+    // same as connect, but for any version
+    fn connect_with(
+        mut deps: DepsMut,
+        channel_id: &str,
+        version: &str,
+        account: impl Into<String>,
+    ) {
         let account: String = account.into();
 
-        let handshake_open = mock_ibc_channel_open_init(channel_id, APP_ORDER, IBC_APP_VERSION);
+        let handshake_open = mock_ibc_channel_open_init(channel_id, APP_ORDER, version);
         // first we try to open with a valid handshake
         ibc_channel_open(deps.branch(), mock_env(), handshake_open).unwrap();
 
         // then we connect (with counter-party version set)
-        let handshake_connect =
-            mock_ibc_channel_connect_ack(channel_id, APP_ORDER, IBC_APP_VERSION);
+        let handshake_connect = mock_ibc_channel_connect_ack(channel_id, APP_ORDER, version);
         let res = ibc_channel_connect(deps.branch(), mock_env(), handshake_connect).unwrap();
         assert_eq!(1, res.messages.len());
         assert_eq!(1, res.events.len());
@@ -414,12 +973,69 @@ mod tests {
     fn instantiate_works() {
         let mut deps = mock_dependencies();
 
-        let msg = InstantiateMsg { cw1_code_id: 17 };
+        let msg = InstantiateMsg {
+            cw1_code_id: 17,
+            policy: None,
+            account_backend: None,
+        };
         let info = mock_info("creator", &[]);
         let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
         assert_eq!(0, res.messages.len())
     }
 
+    #[test]
+    fn migrate_state_of_v0_2() {
+        let mut deps = mock_dependencies();
+        // 0.2 stored no contract version, no policy and no admins
+        deps.storage.set(b"config", br#"{"cw1_code_id":17}"#);
+        ACCOUNTS
+            .save(
+                &mut deps.storage,
+                "channel-1",
+                &Addr::unchecked(REFLECT_ADDR),
+            )
+            .unwrap();
+
+        let msg = MigrateMsg {
+            owner: Some("gov".into()),
+        };
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg { owner: None }).unwrap_err();
+        assert_eq!(err, ContractError::OwnerRequired);
+        let res = migrate(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.attributes[1], attr("from_version", "none"));
+        let role = ADMINS.role(&deps.storage, &Addr::unchecked("gov")).unwrap();
+        assert_eq!(role, Some(AdminRole::Owner));
+        let version = cw2::get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.version, CONTRACT_VERSION);
+        assert_eq!(POLICY.load(&deps.storage).unwrap(), Policy::default());
+        assert_eq!(CONFIG.load(&deps.storage).unwrap().cw1_code_id, 17);
+        let account = query_account(deps.as_ref(), "channel-1".into()).unwrap();
+        assert_eq!(account.account, Some(REFLECT_ADDR.into()));
+
+        // a policy set later is kept by the next migration
+        let policy = Policy {
+            allowed_msgs: Some(vec![MsgKind::Bank]),
+            ..Policy::default()
+        };
+        sudo(deps.as_mut(), mock_env(), SudoMsg::UpdatePolicy { policy }).unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg { owner: None }).unwrap();
+        assert_eq!(res.attributes[1], attr("from_version", CONTRACT_VERSION));
+        let policy = POLICY.load(&deps.storage).unwrap();
+        assert_eq!(policy.allowed_msgs, Some(vec![MsgKind::Bank]));
+
+        // a later migration sets an owner if the last one renounced
+        let renounce = ExecuteMsg::Admin(AdminExecuteMsg::RenounceAdmin {});
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), renounce).unwrap();
+        let msg = MigrateMsg {
+            owner: Some("council".into()),
+        };
+        migrate(deps.as_mut(), mock_env(), msg).unwrap();
+        let role = ADMINS
+            .role(&deps.storage, &Addr::unchecked("council"))
+            .unwrap();
+        assert_eq!(role, Some(AdminRole::Owner));
+    }
+
     #[test]
     fn enforce_version_in_handshake() {
         let mut deps = setup();
@@ -434,6 +1050,41 @@ mod tests {
         ibc_channel_open(deps.as_mut(), mock_env(), valid_handshake).unwrap();
     }
 
+    #[test]
+    fn negotiate_version_in_handshake() {
+        let mut deps = setup();
+
+        // we pick the highest version both sides support
+        let proposal = "simple-ica-v3,simple-ica-v2";
+        let handshake = mock_ibc_channel_open_try("channel-12", APP_ORDER, proposal);
+        let res = ibc_channel_open(deps.as_mut(), mock_env(), handshake).unwrap();
+        assert_eq!(res.unwrap().version, "simple-ica-v3");
+
+        // and still talk to older controllers
+        let handshake = mock_ibc_channel_open_try("channel-12", APP_ORDER, IBC_APP_VERSION);
+        let res = ibc_channel_open(deps.as_mut(), mock_env(), handshake).unwrap();
+        assert_eq!(res.unwrap().version, IBC_APP_VERSION);
+
+        // packets on a v3 channel are wrapped in an envelope
+        let channel_id = "channel-3";
+        connect_with(deps.as_mut(), channel_id, "simple-ica-v3", "acct-3");
+        let packet = PacketEnvelope {
+            version: IcaVersion::V3,
+            packet: PacketMsg::WhoAmI {},
+        };
+        let msg = mock_ibc_packet_recv(channel_id, &packet).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        let response: WhoAmIResponse = ack.unwrap_into();
+        assert_eq!(response.account, "acct-3");
+
+        // so a v2 packet is rejected there
+        let msg = mock_ibc_packet_recv(channel_id, &PacketMsg::WhoAmI {}).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        assert_eq!(ack.error_code(), Some(ErrorCode::InvalidPacket));
+    }
+
     #[test]
     fn proper_handshake_flow() {
         let mut deps = setup();
@@ -458,7 +1109,7 @@ mod tests {
             label,
         }) = &res.messages[0].msg
         {
-            assert_eq!(*admin, None);
+            assert_eq!(admin.as_deref(), Some(MOCK_CONTRACT_ADDR));
             assert_eq!(*code_id, REFLECT_ID);
             assert_eq!(funds.len(), 0);
             assert!(label.contains(channel_id));
@@ -525,8 +1176,10 @@ mod tests {
             callback_id: None,
         };
         let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
-        // this returns an error
-        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap_err();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        assert_eq!(ack.error_code(), Some(ErrorCode::UnknownChannel));
+        assert_eq!(0, res.messages.len());
 
         // register the channel
         connect(deps.as_mut(), channel_id, account);
@@ -565,9 +1218,559 @@ mod tests {
         }
 
         // invalid packet format on registered channel also returns error
-        let bad_data = InstantiateMsg { cw1_code_id: 12345 };
+        let bad_data = InstantiateMsg {
+            cw1_code_id: 12345,
+            policy: None,
+            account_backend: None,
+        };
         let msg = mock_ibc_packet_recv(channel_id, &bad_data).unwrap();
-        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap_err();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        assert_eq!(ack.error_code(), Some(ErrorCode::InvalidPacket));
+    }
+
+    #[test]
+    fn trade_on_exchange() {
+        let mut deps = setup();
+
+        let channel_id = "channel-123";
+        let account = "acct-123";
+        connect(deps.as_mut(), channel_id, account);
+
+        let offer = coin(200, "ibc/27394FB092D2ECCD");
+        let ibc_msg = PacketMsg::Trade {
+            sender: account.to_string(),
+            exchange: "exchange".into(),
+            offer: offer.clone(),
+            callback_id: None,
+        };
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        assert_eq!(RECEIVE_TRADE_ID, res.messages[0].id);
+        let trade = WasmMsg::Execute {
+            contract_addr: "exchange".into(),
+            msg: br#"{"trade":{}}"#.into(),
+            funds: vec![offer.clone()],
+        };
+        let expected = cw1_whitelist::msg::ExecuteMsg::<Empty>::Execute {
+            msgs: vec![trade.into()],
+        };
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: account.into(),
+                msg: to_binary(&expected).unwrap(),
+                funds: vec![],
+            })
+        );
+
+        // the ack holds what the exchange reported
+        let event = Event::new("wasm")
+            .add_attribute("action", TRADE_ACTION)
+            .add_attribute("sent", offer.to_string())
+            .add_attribute("received", "200nhash");
+        let trade_reply = |events| Reply {
+            id: RECEIVE_TRADE_ID,
+            result: SubMsgResult::Ok(SubMsgResponse { events, data: None }),
+        };
+        let res = reply(deps.as_mut(), mock_env(), trade_reply(vec![event])).unwrap();
+        let ack: StdAck = from_slice(&res.data.unwrap()).unwrap();
+        let response: TradeResponse = ack.unwrap_into();
+        assert_eq!(
+            response,
+            TradeResponse {
+                sent: offer,
+                received: coin(200, "nhash"),
+            }
+        );
+
+        // which must have traded
+        let res = reply(deps.as_mut(), mock_env(), trade_reply(vec![])).unwrap();
+        let ack: StdAck = from_slice(&res.data.unwrap()).unwrap();
+        assert_eq!(ack.error_code(), Some(ErrorCode::Internal));
+        let failed = Reply {
+            id: RECEIVE_TRADE_ID,
+            result: SubMsgResult::Err("Invalid funds denom".into()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), failed).unwrap();
+        let ack: StdAck = from_slice(&res.data.unwrap()).unwrap();
+        assert_eq!(ack.error_code(), Some(ErrorCode::ExecutionFailed));
+    }
+
+    #[test]
+    fn failed_dispatch_overwrites_ack() {
+        let mut deps = setup();
+
+        let channel_id = "channel-123";
+        let account = "acct-123";
+        connect(deps.as_mut(), channel_id, account);
+
+        let ibc_msg = PacketMsg::Dispatch {
+            msgs: vec![BankMsg::Send {
+                to_address: "my-friend".into(),
+                amount: coins(123456789, "uatom"),
+            }
+            .into()],
+            sender: account.to_string(),
+            callback_id: None,
+        };
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(ReplyOn::Always, res.messages[0].reply_on);
+
+        // the account has no funds, so the execution fails
+        let response = Reply {
+            id: RECEIVE_DISPATCH_ID,
+            result: SubMsgResult::Err("insufficient funds".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), response).unwrap();
+        let ack: StdAck = from_slice(&res.data.unwrap()).unwrap();
+        assert_eq!(ack.error_code(), Some(ErrorCode::ExecutionFailed));
+        assert_eq!(ack.unwrap_err(), "execution_failed: insufficient funds");
+    }
+
+    #[test]
+    fn policy_violations_return_error_ack() {
+        let mut deps = setup();
+
+        let channel_id = "channel-123";
+        let account = "acct-123";
+        connect(deps.as_mut(), channel_id, account);
+
+        // only bank messages are allowed from now on
+        let policy = Policy {
+            allowed_msgs: Some(vec![MsgKind::Bank]),
+            allowed_contracts: None,
+            allowed_queries: None,
+            allowed_stargate_types: None,
+        };
+        sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::UpdatePolicy {
+                policy: policy.clone(),
+            },
+        )
+        .unwrap();
+        let raw = query(deps.as_ref(), mock_env(), QueryMsg::Policy {}).unwrap();
+        let res: PolicyResponse = from_slice(&raw).unwrap();
+        assert_eq!(res.policy, policy);
+
+        // a wasm message is rejected with an app-level error
+        let ibc_msg = PacketMsg::Dispatch {
+            msgs: vec![WasmMsg::Execute {
+                contract_addr: "some-contract".into(),
+                msg: b"{}".into(),
+                funds: vec![],
+            }
+            .into()],
+            sender: account.to_string(),
+            callback_id: None,
+        };
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(0, res.messages.len());
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        assert_eq!(
+            ack.unwrap_err(),
+            "policy_violation: Message type 'wasm' is not allowed"
+        );
+
+        // while a bank message still goes through
+        let ibc_msg = PacketMsg::Dispatch {
+            msgs: vec![BankMsg::Send {
+                to_address: "my-friend".into(),
+                amount: coins(123456789, "uatom"),
+            }
+            .into()],
+            sender: account.to_string(),
+            callback_id: None,
+        };
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        ack.unwrap();
+    }
+
+    #[cfg(not(feature = "provenance"))]
+    #[test]
+    fn provenance_packets_need_provenance_build() {
+        let mut deps = setup();
+
+        let channel_id = "channel-123";
+        let account = "acct-123";
+        connect(deps.as_mut(), channel_id, account);
+
+        let ibc_msg = PacketMsg::ProvenanceDispatch {
+            msgs: vec![br#"{"custom":{"route":"name","params":{}}}"#.into()],
+            sender: account.to_string(),
+            callback_id: None,
+        };
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(0, res.messages.len());
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        assert_eq!(
+            ack.unwrap_err(),
+            "unsupported_packet: Provenance messages are not supported: this host is not built for Provenance"
+        );
+
+        let ibc_msg = PacketMsg::ProvenanceQuery {
+            msgs: vec![br#"{"custom":{"route":"name","params":{}}}"#.into()],
+            sender: account.to_string(),
+            callback_id: None,
+        };
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        assert_eq!(
+            ack.unwrap_err(),
+            "unsupported_packet: Provenance messages are not supported: this host is not built for Provenance"
+        );
+    }
+
+    #[cfg(feature = "provenance")]
+    #[test]
+    fn provenance_query_runs_on_chain() {
+        use provwasm_std::{MarkerQueryParams, ProvenanceQuery, ProvenanceQueryParams};
+
+        let deps = setup();
+        // the chain answers the custom queries
+        let mut deps = OwnedDeps {
+            storage: deps.storage,
+            api: deps.api,
+            querier: deps.querier.with_custom_handler(|_| {
+                SystemResult::Ok(ContractResult::Ok(br#"{"denom":"nhash"}"#.into()))
+            }),
+            custom_query_type: deps.custom_query_type,
+        };
+
+        let channel_id = "channel-123";
+        let account = "acct-123";
+        connect(deps.as_mut(), channel_id, account);
+
+        let query = QueryRequest::Custom(ProvenanceQuery {
+            route: provwasm_std::ProvenanceRoute::Marker,
+            params: ProvenanceQueryParams::Marker(MarkerQueryParams::GetMarkerByDenom {
+                denom: "nhash".into(),
+            }),
+            version: "2.0.0".into(),
+        });
+        let ibc_msg = PacketMsg::ProvenanceQuery {
+            msgs: vec![to_binary(&query).unwrap()],
+            sender: account.to_string(),
+            callback_id: None,
+        };
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        let response: IbcQueryResponse = from_slice(&ack.unwrap()).unwrap();
+        assert_eq!(
+            response.results,
+            vec![Binary::from(br#"{"denom":"nhash"}"#)]
+        );
+
+        // the policy applies to them like to other queries
+        let policy = Policy {
+            allowed_msgs: None,
+            allowed_contracts: None,
+            allowed_queries: Some(vec![QueryKind::Bank]),
+            allowed_stargate_types: None,
+        };
+        sudo(deps.as_mut(), mock_env(), SudoMsg::UpdatePolicy { policy }).unwrap();
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        assert_eq!(
+            ack.unwrap_err(),
+            "policy_violation: Query type 'custom' is not allowed"
+        );
+    }
+
+    #[cfg(feature = "provenance")]
+    #[test]
+    fn provenance_dispatch_runs_on_account() {
+        use crate::account::AccountBackend;
+        use provwasm_std::{bind_name, NameBinding, ProvenanceMsg};
+
+        let mut deps = setup();
+
+        let channel_id = "channel-123";
+        let account = "acct-123";
+        connect(deps.as_mut(), channel_id, account);
+
+        let bind = bind_name("acct.pb", Addr::unchecked(account), NameBinding::Restricted).unwrap();
+        let ibc_msg = PacketMsg::ProvenanceDispatch {
+            msgs: vec![to_binary(&bind).unwrap()],
+            sender: account.to_string(),
+            callback_id: None,
+        };
+
+        // the accounts of a host on Provenance run them by default
+        assert_eq!(
+            CONFIG.load(&deps.storage).unwrap().account_backend,
+            AccountBackend::Provenance
+        );
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        ack.unwrap();
+        assert_eq!(1, res.messages.len());
+        let expected = cw1_whitelist::msg::ExecuteMsg::<ProvenanceMsg>::Execute {
+            msgs: vec![bind.clone()],
+        };
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: account.into(),
+                msg: to_binary(&expected).unwrap(),
+                funds: vec![],
+            })
+        );
+        // and the account contract runs them
+        let mut account_deps = mock_dependencies();
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        let init = CONFIG
+            .load(&deps.storage)
+            .unwrap()
+            .account_backend
+            .instantiate_msg(&Addr::unchecked(MOCK_CONTRACT_ADDR))
+            .unwrap();
+        provenance_account::contract::instantiate(
+            account_deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            from_slice(&init).unwrap(),
+        )
+        .unwrap();
+        let execute = match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => from_slice(msg).unwrap(),
+            o => panic!("Unexpected message: {:?}", o),
+        };
+        let res =
+            provenance_account::contract::execute(account_deps.as_mut(), mock_env(), info, execute)
+                .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].msg, bind);
+
+        // messages which are no ProvenanceMsg are rejected
+        let ibc_msg = PacketMsg::ProvenanceDispatch {
+            msgs: vec![br#"{"custom":{"foo":{}}}"#.into()],
+            sender: account.to_string(),
+            callback_id: None,
+        };
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        assert!(ack.unwrap_err().starts_with("invalid_packet:"));
+
+        // cw1-whitelist accounts only know plain messages
+        let mut cfg = CONFIG.load(&deps.storage).unwrap();
+        cfg.account_backend = AccountBackend::Cw1Whitelist;
+        CONFIG.save(&mut deps.storage, &cfg).unwrap();
+        let ibc_msg = PacketMsg::ProvenanceDispatch {
+            msgs: vec![to_binary(&bind).unwrap()],
+            sender: account.to_string(),
+            callback_id: None,
+        };
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(0, res.messages.len());
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        assert_eq!(
+            ack.unwrap_err(),
+            "unsupported_packet: Provenance messages are not supported: cw1_whitelist accounts can not run them"
+        );
+    }
+
+    #[test]
+    fn channel_denylist_blocks_queries() {
+        let mut deps = setup();
+
+        let channel_id = "channel-123";
+        let account = "acct-123";
+        connect(deps.as_mut(), channel_id, account);
+        deps.querier
+            .update_wasm(|_| SystemResult::Ok(ContractResult::Ok(b"{}".into())));
+
+        let denylist = ChannelDenylist {
+            queries: vec![QueryKind::WasmRaw],
+            ..ChannelDenylist::default()
+        };
+        sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::UpdateChannelDenylist {
+                channel_id: channel_id.to_string(),
+                denylist: denylist.clone(),
+            },
+        )
+        .unwrap();
+        let raw = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ChannelDenylist {
+                channel_id: channel_id.to_string(),
+            },
+        )
+        .unwrap();
+        let res: ChannelDenylistResponse = from_slice(&raw).unwrap();
+        assert_eq!(res.denylist, denylist);
+
+        let ibc_msg = PacketMsg::IbcQuery {
+            msgs: vec![WasmQuery::Raw {
+                contract_addr: "some-contract".into(),
+                key: b"config".into(),
+            }
+            .into()],
+            sender: account.to_string(),
+            callback_id: None,
+        };
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        assert_eq!(
+            ack.unwrap_err(),
+            "policy_violation: Query type 'wasm_raw' is not allowed"
+        );
+
+        // other channels are not affected by the denylist
+        let other_channel = "channel-456";
+        connect(deps.as_mut(), other_channel, "acct-456");
+        let msg = mock_ibc_packet_recv(other_channel, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        ack.unwrap();
+
+        // and an empty denylist removes the restriction again
+        sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::UpdateChannelDenylist {
+                channel_id: channel_id.to_string(),
+                denylist: ChannelDenylist::default(),
+            },
+        )
+        .unwrap();
+        let msg = mock_ibc_packet_recv(channel_id, &ibc_msg).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        ack.unwrap();
+    }
+
+    #[test]
+    fn migrate_accounts_by_page() {
+        let mut deps = setup();
+        connect(deps.as_mut(), "channel-1", "acct-1");
+        connect(deps.as_mut(), "channel-2", "acct-2");
+        connect(deps.as_mut(), "channel-3", "acct-3");
+        // acct-2 was created before the host was the admin, acct-3 runs the new code already
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::ContractInfo { contract_addr } => {
+                let code_id = if contract_addr == "acct-3" {
+                    202
+                } else {
+                    REFLECT_ID
+                };
+                let mut info = ContractInfoResponse::new(code_id, MOCK_CONTRACT_ADDR);
+                if contract_addr != "acct-2" {
+                    info.admin = Some(MOCK_CONTRACT_ADDR.into());
+                }
+                SystemResult::Ok(ContractResult::Ok(to_binary(&info).unwrap()))
+            }
+            _ => panic!("unexpected query {:?}", query),
+        });
+
+        // only owners may change the account code
+        let update = ExecuteMsg::UpdateCw1CodeId { code_id: 202 };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            update.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, SimpleIcaError::NotOwner("bob".into()).into());
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), update).unwrap();
+        let raw = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let cfg: Config = from_slice(&raw).unwrap();
+        assert_eq!(cfg.cw1_code_id, 202);
+
+        let migrate_msg = Binary::from(b"{}");
+        let page = ExecuteMsg::MigrateAccounts {
+            code_id: 202,
+            msg: migrate_msg.clone(),
+            start_after: None,
+            limit: Some(2),
+            orphaned: false,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), page).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Migrate {
+                contract_addr: "acct-1".into(),
+                new_code_id: 202,
+                msg: migrate_msg.clone(),
+            })]
+        );
+        assert_eq!(
+            res.attributes[1..],
+            [
+                attr("migrated", "channel-1"),
+                attr("skipped", "channel-2"),
+                attr("next_start_after", "channel-2"),
+            ]
+        );
+
+        let page = ExecuteMsg::MigrateAccounts {
+            code_id: 202,
+            msg: migrate_msg.clone(),
+            start_after: Some("channel-2".into()),
+            limit: Some(2),
+            orphaned: false,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), page).unwrap();
+        assert_eq!(res.messages.len(), 0);
+        assert_eq!(res.attributes[1..], [attr("skipped", "channel-3")]);
+
+        // orphaned accounts are migrated by their key
+        for channel_id in ["channel-1", "channel-2"] {
+            let channel = mock_ibc_channel_close_init(channel_id, APP_ORDER, IBC_APP_VERSION);
+            ibc_channel_close(deps.as_mut(), mock_env(), channel).unwrap();
+        }
+        let orphans = |start_after: Option<&str>| ExecuteMsg::MigrateAccounts {
+            code_id: 202,
+            msg: migrate_msg.clone(),
+            start_after: start_after.map(Into::into),
+            limit: Some(1),
+            orphaned: true,
+        };
+        let info = mock_info(CREATOR, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), orphans(None)).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Migrate {
+                contract_addr: "acct-1".into(),
+                new_code_id: 202,
+                msg: migrate_msg.clone(),
+            })]
+        );
+        let key = "connection-2/their_port/channel-1";
+        assert_eq!(
+            res.attributes[1..],
+            [attr("migrated", key), attr("next_start_after", key)]
+        );
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), orphans(Some(key))).unwrap();
+        let key = "connection-2/their_port/channel-2";
+        assert_eq!(
+            res.attributes[1..],
+            [attr("skipped", key), attr("next_start_after", key)]
+        );
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), orphans(Some(key))).unwrap();
+        assert_eq!(res.attributes.len(), 1);
+        execute(deps.as_mut(), mock_env(), info, orphans(Some("channel-1"))).unwrap_err();
     }
 
     #[test]
@@ -601,20 +1804,17 @@ mod tests {
         }) = &res.messages[0].msg
         {
             assert_eq!(contract_addr.as_str(), account);
-            let reflect: ReflectExecuteMsg = from_slice(msg).unwrap();
-            match reflect {
-                ReflectExecuteMsg::ReflectMsg { msgs } => {
-                    assert_eq!(1, msgs.len());
-                    assert_eq!(
-                        &msgs[0],
-                        &BankMsg::Send {
-                            to_address: MOCK_CONTRACT_ADDR.into(),
-                            amount: funds
-                        }
-                        .into()
-                    )
+            let reflect: cw1_whitelist::msg::ExecuteMsg = from_slice(msg).unwrap();
+            assert_eq!(
+                reflect,
+                cw1_whitelist::msg::ExecuteMsg::Execute {
+                    msgs: vec![BankMsg::Send {
+                        to_address: MOCK_CONTRACT_ADDR.into(),
+                        amount: funds.clone()
+                    }
+                    .into()]
                 }
-            }
+            );
         } else {
             panic!("Unexpected message: {:?}", &res.messages[0]);
         }
@@ -623,5 +1823,120 @@ mod tests {
         let raw = query(deps.as_ref(), mock_env(), QueryMsg::ListAccounts {}).unwrap();
         let res: ListAccountsResponse = from_slice(&raw).unwrap();
         assert_eq!(0, res.accounts.len());
+
+        // but keeps the account and funds for the controller
+        let res =
+            query_orphaned_accounts(deps.as_ref(), "connection-2".into(), "their_port".into())
+                .unwrap();
+        assert_eq!(
+            res.accounts,
+            vec![OrphanedAccountInfo {
+                account: account.into(),
+                channel_id: channel_id.into(),
+                claimable: funds,
+            }]
+        );
+    }
+
+    #[test]
+    fn reattach_orphaned_account() {
+        let mut deps = setup();
+
+        let channel_id = "channel-123";
+        let account = "acct-123";
+        connect(deps.as_mut(), channel_id, account);
+        let funds = coins(123456, "uatom");
+        deps.querier.update_balance(account, funds.clone());
+        let channel = mock_ibc_channel_close_init(channel_id, APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_close(deps.as_mut(), mock_env(), channel).unwrap();
+
+        // another controller on the same connection gets a new account
+        let mut channel = mock_ibc_channel("channel-456", APP_ORDER, IBC_APP_VERSION);
+        channel.counterparty_endpoint.port_id = "other_port".into();
+        let handshake = IbcChannelConnectMsg::new_ack(channel, IBC_APP_VERSION);
+        let res = ibc_channel_connect(deps.as_mut(), mock_env(), handshake).unwrap();
+        assert_eq!(INIT_CALLBACK_ID, res.messages[0].id);
+        PENDING.remove(deps.as_mut().storage);
+
+        // while the same controller gets the old account and funds back
+        let new_channel_id = "channel-789";
+        let handshake = mock_ibc_channel_connect_ack(new_channel_id, APP_ORDER, IBC_APP_VERSION);
+        let res = ibc_channel_connect(deps.as_mut(), mock_env(), handshake).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: account.into(),
+                amount: funds,
+            })]
+        );
+        assert!(res
+            .attributes
+            .contains(&attr("recovered_from_channel", channel_id)));
+        let res = query_account(deps.as_ref(), new_channel_id.into()).unwrap();
+        assert_eq!(res.account.unwrap(), account);
+
+        // the account can only be recovered once
+        let res =
+            query_orphaned_accounts(deps.as_ref(), "connection-2".into(), "their_port".into())
+                .unwrap();
+        assert_eq!(0, res.accounts.len());
+    }
+
+    #[test]
+    fn claim_orphaned_funds() {
+        let mut deps = setup();
+
+        // two channels of the same controller close, with funds
+        connect(deps.as_mut(), "channel-1", "acct-1");
+        connect(deps.as_mut(), "channel-2", "acct-2");
+        deps.querier.update_balance("acct-1", coins(100, "uatom"));
+        deps.querier.update_balance("acct-2", coins(200, "uatom"));
+        for channel_id in ["channel-1", "channel-2"] {
+            let channel = mock_ibc_channel_close_init(channel_id, APP_ORDER, IBC_APP_VERSION);
+            ibc_channel_close(deps.as_mut(), mock_env(), channel).unwrap();
+        }
+
+        // a new channel gets only the first account back
+        let handshake = mock_ibc_channel_connect_ack("channel-3", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_connect(deps.as_mut(), mock_env(), handshake).unwrap();
+
+        // the funds of the other one are claimed to it
+        let claim = PacketMsg::ClaimOrphaned {
+            channel_id: "channel-2".into(),
+        };
+        let mut msg = mock_ibc_packet_recv("channel-3", &claim).unwrap();
+        msg.packet.src.port_id = "their_port".into();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "acct-1".into(),
+                amount: coins(200, "uatom"),
+            })]
+        );
+        let ack: ClaimOrphanedResponse = from_slice::<StdAck>(&res.acknowledgement)
+            .unwrap()
+            .unwrap_into();
+        assert_eq!(ack.account, "acct-1");
+        assert_eq!(ack.claimed, coins(200, "uatom"));
+
+        // they can be claimed once, but the account is kept
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+        assert_eq!(0, res.messages.len());
+        let ack: ClaimOrphanedResponse = from_slice::<StdAck>(&res.acknowledgement)
+            .unwrap()
+            .unwrap_into();
+        assert_eq!(ack.claimed, vec![]);
+        let res =
+            query_orphaned_accounts(deps.as_ref(), "connection-2".into(), "their_port".into())
+                .unwrap();
+        assert_eq!(res.accounts.len(), 1);
+        assert_eq!(res.accounts[0].account, "acct-2");
+
+        // other controllers can not claim them
+        msg.packet.src.port_id = "other_port".into();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_slice(&res.acknowledgement).unwrap();
+        assert_eq!(ack.error_code(), Some(ErrorCode::UnknownChannel));
     }
 }
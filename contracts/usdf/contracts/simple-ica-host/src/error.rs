@@ -3,7 +3,9 @@ use thiserror::Error;
 use cosmwasm_std::StdError;
 use cw_utils::ParseReplyError;
 
-use simple_ica::SimpleIcaError;
+use simple_ica::{ErrorCode, SimpleIcaError};
+
+use crate::policy::PolicyError;
 
 #[derive(Error, Debug, PartialEq)]
 pub enum ContractError {
@@ -16,9 +18,46 @@ pub enum ContractError {
     #[error("{0}")]
     SimpleIca(#[from] SimpleIcaError),
 
+    #[error("{0}")]
+    Policy(#[from] PolicyError),
+
     #[error("Cannot register over an existing channel")]
     ChannelAlreadyRegistered,
 
     #[error("Invalid reply id")]
     InvalidReplyId,
+
+    #[error("Invalid packet: {0}")]
+    InvalidPacket(String),
+
+    #[error("No account for channel {0}")]
+    UnknownChannel(String),
+
+    #[error("{0}")]
+    QueryFailed(String),
+
+    #[error("No orphaned account of channel {0}")]
+    UnknownOrphan(String),
+
+    #[error("The host has no owner, the migration must set one")]
+    OwnerRequired,
+
+    #[error("Provenance messages are not supported: {0}")]
+    ProvenanceUnsupported(String),
+}
+
+impl ContractError {
+    /// The code we report in error acknowledgements for this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ContractError::InvalidPacket(_) => ErrorCode::InvalidPacket,
+            ContractError::UnknownChannel(_) | ContractError::UnknownOrphan(_) => {
+                ErrorCode::UnknownChannel
+            }
+            ContractError::Policy(_) => ErrorCode::PolicyViolation,
+            ContractError::QueryFailed(_) => ErrorCode::QueryFailed,
+            ContractError::ProvenanceUnsupported(_) => ErrorCode::UnsupportedPacket,
+            _ => ErrorCode::Internal,
+        }
+    }
 }
@@ -1,4 +1,6 @@
+pub mod account;
 pub mod contract;
 pub mod error;
 pub mod msg;
+pub mod policy;
 pub mod state;
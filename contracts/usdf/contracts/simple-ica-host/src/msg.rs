@@ -1,11 +1,62 @@
-use cosmwasm_std::CosmosMsg;
+use cosmwasm_std::{Binary, Coin};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use simple_ica::admin::AdminExecuteMsg;
+
+use crate::account::AccountBackend;
+use crate::policy::{ChannelDenylist, Policy};
+
 /// Just needs to know the code_id of a reflect contract to spawn sub-accounts
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub cw1_code_id: u64,
+    /// Restricts what remote controllers may do. Defaults to allowing everything.
+    pub policy: Option<Policy>,
+    /// The contract `cw1_code_id` is. If unset, provenance-account on hosts built for
+    /// Provenance and cw1-whitelist elsewhere.
+    pub account_backend: Option<AccountBackend>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {
+    /// Becomes an owner if the host has none, like when migrating from 0.2, which had no admins.
+    /// Required then.
+    pub owner: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Manages the admins. Owners update and migrate the account code, like governance would.
+    Admin(AdminExecuteMsg),
+    /// Sets the code of the accounts created from now on (owners only)
+    UpdateCw1CodeId { code_id: u64 },
+    /// Migrates the accounts of open channels to the code, a page at a time by channel id
+    /// (owners only). Accounts already running it, or without the host as their admin, are
+    /// skipped. The `next_start_after` attribute tells where the next page starts.
+    MigrateAccounts {
+        code_id: u64,
+        msg: Binary,
+        start_after: Option<String>,
+        limit: Option<u32>,
+        /// Migrates the orphaned accounts instead, by `connection_id/port_id/channel_id`
+        #[serde(default)]
+        orphaned: bool,
+    },
+}
+
+/// Called by governance to manage the sandbox of the remote accounts
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    /// Replaces the policy applied to all channels
+    UpdatePolicy { policy: Policy },
+    /// Sets the denylist for one channel. An empty denylist removes it.
+    UpdateChannelDenylist {
+        channel_id: String,
+        denylist: ChannelDenylist,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -17,6 +68,20 @@ pub enum QueryMsg {
     /// Returns all (channel, reflect_account) pairs.
     /// No pagination - this is a test contract
     ListAccounts {},
+    /// Returns the code id of new accounts
+    Config {},
+    /// Returns the admins and the roles proposed to others
+    Admins {},
+    /// Returns the policy applied to all channels
+    Policy {},
+    /// Returns the additional denylist for this channel (empty if none)
+    ChannelDenylist { channel_id: String },
+    /// Returns the accounts of closed channels from this controller,
+    /// which will be reattached to its next channels
+    OrphanedAccounts {
+        connection_id: String,
+        port_id: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -36,7 +101,25 @@ pub struct AccountInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-#[serde(rename_all = "snake_case")]
-pub enum ReflectExecuteMsg {
-    ReflectMsg { msgs: Vec<CosmosMsg> },
+pub struct PolicyResponse {
+    pub policy: Policy,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ChannelDenylistResponse {
+    pub denylist: ChannelDenylist,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OrphanedAccountsResponse {
+    pub accounts: Vec<OrphanedAccountInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OrphanedAccountInfo {
+    pub account: String,
+    /// The channel that was closed
+    pub channel_id: String,
+    /// Funds held by the host until the account is reattached
+    pub claimable: Vec<Coin>,
 }
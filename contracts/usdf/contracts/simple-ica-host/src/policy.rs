@@ -0,0 +1,415 @@
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use cosmwasm_std::{CosmosMsg, QueryRequest, WasmMsg, WasmQuery};
+
+/// The kinds of `CosmosMsg` a remote controller may ask us to dispatch
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MsgKind {
+    Bank,
+    Custom,
+    Staking,
+    Distribution,
+    Stargate,
+    Ibc,
+    Wasm,
+    Gov,
+}
+
+impl MsgKind {
+    /// Returns None for message types this version of cosmwasm-std doesn't know about
+    pub fn of<T>(msg: &CosmosMsg<T>) -> Option<Self> {
+        match msg {
+            CosmosMsg::Bank(_) => Some(MsgKind::Bank),
+            CosmosMsg::Custom(_) => Some(MsgKind::Custom),
+            CosmosMsg::Staking(_) => Some(MsgKind::Staking),
+            CosmosMsg::Distribution(_) => Some(MsgKind::Distribution),
+            CosmosMsg::Stargate { .. } => Some(MsgKind::Stargate),
+            CosmosMsg::Ibc(_) => Some(MsgKind::Ibc),
+            CosmosMsg::Wasm(_) => Some(MsgKind::Wasm),
+            CosmosMsg::Gov(_) => Some(MsgKind::Gov),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MsgKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MsgKind::Bank => "bank",
+            MsgKind::Custom => "custom",
+            MsgKind::Staking => "staking",
+            MsgKind::Distribution => "distribution",
+            MsgKind::Stargate => "stargate",
+            MsgKind::Ibc => "ibc",
+            MsgKind::Wasm => "wasm",
+            MsgKind::Gov => "gov",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The kinds of `QueryRequest` a remote controller may ask us to run.
+/// Wasm queries are split up, as raw queries can read any contract's internal state.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryKind {
+    Bank,
+    Custom,
+    Staking,
+    Stargate,
+    Ibc,
+    WasmSmart,
+    WasmRaw,
+    WasmContractInfo,
+}
+
+impl QueryKind {
+    /// Returns None for query types this version of cosmwasm-std doesn't know about
+    pub fn of<C>(query: &QueryRequest<C>) -> Option<Self> {
+        match query {
+            QueryRequest::Bank(_) => Some(QueryKind::Bank),
+            QueryRequest::Custom(_) => Some(QueryKind::Custom),
+            QueryRequest::Staking(_) => Some(QueryKind::Staking),
+            QueryRequest::Stargate { .. } => Some(QueryKind::Stargate),
+            QueryRequest::Ibc(_) => Some(QueryKind::Ibc),
+            QueryRequest::Wasm(WasmQuery::Smart { .. }) => Some(QueryKind::WasmSmart),
+            QueryRequest::Wasm(WasmQuery::Raw { .. }) => Some(QueryKind::WasmRaw),
+            QueryRequest::Wasm(WasmQuery::ContractInfo { .. }) => Some(QueryKind::WasmContractInfo),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for QueryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            QueryKind::Bank => "bank",
+            QueryKind::Custom => "custom",
+            QueryKind::Staking => "staking",
+            QueryKind::Stargate => "stargate",
+            QueryKind::Ibc => "ibc",
+            QueryKind::WasmSmart => "wasm_smart",
+            QueryKind::WasmRaw => "wasm_raw",
+            QueryKind::WasmContractInfo => "wasm_contract_info",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PolicyError {
+    #[error("Message type '{0}' is not allowed")]
+    MsgNotAllowed(MsgKind),
+
+    #[error("Query type '{0}' is not allowed")]
+    QueryNotAllowed(QueryKind),
+
+    #[error("Contract {0} is not allowed")]
+    ContractNotAllowed(String),
+
+    #[error("Instantiating contracts is not allowed while the contracts are restricted")]
+    InstantiateNotAllowed,
+
+    #[error("Stargate type {0} is not allowed")]
+    StargateNotAllowed(String),
+
+    #[error("Unsupported message type")]
+    UnsupportedMsg,
+
+    #[error("Unsupported query type")]
+    UnsupportedQuery,
+}
+
+/// Host-wide rules for what remote controllers may do. This is set by governance
+/// via `sudo`. A `None` list places no restriction, so the default policy allows everything.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct Policy {
+    /// Message types that may be dispatched
+    pub allowed_msgs: Option<Vec<MsgKind>>,
+    /// Contracts that may be the target of a `WasmMsg` or a `WasmQuery`.
+    /// While set, no contracts may be instantiated.
+    pub allowed_contracts: Option<Vec<String>>,
+    /// Query types that may be run
+    pub allowed_queries: Option<Vec<QueryKind>>,
+    /// Type URLs of the stargate messages and paths of the stargate queries that may be run.
+    /// While the contracts are restricted, unlisted ones are rejected even if this is unset,
+    /// as they could reach any contract.
+    pub allowed_stargate_types: Option<Vec<String>>,
+}
+
+/// Additional restrictions for one channel, applied on top of the `Policy`
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct ChannelDenylist {
+    #[serde(default)]
+    pub msgs: Vec<MsgKind>,
+    #[serde(default)]
+    pub contracts: Vec<String>,
+    #[serde(default)]
+    pub queries: Vec<QueryKind>,
+}
+
+impl ChannelDenylist {
+    pub fn is_empty(&self) -> bool {
+        self.msgs.is_empty() && self.contracts.is_empty() && self.queries.is_empty()
+    }
+}
+
+impl Policy {
+    /// Ensures every message may be dispatched over a channel with the given denylist
+    pub fn check_msgs<T>(
+        &self,
+        denylist: &ChannelDenylist,
+        msgs: &[CosmosMsg<T>],
+    ) -> Result<(), PolicyError> {
+        for msg in msgs {
+            let kind = MsgKind::of(msg).ok_or(PolicyError::UnsupportedMsg)?;
+            if !allows(&self.allowed_msgs, &kind) || denylist.msgs.contains(&kind) {
+                return Err(PolicyError::MsgNotAllowed(kind));
+            }
+            match msg {
+                CosmosMsg::Wasm(wasm) => match wasm_msg_target(wasm) {
+                    Some(contract) => self.check_contract(denylist, contract)?,
+                    // the new contract could not be restricted
+                    None if self.allowed_contracts.is_some() => {
+                        return Err(PolicyError::InstantiateNotAllowed)
+                    }
+                    None => {}
+                },
+                CosmosMsg::Stargate { type_url, .. } => self.check_stargate(type_url)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Ensures every query may be run over a channel with the given denylist
+    pub fn check_queries<C>(
+        &self,
+        denylist: &ChannelDenylist,
+        queries: &[QueryRequest<C>],
+    ) -> Result<(), PolicyError> {
+        for query in queries {
+            let kind = QueryKind::of(query).ok_or(PolicyError::UnsupportedQuery)?;
+            if !allows(&self.allowed_queries, &kind) || denylist.queries.contains(&kind) {
+                return Err(PolicyError::QueryNotAllowed(kind));
+            }
+            match query {
+                QueryRequest::Wasm(wasm) => {
+                    if let Some(contract) = wasm_query_target(wasm) {
+                        self.check_contract(denylist, contract)?;
+                    }
+                }
+                QueryRequest::Stargate { path, .. } => self.check_stargate(path)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn check_contract(
+        &self,
+        denylist: &ChannelDenylist,
+        contract: &str,
+    ) -> Result<(), PolicyError> {
+        let contract = contract.to_string();
+        if !allows(&self.allowed_contracts, &contract) || denylist.contracts.contains(&contract) {
+            return Err(PolicyError::ContractNotAllowed(contract));
+        }
+        Ok(())
+    }
+
+    fn check_stargate(&self, type_url: &str) -> Result<(), PolicyError> {
+        let allowed = match &self.allowed_stargate_types {
+            Some(types) => types.iter().any(|allowed| allowed == type_url),
+            None => self.allowed_contracts.is_none(),
+        };
+        if !allowed {
+            return Err(PolicyError::StargateNotAllowed(type_url.to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn allows<T: PartialEq>(allowed: &Option<Vec<T>>, item: &T) -> bool {
+    match allowed {
+        Some(list) => list.contains(item),
+        None => true,
+    }
+}
+
+/// The existing contract a message acts upon. Instantiate creates a new one, so has none.
+fn wasm_msg_target(msg: &WasmMsg) -> Option<&str> {
+    match msg {
+        WasmMsg::Execute { contract_addr, .. }
+        | WasmMsg::Migrate { contract_addr, .. }
+        | WasmMsg::UpdateAdmin { contract_addr, .. }
+        | WasmMsg::ClearAdmin { contract_addr } => Some(contract_addr),
+        _ => None,
+    }
+}
+
+fn wasm_query_target(query: &WasmQuery) -> Option<&str> {
+    match query {
+        WasmQuery::Smart { contract_addr, .. }
+        | WasmQuery::Raw { contract_addr, .. }
+        | WasmQuery::ContractInfo { contract_addr } => Some(contract_addr),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{coins, to_binary, BankMsg, BankQuery, Empty, StakingMsg};
+    use std::slice;
+
+    fn execute(contract: &str) -> CosmosMsg {
+        WasmMsg::Execute {
+            contract_addr: contract.to_string(),
+            msg: to_binary(&Empty {}).unwrap(),
+            funds: vec![],
+        }
+        .into()
+    }
+
+    #[test]
+    fn default_policy_allows_everything() {
+        let policy = Policy::default();
+        let denylist = ChannelDenylist::default();
+        let msgs = vec![
+            BankMsg::Burn {
+                amount: coins(123, "uatom"),
+            }
+            .into(),
+            execute("any-contract"),
+        ];
+        policy.check_msgs(&denylist, &msgs).unwrap();
+
+        let queries: Vec<QueryRequest<Empty>> = vec![WasmQuery::Raw {
+            contract_addr: "any-contract".into(),
+            key: b"config".into(),
+        }
+        .into()];
+        policy.check_queries(&denylist, &queries).unwrap();
+    }
+
+    #[test]
+    fn policy_restricts_msgs_and_contracts() {
+        let policy = Policy {
+            allowed_msgs: Some(vec![MsgKind::Bank, MsgKind::Wasm]),
+            allowed_contracts: Some(vec!["good-contract".to_string()]),
+            allowed_queries: None,
+            allowed_stargate_types: None,
+        };
+        let denylist = ChannelDenylist::default();
+
+        policy
+            .check_msgs(&denylist, &[execute("good-contract")])
+            .unwrap();
+        let err = policy
+            .check_msgs(&denylist, &[execute("bad-contract")])
+            .unwrap_err();
+        assert_eq!(err, PolicyError::ContractNotAllowed("bad-contract".into()));
+
+        let instantiate: CosmosMsg = WasmMsg::Instantiate {
+            admin: None,
+            code_id: 1,
+            msg: to_binary(&Empty {}).unwrap(),
+            funds: vec![],
+            label: "escape".to_string(),
+        }
+        .into();
+        let err = policy.check_msgs(&denylist, &[instantiate]).unwrap_err();
+        assert_eq!(err, PolicyError::InstantiateNotAllowed);
+
+        let delegate: CosmosMsg = StakingMsg::Delegate {
+            validator: "val".into(),
+            amount: cosmwasm_std::coin(100, "uatom"),
+        }
+        .into();
+        let err = policy.check_msgs(&denylist, &[delegate]).unwrap_err();
+        assert_eq!(err, PolicyError::MsgNotAllowed(MsgKind::Staking));
+    }
+
+    #[test]
+    fn restricted_contracts_reject_stargate() {
+        let execute_type = "/cosmwasm.wasm.v1.MsgExecuteContract";
+        let stargate: CosmosMsg = CosmosMsg::Stargate {
+            type_url: execute_type.into(),
+            value: b"".into(),
+        };
+        let query: QueryRequest<Empty> = QueryRequest::Stargate {
+            path: "/cosmwasm.wasm.v1.Query/SmartContractState".into(),
+            data: b"".into(),
+        };
+        let denylist = ChannelDenylist::default();
+
+        // stargate could reach any contract, so it is only allowed with unrestricted contracts
+        let mut policy = Policy::default();
+        policy
+            .check_msgs(&denylist, slice::from_ref(&stargate))
+            .unwrap();
+        policy.allowed_contracts = Some(vec!["good-contract".to_string()]);
+        let err = policy
+            .check_msgs(&denylist, slice::from_ref(&stargate))
+            .unwrap_err();
+        assert_eq!(err, PolicyError::StargateNotAllowed(execute_type.into()));
+        let err = policy
+            .check_queries(&denylist, slice::from_ref(&query))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            PolicyError::StargateNotAllowed("/cosmwasm.wasm.v1.Query/SmartContractState".into())
+        );
+
+        // unless the type is listed
+        let send: CosmosMsg = CosmosMsg::Stargate {
+            type_url: "/cosmos.bank.v1beta1.MsgSend".into(),
+            value: b"".into(),
+        };
+        policy.allowed_stargate_types = Some(vec!["/cosmos.bank.v1beta1.MsgSend".to_string()]);
+        policy.check_msgs(&denylist, &[send]).unwrap();
+        policy.check_msgs(&denylist, &[stargate]).unwrap_err();
+        policy.check_queries(&denylist, &[query]).unwrap_err();
+    }
+
+    #[test]
+    fn channel_denylist_applies_on_top() {
+        let policy = Policy::default();
+        let denylist = ChannelDenylist {
+            msgs: vec![],
+            contracts: vec!["secret-contract".to_string()],
+            queries: vec![QueryKind::WasmRaw],
+        };
+
+        let smart: QueryRequest<Empty> = WasmQuery::Smart {
+            contract_addr: "secret-contract".into(),
+            msg: b"{}".into(),
+        }
+        .into();
+        let err = policy.check_queries(&denylist, &[smart]).unwrap_err();
+        assert_eq!(
+            err,
+            PolicyError::ContractNotAllowed("secret-contract".into())
+        );
+
+        let raw: QueryRequest<Empty> = WasmQuery::Raw {
+            contract_addr: "other-contract".into(),
+            key: b"config".into(),
+        }
+        .into();
+        let err = policy.check_queries(&denylist, &[raw]).unwrap_err();
+        assert_eq!(err, PolicyError::QueryNotAllowed(QueryKind::WasmRaw));
+
+        let balance: QueryRequest<Empty> = BankQuery::AllBalances {
+            address: "someone".into(),
+        }
+        .into();
+        policy.check_queries(&denylist, &[balance]).unwrap();
+    }
+}
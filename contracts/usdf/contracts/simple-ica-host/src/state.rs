@@ -1,17 +1,51 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Binary};
+use cosmwasm_std::{Addr, Binary, Coin};
 use cw_storage_plus::{Item, Map};
+use simple_ica::admin::Admins;
+use simple_ica::IcaVersion;
+
+use crate::account::AccountBackend;
+use crate::policy::{ChannelDenylist, Policy};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
+    /// The code of the accounts created from now on
     pub cw1_code_id: u64,
+    /// Hosts from before the backends were added run cw1-whitelist accounts
+    #[serde(default = "cw1_whitelist_backend")]
+    pub account_backend: AccountBackend,
+}
+
+fn cw1_whitelist_backend() -> AccountBackend {
+    AccountBackend::Cw1Whitelist
 }
 
+pub const ADMINS: Admins = Admins::new("admins", "admin_proposals");
 pub const CONFIG: Item<Config> = Item::new("config");
 pub const PENDING: Item<String> = Item::new("pending");
 pub const ACCOUNTS: Map<&str, Addr> = Map::new("accounts");
+/// The protocol version negotiated for each channel.
+/// Channels opened by older versions have none, they speak v2.
+pub const CHANNEL_VERSIONS: Map<&str, IcaVersion> = Map::new("channel_versions");
+/// The connection of each channel, to find the orphaned accounts of its controller.
+/// Channels connected by older versions have none.
+pub const CHANNEL_CONNECTIONS: Map<&str, String> = Map::new("channel_connections");
+/// An account whose channel was closed, kept for its controller to reattach
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OrphanedAccount {
+    pub account: Addr,
+    /// Funds swept from the account into this contract on close.
+    /// They are returned when the account is reattached.
+    pub claimable: Vec<Coin>,
+}
+
+/// Keyed by (connection id, counterparty port id, closed channel id). The first two
+/// identify the controller, which may reattach the account from any new channel.
+pub const ORPHANED_ACCOUNTS: Map<(&str, &str, &str), OrphanedAccount> = Map::new("orphans");
+pub const POLICY: Item<Policy> = Item::new("policy");
+pub const CHANNEL_DENYLISTS: Map<&str, ChannelDenylist> = Map::new("denylists");
 
 // this stores all results from current dispatch
 pub const RESULTS: Item<Vec<Binary>> = Item::new("results");
@@ -0,0 +1,905 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::testing::MockApi;
+use cosmwasm_std::{
+    from_slice, to_binary, Addr, AllBalanceResponse, Api, BalanceResponse, BankMsg, BankQuery,
+    Binary, BlockInfo, Coin, ContractInfo, ContractInfoResponse, ContractResult, CosmosMsg, Deps,
+    DepsMut, Empty, Env, Event, IbcChannel, IbcChannelCloseMsg, IbcChannelConnectMsg,
+    IbcChannelOpenMsg, IbcMsg, IbcPacket, IbcPacketAckMsg, IbcPacketReceiveMsg,
+    IbcPacketTimeoutMsg, IbcTimeout, MessageInfo, Querier, QuerierResult, QuerierWrapper,
+    QueryRequest, Reply, ReplyOn, StdResult, Storage, SubMsg, SubMsgResponse, SubMsgResult,
+    SystemError, SystemResult, Timestamp, Uint128, WasmMsg, WasmQuery,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::contract::{into_sim_msg, ContractWrapper, CustomJson, SimResponse};
+use crate::error::SimError;
+use crate::storage::SimStorage;
+
+/// Relays all packets, and receives the relayer fees if any
+pub const RELAYER: &str = "relayer";
+
+/// The port a contract binds
+pub fn port_id(contract: &Addr) -> String {
+    format!("wasm.{}", contract)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChannelState {
+    Init,
+    TryOpen,
+    Open,
+    Closed,
+}
+
+/// Our end of a channel
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelEnd {
+    /// As the contract was last told about it
+    pub channel: IbcChannel,
+    pub counterparty_chain: String,
+    pub state: ChannelState,
+    next_sequence: u64,
+}
+
+/// The events and data of executed messages, as a transaction would return them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AppResponse {
+    pub events: Vec<Event>,
+    pub data: Option<Binary>,
+}
+
+#[derive(Clone)]
+struct ContractData {
+    code_id: u64,
+    creator: Addr,
+    admin: Option<Addr>,
+    storage: SimStorage,
+}
+
+// Everything a failed message reverts
+#[derive(Clone, Default)]
+struct ChainState {
+    balances: BTreeMap<Addr, BTreeMap<String, Uint128>>,
+    contracts: BTreeMap<Addr, ContractData>,
+    contract_count: u64,
+    channels: BTreeMap<String, ChannelEnd>,
+    /// Sent, but not relayed yet
+    packets: Vec<IbcPacket>,
+    custom_msgs: Vec<(Addr, Binary)>,
+}
+
+/// A chain running contracts in process, with a bank and the wasm and IBC messages contracts
+/// send. Messages failing revert everything they did, like on a real chain.
+/// A contract querying itself while it executes sees no state.
+pub struct MockChain {
+    block: BlockInfo,
+    api: MockApi,
+    codes: Vec<ContractWrapper>,
+    state: ChainState,
+}
+
+impl MockChain {
+    pub fn new(chain_id: &str) -> Self {
+        MockChain {
+            block: BlockInfo {
+                height: 12_345,
+                time: Timestamp::from_nanos(1_571_797_419_879_305_533),
+                chain_id: chain_id.to_string(),
+            },
+            api: MockApi::default(),
+            codes: vec![],
+            state: ChainState::default(),
+        }
+    }
+
+    pub fn chain_id(&self) -> &str {
+        &self.block.chain_id
+    }
+
+    pub fn block(&self) -> &BlockInfo {
+        &self.block
+    }
+
+    pub fn update_block(&mut self, update: impl FnOnce(&mut BlockInfo)) {
+        update(&mut self.block);
+    }
+
+    /// Moves to the next block, 5 seconds later
+    pub fn next_block(&mut self) {
+        self.block.height += 1;
+        self.block.time = self.block.time.plus_seconds(5);
+    }
+
+    /// Returns the code id
+    pub fn store_code(&mut self, contract: ContractWrapper) -> u64 {
+        self.codes.push(contract);
+        self.codes.len() as u64
+    }
+
+    pub fn instantiate<T: Serialize>(
+        &mut self,
+        code_id: u64,
+        sender: &Addr,
+        msg: &T,
+        funds: &[Coin],
+        label: &str,
+    ) -> Result<Addr, SimError> {
+        let msg = to_binary(msg)?;
+        self.transact(|chain| {
+            let (contract, _) = chain.instantiate_contract(sender, None, code_id, msg, funds)?;
+            Ok(contract)
+        })
+        .map_err(|err| match err {
+            SimError::Contract(err) => SimError::Contract(format!("{}: {}", label, err)),
+            err => err,
+        })
+    }
+
+    pub fn execute<T: Serialize>(
+        &mut self,
+        sender: &Addr,
+        contract: &Addr,
+        msg: &T,
+        funds: &[Coin],
+    ) -> Result<AppResponse, SimError> {
+        let msg = WasmMsg::Execute {
+            contract_addr: contract.to_string(),
+            msg: to_binary(msg)?,
+            funds: funds.to_vec(),
+        };
+        self.execute_msg(sender, msg)
+    }
+
+    /// Executes the message signed by `sender`
+    pub fn execute_msg(
+        &mut self,
+        sender: &Addr,
+        msg: impl Into<CosmosMsg>,
+    ) -> Result<AppResponse, SimError> {
+        let msg = into_sim_msg(msg.into())?;
+        self.transact(|chain| chain.handle_msg(sender, msg))
+    }
+
+    pub fn query<T: Serialize, U: DeserializeOwned>(
+        &self,
+        contract: &Addr,
+        msg: &T,
+    ) -> Result<U, SimError> {
+        let res = self.query_contract(contract, &to_binary(msg)?)?;
+        Ok(from_slice(&res)?)
+    }
+
+    pub fn set_balance(&mut self, address: &Addr, amount: &[Coin]) {
+        let balance = amount
+            .iter()
+            .map(|coin| (coin.denom.clone(), coin.amount))
+            .collect();
+        self.state.balances.insert(address.clone(), balance);
+    }
+
+    pub fn balance(&self, address: &Addr, denom: &str) -> Coin {
+        let amount = self
+            .state
+            .balances
+            .get(address)
+            .and_then(|balance| balance.get(denom))
+            .copied()
+            .unwrap_or_default();
+        Coin {
+            denom: denom.to_string(),
+            amount,
+        }
+    }
+
+    pub fn all_balances(&self, address: &Addr) -> Vec<Coin> {
+        let balance = match self.state.balances.get(address) {
+            Some(balance) => balance,
+            None => return vec![],
+        };
+        balance
+            .iter()
+            .filter(|(_, amount)| !amount.is_zero())
+            .map(|(denom, amount)| Coin {
+                denom: denom.clone(),
+                amount: *amount,
+            })
+            .collect()
+    }
+
+    pub fn channel(&self, channel_id: &str) -> Option<&ChannelEnd> {
+        self.state.channels.get(channel_id)
+    }
+
+    /// The custom messages contracts sent as JSON, with their sender. The chain does not run
+    /// them, so they always succeed.
+    pub fn custom_msgs(&self) -> &[(Addr, Binary)] {
+        &self.state.custom_msgs
+    }
+
+    /// Packets sent, which the relayer did not deliver yet
+    pub fn pending_packets(&self) -> &[IbcPacket] {
+        &self.state.packets
+    }
+
+    /// Opens our end of a channel, and returns the version. OpenInit leaves the counterparty
+    /// channel id empty.
+    pub(crate) fn channel_open(
+        &mut self,
+        msg: IbcChannelOpenMsg,
+        counterparty_chain: &str,
+    ) -> Result<String, SimError> {
+        let (mut channel, state) = match &msg {
+            IbcChannelOpenMsg::OpenInit { channel } => (channel.clone(), ChannelState::Init),
+            IbcChannelOpenMsg::OpenTry { channel, .. } => (channel.clone(), ChannelState::TryOpen),
+        };
+        let contract = port_contract(&channel.endpoint.port_id)?;
+        let version = self.transact(|chain| {
+            chain.call(&contract, |code, deps, env| {
+                code.ibc_channel_open(deps, env, msg)
+            })
+        })?;
+        if let Some(version) = version {
+            channel.version = version;
+        }
+        let version = channel.version.clone();
+        let end = ChannelEnd {
+            channel,
+            counterparty_chain: counterparty_chain.to_string(),
+            state,
+            next_sequence: 1,
+        };
+        self.state
+            .channels
+            .insert(end.channel.endpoint.channel_id.clone(), end);
+        Ok(version)
+    }
+
+    /// The id our next channel gets
+    pub(crate) fn next_channel_id(&self) -> String {
+        format!("channel-{}", self.state.channels.len())
+    }
+
+    pub(crate) fn channel_connect(
+        &mut self,
+        msg: IbcChannelConnectMsg,
+    ) -> Result<AppResponse, SimError> {
+        let channel = msg.channel().clone();
+        let contract = port_contract(&channel.endpoint.port_id)?;
+        self.transact(|chain| {
+            // open already, so the contract can send packets when connecting
+            let end = chain.channel_end(&channel.endpoint.channel_id)?;
+            end.channel = channel;
+            end.state = ChannelState::Open;
+            let res = chain.call(&contract, |code, deps, env| {
+                code.ibc_channel_connect(deps, env, msg)
+            })?;
+            chain.dispatch(&contract, res)
+        })
+    }
+
+    pub(crate) fn channel_close(
+        &mut self,
+        msg: IbcChannelCloseMsg,
+    ) -> Result<AppResponse, SimError> {
+        let channel = msg.channel().clone();
+        let contract = port_contract(&channel.endpoint.port_id)?;
+        self.transact(|chain| {
+            chain.channel_end(&channel.endpoint.channel_id)?.state = ChannelState::Closed;
+            let res = chain.call(&contract, |code, deps, env| {
+                code.ibc_channel_close(deps, env, msg)
+            })?;
+            chain.dispatch(&contract, res)
+        })
+    }
+
+    /// Returns the ack
+    pub(crate) fn receive_packet(&mut self, packet: IbcPacket) -> Result<Binary, SimError> {
+        let contract = port_contract(&packet.dest.port_id)?;
+        let msg = IbcPacketReceiveMsg::new(packet, Addr::unchecked(RELAYER));
+        self.transact(|chain| {
+            let res = chain.call(&contract, |code, deps, env| {
+                code.ibc_packet_receive(deps, env, msg)
+            })?;
+            // data set by a reply overwrites the ack
+            let res = chain.dispatch(&contract, res)?;
+            Ok(res.data.unwrap_or_default())
+        })
+    }
+
+    pub(crate) fn acknowledge_packet(
+        &mut self,
+        msg: IbcPacketAckMsg,
+    ) -> Result<AppResponse, SimError> {
+        let contract = port_contract(&msg.original_packet.src.port_id)?;
+        self.transact(|chain| {
+            let res = chain.call(&contract, |code, deps, env| {
+                code.ibc_packet_ack(deps, env, msg)
+            })?;
+            chain.dispatch(&contract, res)
+        })
+    }
+
+    pub(crate) fn timeout_packet(&mut self, packet: IbcPacket) -> Result<AppResponse, SimError> {
+        let contract = port_contract(&packet.src.port_id)?;
+        let msg = IbcPacketTimeoutMsg::new(packet, Addr::unchecked(RELAYER));
+        self.transact(|chain| {
+            let res = chain.call(&contract, |code, deps, env| {
+                code.ibc_packet_timeout(deps, env, msg)
+            })?;
+            chain.dispatch(&contract, res)
+        })
+    }
+
+    /// The oldest packet waiting to be relayed to the chain
+    pub(crate) fn next_packet_to(&self, chain_id: &str) -> Option<IbcPacket> {
+        self.state
+            .packets
+            .iter()
+            .find(|packet| {
+                matches!(
+                    self.state.channels.get(&packet.src.channel_id),
+                    Some(end) if end.counterparty_chain == chain_id
+                )
+            })
+            .cloned()
+    }
+
+    pub(crate) fn remove_packet(&mut self, packet: &IbcPacket) {
+        self.state
+            .packets
+            .retain(|pending| pending.src != packet.src || pending.sequence != packet.sequence);
+    }
+
+    /// Closes our end without telling the contract, like a timeout on an ordered channel does
+    pub(crate) fn close_channel_end(&mut self, channel_id: &str) -> Result<(), SimError> {
+        self.channel_end(channel_id)?.state = ChannelState::Closed;
+        Ok(())
+    }
+
+    /// The channels to the chain we closed
+    pub(crate) fn closed_channels_to(&self, chain_id: &str) -> Vec<ChannelEnd> {
+        self.state
+            .channels
+            .values()
+            .filter(|end| end.counterparty_chain == chain_id && end.state == ChannelState::Closed)
+            .cloned()
+            .collect()
+    }
+
+    // runs `f`, reverting all it did if it fails
+    fn transact<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, SimError>,
+    ) -> Result<T, SimError> {
+        let snapshot = self.state.clone();
+        let result = f(self);
+        if result.is_err() {
+            self.state = snapshot;
+        }
+        result
+    }
+
+    fn channel_end(&mut self, channel_id: &str) -> Result<&mut ChannelEnd, SimError> {
+        self.state
+            .channels
+            .get_mut(channel_id)
+            .ok_or_else(|| SimError::UnknownChannel(channel_id.to_string()))
+    }
+
+    fn code(&self, code_id: u64) -> Result<&ContractWrapper, SimError> {
+        let index = code_id
+            .checked_sub(1)
+            .ok_or(SimError::UnknownCode(code_id))?;
+        self.codes
+            .get(index as usize)
+            .ok_or(SimError::UnknownCode(code_id))
+    }
+
+    fn env(&self, contract: &Addr) -> Env {
+        Env {
+            block: self.block.clone(),
+            transaction: None,
+            contract: ContractInfo {
+                address: contract.clone(),
+            },
+        }
+    }
+
+    // calls an entry point of the contract
+    fn call<T>(
+        &mut self,
+        contract: &Addr,
+        f: impl FnOnce(&ContractWrapper, DepsMut, Env) -> Result<T, SimError>,
+    ) -> Result<T, SimError> {
+        let data = self
+            .state
+            .contracts
+            .get_mut(contract)
+            .ok_or_else(|| SimError::UnknownContract(contract.to_string()))?;
+        // taken out, so the contract can query the rest of the chain meanwhile
+        let mut storage = std::mem::take(&mut data.storage);
+        let code_id = data.code_id;
+        let result = {
+            let code = self.code(code_id)?;
+            let querier = ChainQuerier(self);
+            let deps = DepsMut {
+                storage: &mut storage,
+                api: &self.api,
+                querier: QuerierWrapper::new(&querier),
+            };
+            f(code, deps, self.env(contract))
+        };
+        if let Some(data) = self.state.contracts.get_mut(contract) {
+            data.storage = storage;
+        }
+        result
+    }
+
+    fn query_contract(&self, contract: &Addr, msg: &[u8]) -> Result<Binary, SimError> {
+        let data = self
+            .state
+            .contracts
+            .get(contract)
+            .ok_or_else(|| SimError::UnknownContract(contract.to_string()))?;
+        let querier = ChainQuerier(self);
+        let deps = Deps {
+            storage: &data.storage,
+            api: &self.api,
+            querier: QuerierWrapper::new(&querier),
+        };
+        self.code(data.code_id)?
+            .query(deps, self.env(contract), msg)
+    }
+
+    // executes the messages of the contract's response, and returns its events and data
+    fn dispatch(&mut self, contract: &Addr, res: SimResponse) -> Result<AppResponse, SimError> {
+        let mut events = vec![];
+        if !res.attributes.is_empty() {
+            let event = Event::new("wasm")
+                .add_attribute("_contract_address", contract)
+                .add_attributes(res.attributes);
+            events.push(event);
+        }
+        events.extend(res.events.into_iter().map(|event| {
+            Event::new(format!("wasm-{}", event.ty))
+                .add_attribute("_contract_address", contract)
+                .add_attributes(event.attributes)
+        }));
+
+        let mut data = res.data;
+        for msg in res.messages {
+            let res = self.execute_submsg(contract, msg)?;
+            events.extend(res.events);
+            if res.data.is_some() {
+                data = res.data;
+            }
+        }
+        Ok(AppResponse { events, data })
+    }
+
+    // the data returned is the one of the reply, if any
+    fn execute_submsg(
+        &mut self,
+        contract: &Addr,
+        msg: SubMsg<CustomJson>,
+    ) -> Result<AppResponse, SimError> {
+        let SubMsg {
+            id, msg, reply_on, ..
+        } = msg;
+        let result = self.transact(|chain| chain.handle_msg(contract, msg));
+        match (reply_on, result) {
+            (ReplyOn::Always | ReplyOn::Success, Ok(res)) => {
+                let reply = Reply {
+                    id,
+                    result: SubMsgResult::Ok(SubMsgResponse {
+                        events: res.events.clone(),
+                        data: res.data,
+                    }),
+                };
+                let replied = self.reply(contract, reply)?;
+                let mut events = res.events;
+                events.extend(replied.events);
+                Ok(AppResponse {
+                    events,
+                    data: replied.data,
+                })
+            }
+            (ReplyOn::Always | ReplyOn::Error, Err(err)) => {
+                let reply = Reply {
+                    id,
+                    result: SubMsgResult::Err(err.to_string()),
+                };
+                self.reply(contract, reply)
+            }
+            (_, Ok(res)) => Ok(AppResponse {
+                events: res.events,
+                data: None,
+            }),
+            (_, Err(err)) => Err(err),
+        }
+    }
+
+    fn reply(&mut self, contract: &Addr, reply: Reply) -> Result<AppResponse, SimError> {
+        let res = self.call(contract, |code, deps, env| code.reply(deps, env, reply))?;
+        self.dispatch(contract, res)
+    }
+
+    fn handle_msg(
+        &mut self,
+        sender: &Addr,
+        msg: CosmosMsg<CustomJson>,
+    ) -> Result<AppResponse, SimError> {
+        match msg {
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                let recipient = self.api.addr_validate(&to_address)?;
+                self.transfer(sender, &recipient, &amount)?;
+                let event = Event::new("transfer")
+                    .add_attribute("recipient", recipient)
+                    .add_attribute("sender", sender)
+                    .add_attribute("amount", coins_to_string(&amount));
+                Ok(AppResponse {
+                    events: vec![event],
+                    data: None,
+                })
+            }
+            CosmosMsg::Bank(BankMsg::Burn { amount }) => {
+                for coin in &amount {
+                    self.sub_balance(sender, coin)?;
+                }
+                let event = Event::new("burn")
+                    .add_attribute("burner", sender)
+                    .add_attribute("amount", coins_to_string(&amount));
+                Ok(AppResponse {
+                    events: vec![event],
+                    data: None,
+                })
+            }
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                msg,
+                funds,
+            }) => self.execute_contract(sender, contract_addr, msg, funds),
+            CosmosMsg::Wasm(WasmMsg::Instantiate {
+                admin,
+                code_id,
+                msg,
+                funds,
+                ..
+            }) => {
+                let (_, res) = self.instantiate_contract(sender, admin, code_id, msg, &funds)?;
+                Ok(res)
+            }
+            CosmosMsg::Wasm(WasmMsg::Migrate {
+                contract_addr,
+                new_code_id,
+                msg,
+            }) => self.migrate_contract(sender, contract_addr, new_code_id, msg),
+            CosmosMsg::Wasm(WasmMsg::UpdateAdmin {
+                contract_addr,
+                admin,
+            }) => {
+                let admin = self.api.addr_validate(&admin)?;
+                self.contract_as_admin(sender, &contract_addr)?.admin = Some(admin);
+                Ok(AppResponse::default())
+            }
+            CosmosMsg::Wasm(WasmMsg::ClearAdmin { contract_addr }) => {
+                self.contract_as_admin(sender, &contract_addr)?.admin = None;
+                Ok(AppResponse::default())
+            }
+            CosmosMsg::Ibc(IbcMsg::SendPacket {
+                channel_id,
+                data,
+                timeout,
+            }) => self.send_packet(sender, channel_id, data, timeout),
+            CosmosMsg::Ibc(IbcMsg::CloseChannel { channel_id }) => {
+                // the relayer tells the counterparty
+                self.sender_channel(sender, &channel_id)?.state = ChannelState::Closed;
+                let event = Event::new("channel_close_init")
+                    .add_attribute("port_id", port_id(sender))
+                    .add_attribute("channel_id", channel_id);
+                Ok(AppResponse {
+                    events: vec![event],
+                    data: None,
+                })
+            }
+            CosmosMsg::Custom(CustomJson(msg)) => {
+                self.state.custom_msgs.push((sender.clone(), msg));
+                let event = Event::new("custom").add_attribute("sender", sender);
+                Ok(AppResponse {
+                    events: vec![event],
+                    data: None,
+                })
+            }
+            msg => Err(SimError::Unsupported(format!("{:?}", msg))),
+        }
+    }
+
+    fn instantiate_contract(
+        &mut self,
+        sender: &Addr,
+        admin: Option<String>,
+        code_id: u64,
+        msg: Binary,
+        funds: &[Coin],
+    ) -> Result<(Addr, AppResponse), SimError> {
+        self.code(code_id)?;
+        let admin = admin
+            .map(|admin| self.api.addr_validate(&admin))
+            .transpose()?;
+        self.state.contract_count += 1;
+        let contract = Addr::unchecked(format!("contract{}", self.state.contract_count));
+        let data = ContractData {
+            code_id,
+            creator: sender.clone(),
+            admin,
+            storage: SimStorage::default(),
+        };
+        self.state.contracts.insert(contract.clone(), data);
+
+        self.transfer(sender, &contract, funds)?;
+        let info = MessageInfo {
+            sender: sender.clone(),
+            funds: funds.to_vec(),
+        };
+        let res = self.call(&contract, |code, deps, env| {
+            code.instantiate(deps, env, info, &msg)
+        })?;
+        let res = self.dispatch(&contract, res)?;
+
+        let event = Event::new("instantiate")
+            .add_attribute("_contract_address", &contract)
+            .add_attribute("code_id", code_id.to_string());
+        let mut events = vec![event];
+        events.extend(res.events);
+        let data = instantiate_response(&contract, res.data);
+        Ok((
+            contract,
+            AppResponse {
+                events,
+                data: Some(data),
+            },
+        ))
+    }
+
+    fn execute_contract(
+        &mut self,
+        sender: &Addr,
+        contract_addr: String,
+        msg: Binary,
+        funds: Vec<Coin>,
+    ) -> Result<AppResponse, SimError> {
+        let contract = Addr::unchecked(contract_addr);
+        if !self.state.contracts.contains_key(&contract) {
+            return Err(SimError::UnknownContract(contract.into()));
+        }
+        self.transfer(sender, &contract, &funds)?;
+        let info = MessageInfo {
+            sender: sender.clone(),
+            funds,
+        };
+        let res = self.call(&contract, |code, deps, env| {
+            code.execute(deps, env, info, &msg)
+        })?;
+        let res = self.dispatch(&contract, res)?;
+
+        let event = Event::new("execute").add_attribute("_contract_address", &contract);
+        let mut events = vec![event];
+        events.extend(res.events);
+        Ok(AppResponse {
+            events,
+            data: res.data,
+        })
+    }
+
+    fn migrate_contract(
+        &mut self,
+        sender: &Addr,
+        contract_addr: String,
+        new_code_id: u64,
+        msg: Binary,
+    ) -> Result<AppResponse, SimError> {
+        self.code(new_code_id)?;
+        self.contract_as_admin(sender, &contract_addr)?.code_id = new_code_id;
+        let contract = Addr::unchecked(contract_addr);
+        let res = self.call(&contract, |code, deps, env| code.migrate(deps, env, &msg))?;
+        let res = self.dispatch(&contract, res)?;
+
+        let event = Event::new("migrate")
+            .add_attribute("_contract_address", &contract)
+            .add_attribute("code_id", new_code_id.to_string());
+        let mut events = vec![event];
+        events.extend(res.events);
+        Ok(AppResponse {
+            events,
+            data: res.data,
+        })
+    }
+
+    fn contract_as_admin(
+        &mut self,
+        sender: &Addr,
+        contract_addr: &str,
+    ) -> Result<&mut ContractData, SimError> {
+        let data = self
+            .state
+            .contracts
+            .get_mut(&Addr::unchecked(contract_addr))
+            .ok_or_else(|| SimError::UnknownContract(contract_addr.to_string()))?;
+        if data.admin.as_ref() != Some(sender) {
+            return Err(SimError::Unauthorized(contract_addr.to_string()));
+        }
+        Ok(data)
+    }
+
+    // the channel, if the sender's port is bound to it
+    fn sender_channel(
+        &mut self,
+        sender: &Addr,
+        channel_id: &str,
+    ) -> Result<&mut ChannelEnd, SimError> {
+        let port = port_id(sender);
+        let end = self.channel_end(channel_id)?;
+        if end.channel.endpoint.port_id != port {
+            return Err(SimError::WrongPort(port, channel_id.to_string()));
+        }
+        Ok(end)
+    }
+
+    fn send_packet(
+        &mut self,
+        sender: &Addr,
+        channel_id: String,
+        data: Binary,
+        timeout: IbcTimeout,
+    ) -> Result<AppResponse, SimError> {
+        let end = self.sender_channel(sender, &channel_id)?;
+        if end.state == ChannelState::Closed {
+            return Err(SimError::ChannelNotOpen(channel_id));
+        }
+        let sequence = end.next_sequence;
+        end.next_sequence += 1;
+        let packet = IbcPacket::new(
+            data,
+            end.channel.endpoint.clone(),
+            end.channel.counterparty_endpoint.clone(),
+            sequence,
+            timeout,
+        );
+        let event = Event::new("send_packet")
+            .add_attribute("packet_src_port", &packet.src.port_id)
+            .add_attribute("packet_src_channel", &packet.src.channel_id)
+            .add_attribute("packet_dst_port", &packet.dest.port_id)
+            .add_attribute("packet_dst_channel", &packet.dest.channel_id)
+            .add_attribute("packet_sequence", sequence.to_string());
+        self.state.packets.push(packet);
+        Ok(AppResponse {
+            events: vec![event],
+            data: None,
+        })
+    }
+
+    fn transfer(&mut self, from: &Addr, to: &Addr, amount: &[Coin]) -> Result<(), SimError> {
+        for coin in amount {
+            self.sub_balance(from, coin)?;
+            let balance = self.state.balances.entry(to.clone()).or_default();
+            *balance.entry(coin.denom.clone()).or_default() += coin.amount;
+        }
+        Ok(())
+    }
+
+    fn sub_balance(&mut self, address: &Addr, coin: &Coin) -> Result<(), SimError> {
+        let balance = self.state.balances.entry(address.clone()).or_default();
+        let amount = balance.entry(coin.denom.clone()).or_default();
+        *amount = amount
+            .checked_sub(coin.amount)
+            .map_err(|_| SimError::InsufficientFunds(address.to_string(), coin.to_string()))?;
+        Ok(())
+    }
+
+    fn handle_query(&self, request: QueryRequest<Empty>) -> QuerierResult {
+        let result: StdResult<Binary> = match request {
+            QueryRequest::Bank(BankQuery::Balance { address, denom }) => {
+                let amount = self.balance(&Addr::unchecked(address), &denom);
+                to_binary(&BalanceResponse { amount })
+            }
+            QueryRequest::Bank(BankQuery::AllBalances { address }) => {
+                let amount = self.all_balances(&Addr::unchecked(address));
+                to_binary(&AllBalanceResponse { amount })
+            }
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                let contract = Addr::unchecked(&contract_addr);
+                return match self.query_contract(&contract, &msg) {
+                    Err(SimError::UnknownContract(addr)) => {
+                        SystemResult::Err(SystemError::NoSuchContract { addr })
+                    }
+                    res => SystemResult::Ok(res.map_err(|err| err.to_string()).into()),
+                };
+            }
+            QueryRequest::Wasm(WasmQuery::Raw { contract_addr, key }) => {
+                let value = match self.state.contracts.get(&Addr::unchecked(&contract_addr)) {
+                    Some(data) => data.storage.get(&key).unwrap_or_default(),
+                    None => {
+                        return SystemResult::Err(SystemError::NoSuchContract {
+                            addr: contract_addr,
+                        })
+                    }
+                };
+                Ok(value.into())
+            }
+            QueryRequest::Wasm(WasmQuery::ContractInfo { contract_addr }) => {
+                let contract = Addr::unchecked(&contract_addr);
+                let data = match self.state.contracts.get(&contract) {
+                    Some(data) => data,
+                    None => {
+                        return SystemResult::Err(SystemError::NoSuchContract {
+                            addr: contract_addr,
+                        })
+                    }
+                };
+                let mut info = ContractInfoResponse::new(data.code_id, &data.creator);
+                info.admin = data.admin.as_ref().map(Addr::to_string);
+                info.ibc_port = Some(port_id(&contract));
+                to_binary(&info)
+            }
+            request => {
+                return SystemResult::Err(SystemError::UnsupportedRequest {
+                    kind: format!("{:?}", request),
+                })
+            }
+        };
+        SystemResult::Ok(ContractResult::from(result))
+    }
+}
+
+// lets contracts query the chain
+struct ChainQuerier<'a>(&'a MockChain);
+
+impl<'a> Querier for ChainQuerier<'a> {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        match from_slice(bin_request) {
+            Ok(request) => self.0.handle_query(request),
+            // custom queries end up here too
+            Err(err) => SystemResult::Err(SystemError::InvalidRequest {
+                error: err.to_string(),
+                request: bin_request.into(),
+            }),
+        }
+    }
+}
+
+// IBC ports are bound by contracts only
+fn port_contract(port_id: &str) -> Result<Addr, SimError> {
+    port_id
+        .strip_prefix("wasm.")
+        .map(Addr::unchecked)
+        .ok_or_else(|| SimError::Unsupported(format!("port {}", port_id)))
+}
+
+fn coins_to_string(coins: &[Coin]) -> String {
+    let coins: Vec<_> = coins.iter().map(Coin::to_string).collect();
+    coins.join(",")
+}
+
+// MsgInstantiateContractResponse, as parse_reply_instantiate_data reads it
+fn instantiate_response(contract: &Addr, data: Option<Binary>) -> Binary {
+    let mut buf = vec![];
+    encode_bytes(&mut buf, 1, contract.as_bytes());
+    if let Some(data) = data {
+        encode_bytes(&mut buf, 2, &data);
+    }
+    buf.into()
+}
+
+fn encode_bytes(buf: &mut Vec<u8>, field: u8, bytes: &[u8]) {
+    // length delimited
+    buf.push(field << 3 | 2);
+    let mut len = bytes.len();
+    while len >= 0x80 {
+        buf.push(len as u8 | 0x80);
+        len >>= 7;
+    }
+    buf.push(len as u8);
+    buf.extend_from_slice(bytes);
+}
@@ -0,0 +1,369 @@
+use std::fmt::Display;
+
+use cosmwasm_std::{
+    from_slice, to_binary, to_vec, Binary, CosmosMsg, CustomMsg, CustomQuery, Deps, DepsMut, Env,
+    Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannelCloseMsg, IbcChannelConnectMsg,
+    IbcChannelOpenMsg, IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg,
+    IbcReceiveResponse, MessageInfo, QuerierWrapper, Reply, Response, SubMsg,
+};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::SimError;
+
+/// A custom message as its JSON, as the chain does not know the types contracts use
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub(crate) struct CustomJson(pub Binary);
+
+impl CustomMsg for CustomJson {}
+
+/// The responses of all contracts, whatever their custom message
+pub(crate) type SimResponse = Response<CustomJson>;
+
+// the entry points as contracts define them
+type ExecuteFn<T, C, E, Q> = fn(DepsMut<Q>, Env, MessageInfo, T) -> Result<Response<C>, E>;
+type QueryFn<T, E, Q> = fn(Deps<Q>, Env, T) -> Result<Binary, E>;
+type EntryPointFn<T, R, E, Q> = fn(DepsMut<Q>, Env, T) -> Result<R, E>;
+
+// and as we keep them
+type BoxedExecuteFn =
+    Box<dyn Fn(DepsMut, Env, MessageInfo, &[u8]) -> Result<SimResponse, SimError>>;
+type BoxedQueryFn = Box<dyn Fn(Deps, Env, &[u8]) -> Result<Binary, SimError>>;
+type BoxedMigrateFn = Box<dyn Fn(DepsMut, Env, &[u8]) -> Result<SimResponse, SimError>>;
+type BoxedEntryPointFn<T, R> = Box<dyn Fn(DepsMut, Env, T) -> Result<R, SimError>>;
+
+/// What `ibc_channel_open` returns: nothing, or with IBC v3 the version to use
+pub trait OpenResponse {
+    fn into_version(self) -> Option<String>;
+}
+
+impl OpenResponse for () {
+    fn into_version(self) -> Option<String> {
+        None
+    }
+}
+
+impl OpenResponse for Option<Ibc3ChannelOpenResponse> {
+    fn into_version(self) -> Option<String> {
+        self.map(|res| res.version)
+    }
+}
+
+// The IBC entry points. Their responses are kept as `SimResponse`, the ack of a receive as its data.
+struct IbcEntryPoints {
+    open: BoxedEntryPointFn<IbcChannelOpenMsg, Option<String>>,
+    connect: BoxedEntryPointFn<IbcChannelConnectMsg, SimResponse>,
+    close: BoxedEntryPointFn<IbcChannelCloseMsg, SimResponse>,
+    receive: BoxedEntryPointFn<IbcPacketReceiveMsg, SimResponse>,
+    ack: BoxedEntryPointFn<IbcPacketAckMsg, SimResponse>,
+    timeout: BoxedEntryPointFn<IbcPacketTimeoutMsg, SimResponse>,
+}
+
+/// The entry points of a contract, so a `MockChain` can run it. Custom queries are not
+/// answered, and custom messages are only kept (see `MockChain::custom_msgs`).
+pub struct ContractWrapper {
+    instantiate: BoxedExecuteFn,
+    execute: Option<BoxedExecuteFn>,
+    query: BoxedQueryFn,
+    reply: Option<BoxedEntryPointFn<Reply, SimResponse>>,
+    migrate: Option<BoxedMigrateFn>,
+    ibc: Option<IbcEntryPoints>,
+}
+
+impl ContractWrapper {
+    pub fn new<I, Q, QC, C, E1, E2>(
+        instantiate: ExecuteFn<I, C, E1, QC>,
+        query: QueryFn<Q, E2, QC>,
+    ) -> Self
+    where
+        I: DeserializeOwned + 'static,
+        Q: DeserializeOwned + 'static,
+        QC: CustomQuery + 'static,
+        C: CustomMsg + 'static,
+        E1: Display + 'static,
+        E2: Display + 'static,
+    {
+        ContractWrapper {
+            instantiate: Box::new(move |mut deps, env, info, msg| {
+                let res = instantiate(custom_deps_mut(&mut deps), env, info, from_slice(msg)?);
+                into_response(res.map_err(contract_error)?)
+            }),
+            execute: None,
+            query: Box::new(move |deps, env, msg| {
+                query(custom_deps(&deps), env, from_slice(msg)?).map_err(contract_error)
+            }),
+            reply: None,
+            migrate: None,
+            ibc: None,
+        }
+    }
+
+    pub fn with_execute<X, QC, C, E>(mut self, execute: ExecuteFn<X, C, E, QC>) -> Self
+    where
+        X: DeserializeOwned + 'static,
+        QC: CustomQuery + 'static,
+        C: CustomMsg + 'static,
+        E: Display + 'static,
+    {
+        self.execute = Some(Box::new(move |mut deps, env, info, msg| {
+            let res = execute(custom_deps_mut(&mut deps), env, info, from_slice(msg)?);
+            into_response(res.map_err(contract_error)?)
+        }));
+        self
+    }
+
+    pub fn with_reply<QC, C, E>(mut self, reply: EntryPointFn<Reply, Response<C>, E, QC>) -> Self
+    where
+        QC: CustomQuery + 'static,
+        C: CustomMsg + 'static,
+        E: Display + 'static,
+    {
+        self.reply = Some(Box::new(move |mut deps, env, msg| {
+            let res = reply(custom_deps_mut(&mut deps), env, msg);
+            into_response(res.map_err(contract_error)?)
+        }));
+        self
+    }
+
+    pub fn with_migrate<M, QC, C, E>(mut self, migrate: EntryPointFn<M, Response<C>, E, QC>) -> Self
+    where
+        M: DeserializeOwned + 'static,
+        QC: CustomQuery + 'static,
+        C: CustomMsg + 'static,
+        E: Display + 'static,
+    {
+        self.migrate = Some(Box::new(move |mut deps, env, msg| {
+            let res = migrate(custom_deps_mut(&mut deps), env, from_slice(msg)?);
+            into_response(res.map_err(contract_error)?)
+        }));
+        self
+    }
+
+    /// Adds the six IBC entry points, which every IBC enabled contract has
+    pub fn with_ibc<QC, C, R, E1, E2, E3, E4, E5, E6>(
+        mut self,
+        open: EntryPointFn<IbcChannelOpenMsg, R, E1, QC>,
+        connect: EntryPointFn<IbcChannelConnectMsg, IbcBasicResponse<C>, E2, QC>,
+        close: EntryPointFn<IbcChannelCloseMsg, IbcBasicResponse<C>, E3, QC>,
+        receive: EntryPointFn<IbcPacketReceiveMsg, IbcReceiveResponse<C>, E4, QC>,
+        ack: EntryPointFn<IbcPacketAckMsg, IbcBasicResponse<C>, E5, QC>,
+        timeout: EntryPointFn<IbcPacketTimeoutMsg, IbcBasicResponse<C>, E6, QC>,
+    ) -> Self
+    where
+        QC: CustomQuery + 'static,
+        C: CustomMsg + 'static,
+        R: OpenResponse + 'static,
+        E1: Display + 'static,
+        E2: Display + 'static,
+        E3: Display + 'static,
+        E4: Display + 'static,
+        E5: Display + 'static,
+        E6: Display + 'static,
+    {
+        self.ibc = Some(IbcEntryPoints {
+            open: Box::new(move |mut deps, env, msg| {
+                let res = open(custom_deps_mut(&mut deps), env, msg).map_err(contract_error)?;
+                Ok(res.into_version())
+            }),
+            connect: Box::new(move |mut deps, env, msg| {
+                let res = connect(custom_deps_mut(&mut deps), env, msg);
+                from_basic_response(res.map_err(contract_error)?)
+            }),
+            close: Box::new(move |mut deps, env, msg| {
+                let res = close(custom_deps_mut(&mut deps), env, msg);
+                from_basic_response(res.map_err(contract_error)?)
+            }),
+            receive: Box::new(move |mut deps, env, msg| {
+                let res = receive(custom_deps_mut(&mut deps), env, msg).map_err(contract_error)?;
+                let mut response = SimResponse::new()
+                    .add_submessages(into_submsgs(res.messages)?)
+                    .add_attributes(res.attributes)
+                    .add_events(res.events);
+                response.data = Some(res.acknowledgement);
+                Ok(response)
+            }),
+            ack: Box::new(move |mut deps, env, msg| {
+                let res = ack(custom_deps_mut(&mut deps), env, msg);
+                from_basic_response(res.map_err(contract_error)?)
+            }),
+            timeout: Box::new(move |mut deps, env, msg| {
+                let res = timeout(custom_deps_mut(&mut deps), env, msg);
+                from_basic_response(res.map_err(contract_error)?)
+            }),
+        });
+        self
+    }
+
+    pub(crate) fn instantiate(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> Result<SimResponse, SimError> {
+        (self.instantiate)(deps, env, info, msg)
+    }
+
+    pub(crate) fn execute(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> Result<SimResponse, SimError> {
+        let execute = self
+            .execute
+            .as_ref()
+            .ok_or(SimError::MissingEntryPoint("execute"))?;
+        execute(deps, env, info, msg)
+    }
+
+    pub(crate) fn query(&self, deps: Deps, env: Env, msg: &[u8]) -> Result<Binary, SimError> {
+        (self.query)(deps, env, msg)
+    }
+
+    pub(crate) fn reply(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: Reply,
+    ) -> Result<SimResponse, SimError> {
+        let reply = self
+            .reply
+            .as_ref()
+            .ok_or(SimError::MissingEntryPoint("reply"))?;
+        reply(deps, env, msg)
+    }
+
+    pub(crate) fn migrate(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: &[u8],
+    ) -> Result<SimResponse, SimError> {
+        let migrate = self
+            .migrate
+            .as_ref()
+            .ok_or(SimError::MissingEntryPoint("migrate"))?;
+        migrate(deps, env, msg)
+    }
+
+    pub(crate) fn ibc_channel_open(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcChannelOpenMsg,
+    ) -> Result<Option<String>, SimError> {
+        (self.ibc()?.open)(deps, env, msg)
+    }
+
+    pub(crate) fn ibc_channel_connect(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcChannelConnectMsg,
+    ) -> Result<SimResponse, SimError> {
+        (self.ibc()?.connect)(deps, env, msg)
+    }
+
+    pub(crate) fn ibc_channel_close(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcChannelCloseMsg,
+    ) -> Result<SimResponse, SimError> {
+        (self.ibc()?.close)(deps, env, msg)
+    }
+
+    pub(crate) fn ibc_packet_receive(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketReceiveMsg,
+    ) -> Result<SimResponse, SimError> {
+        (self.ibc()?.receive)(deps, env, msg)
+    }
+
+    pub(crate) fn ibc_packet_ack(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketAckMsg,
+    ) -> Result<SimResponse, SimError> {
+        (self.ibc()?.ack)(deps, env, msg)
+    }
+
+    pub(crate) fn ibc_packet_timeout(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketTimeoutMsg,
+    ) -> Result<SimResponse, SimError> {
+        (self.ibc()?.timeout)(deps, env, msg)
+    }
+
+    fn ibc(&self) -> Result<&IbcEntryPoints, SimError> {
+        self.ibc.as_ref().ok_or(SimError::MissingEntryPoint("ibc"))
+    }
+}
+
+fn contract_error(err: impl Display) -> SimError {
+    SimError::Contract(err.to_string())
+}
+
+// contracts with custom queries get them through the same querier, which rejects them
+fn custom_deps_mut<'a, Q: CustomQuery>(deps: &'a mut DepsMut) -> DepsMut<'a, Q> {
+    DepsMut {
+        storage: &mut *deps.storage,
+        api: deps.api,
+        querier: QuerierWrapper::new(&*deps.querier),
+    }
+}
+
+fn custom_deps<'a, Q: CustomQuery>(deps: &'a Deps) -> Deps<'a, Q> {
+    Deps {
+        storage: deps.storage,
+        api: deps.api,
+        querier: QuerierWrapper::new(&*deps.querier),
+    }
+}
+
+/// Keeps custom messages as their JSON, the other ones are the same whatever the custom message
+pub(crate) fn into_sim_msg<C: CustomMsg>(
+    msg: CosmosMsg<C>,
+) -> Result<CosmosMsg<CustomJson>, SimError> {
+    match msg {
+        CosmosMsg::Custom(custom) => Ok(CosmosMsg::Custom(CustomJson(to_binary(&custom)?))),
+        msg => Ok(from_slice(&to_vec(&msg)?)?),
+    }
+}
+
+fn into_submsgs<C: CustomMsg>(msgs: Vec<SubMsg<C>>) -> Result<Vec<SubMsg<CustomJson>>, SimError> {
+    msgs.into_iter()
+        .map(|msg| {
+            Ok(SubMsg {
+                id: msg.id,
+                msg: into_sim_msg(msg.msg)?,
+                gas_limit: msg.gas_limit,
+                reply_on: msg.reply_on,
+            })
+        })
+        .collect()
+}
+
+fn into_response<C: CustomMsg>(res: Response<C>) -> Result<SimResponse, SimError> {
+    let mut response = SimResponse::new()
+        .add_submessages(into_submsgs(res.messages)?)
+        .add_attributes(res.attributes)
+        .add_events(res.events);
+    response.data = res.data;
+    Ok(response)
+}
+
+fn from_basic_response<C: CustomMsg>(res: IbcBasicResponse<C>) -> Result<SimResponse, SimError> {
+    Ok(SimResponse::new()
+        .add_submessages(into_submsgs(res.messages)?)
+        .add_attributes(res.attributes)
+        .add_events(res.events))
+}
@@ -0,0 +1,39 @@
+use cosmwasm_std::StdError;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SimError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    /// An entry point of a contract returned an error
+    #[error("{0}")]
+    Contract(String),
+
+    #[error("No code with id {0}")]
+    UnknownCode(u64),
+
+    #[error("No contract at {0}")]
+    UnknownContract(String),
+
+    #[error("The contract has no {0} entry point")]
+    MissingEntryPoint(&'static str),
+
+    #[error("No channel {0}")]
+    UnknownChannel(String),
+
+    #[error("Channel {0} is not open")]
+    ChannelNotOpen(String),
+
+    #[error("{0} can not use channel {1}")]
+    WrongPort(String, String),
+
+    #[error("{0} does not have {1}")]
+    InsufficientFunds(String, String),
+
+    #[error("Only the admin may change {0}")]
+    Unauthorized(String),
+
+    #[error("Not supported by the simulator: {0}")]
+    Unsupported(String),
+}
@@ -0,0 +1,17 @@
+//! Runs IBC contracts on two mock chains in process. The relayer opens channels between them,
+//! and delivers packets with their acks and timeouts, so paired contracts can be tested end
+//! to end with `cargo test`.
+
+mod chain;
+mod contract;
+mod error;
+mod relayer;
+mod storage;
+
+pub use crate::chain::{port_id, AppResponse, ChannelEnd, ChannelState, MockChain, RELAYER};
+pub use crate::contract::{ContractWrapper, OpenResponse};
+pub use crate::error::SimError;
+pub use crate::relayer::{
+    close_channel, open_channel, relay_packets, ChannelInfo, PacketOutcome, RelayedPacket,
+    CONNECTION_ID,
+};
@@ -0,0 +1,188 @@
+use cosmwasm_std::{
+    Addr, Binary, BlockInfo, IbcAcknowledgement, IbcChannel, IbcChannelCloseMsg,
+    IbcChannelConnectMsg, IbcChannelOpenMsg, IbcEndpoint, IbcOrder, IbcPacket, IbcPacketAckMsg,
+    IbcTimeout,
+};
+
+use crate::chain::{port_id, ChannelState, MockChain, RELAYER};
+use crate::error::SimError;
+
+/// The connection all channels are opened on, on both chains
+pub const CONNECTION_ID: &str = "connection-0";
+
+/// Both ends of an open channel, and the version they agreed on
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelInfo {
+    pub src: IbcEndpoint,
+    pub dst: IbcEndpoint,
+    pub version: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PacketOutcome {
+    Acknowledged(Binary),
+    TimedOut,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelayedPacket {
+    pub packet: IbcPacket,
+    pub outcome: PacketOutcome,
+}
+
+/// Runs the handshake between the contracts, started on `src`. An empty `version` leaves it to
+/// the contract starting it. Packets sent when connecting are left for `relay_packets`.
+pub fn open_channel(
+    src: &mut MockChain,
+    src_contract: &Addr,
+    dst: &mut MockChain,
+    dst_contract: &Addr,
+    order: IbcOrder,
+    version: &str,
+) -> Result<ChannelInfo, SimError> {
+    let src_end = IbcEndpoint {
+        port_id: port_id(src_contract),
+        channel_id: src.next_channel_id(),
+    };
+    // the counterparty channel is not known before OpenTry
+    let pending_end = IbcEndpoint {
+        port_id: port_id(dst_contract),
+        channel_id: String::new(),
+    };
+    let channel = IbcChannel::new(
+        src_end.clone(),
+        pending_end,
+        order.clone(),
+        version,
+        CONNECTION_ID,
+    );
+    let version = src.channel_open(IbcChannelOpenMsg::new_init(channel), dst.chain_id())?;
+
+    let dst_end = IbcEndpoint {
+        port_id: port_id(dst_contract),
+        channel_id: dst.next_channel_id(),
+    };
+    let channel = IbcChannel::new(
+        dst_end.clone(),
+        src_end.clone(),
+        order.clone(),
+        &version,
+        CONNECTION_ID,
+    );
+    let msg = IbcChannelOpenMsg::new_try(channel, &version);
+    let version = dst.channel_open(msg, src.chain_id())?;
+
+    let channel = IbcChannel::new(
+        src_end.clone(),
+        dst_end.clone(),
+        order.clone(),
+        &version,
+        CONNECTION_ID,
+    );
+    src.channel_connect(IbcChannelConnectMsg::new_ack(channel, &version))?;
+    let channel = IbcChannel::new(
+        dst_end.clone(),
+        src_end.clone(),
+        order,
+        &version,
+        CONNECTION_ID,
+    );
+    dst.channel_connect(IbcChannelConnectMsg::new_confirm(channel))?;
+
+    Ok(ChannelInfo {
+        src: src_end,
+        dst: dst_end,
+        version,
+    })
+}
+
+/// Delivers the packets between the chains, and their acks or timeouts, until none are left.
+/// Stops at the first entry point failing, leaving a packet which failed to be received
+/// pending. Closed channels are closed on the other end first.
+pub fn relay_packets(a: &mut MockChain, b: &mut MockChain) -> Result<Vec<RelayedPacket>, SimError> {
+    let mut relayed = vec![];
+    loop {
+        confirm_closed_channels(a, b)?;
+        confirm_closed_channels(b, a)?;
+        if let Some(packet) = a.next_packet_to(b.chain_id()) {
+            relayed.push(relay_packet(a, b, packet)?);
+        } else if let Some(packet) = b.next_packet_to(a.chain_id()) {
+            relayed.push(relay_packet(b, a, packet)?);
+        } else {
+            return Ok(relayed);
+        }
+    }
+}
+
+/// Closes the channel on `src`, then on `dst`. The contracts are told on both ends.
+pub fn close_channel(
+    src: &mut MockChain,
+    dst: &mut MockChain,
+    channel_id: &str,
+) -> Result<(), SimError> {
+    let end = src
+        .channel(channel_id)
+        .ok_or_else(|| SimError::UnknownChannel(channel_id.to_string()))?;
+    let channel = end.channel.clone();
+    src.channel_close(IbcChannelCloseMsg::new_init(channel))?;
+    confirm_closed_channels(src, dst)
+}
+
+fn relay_packet(
+    src: &mut MockChain,
+    dst: &mut MockChain,
+    packet: IbcPacket,
+) -> Result<RelayedPacket, SimError> {
+    let dst_end = dst.channel(&packet.dest.channel_id).cloned();
+    let open = matches!(&dst_end, Some(end) if end.state == ChannelState::Open);
+    if !open || timed_out(&packet.timeout, dst.block()) {
+        src.timeout_packet(packet.clone())?;
+        src.remove_packet(&packet);
+        // a timeout closes an ordered channel
+        let ordered = matches!(
+            src.channel(&packet.src.channel_id),
+            Some(end) if end.channel.order == IbcOrder::Ordered
+        );
+        if ordered {
+            src.close_channel_end(&packet.src.channel_id)?;
+        }
+        return Ok(RelayedPacket {
+            packet,
+            outcome: PacketOutcome::TimedOut,
+        });
+    }
+
+    let ack = dst.receive_packet(packet.clone())?;
+    // received, so it must not be delivered again if the ack fails
+    src.remove_packet(&packet);
+    let msg = IbcPacketAckMsg::new(
+        IbcAcknowledgement::new(ack.clone()),
+        packet.clone(),
+        Addr::unchecked(RELAYER),
+    );
+    src.acknowledge_packet(msg)?;
+    Ok(RelayedPacket {
+        packet,
+        outcome: PacketOutcome::Acknowledged(ack),
+    })
+}
+
+// tells `to` about the channels `from` closed
+fn confirm_closed_channels(from: &MockChain, to: &mut MockChain) -> Result<(), SimError> {
+    for end in from.closed_channels_to(to.chain_id()) {
+        let counterparty = &end.channel.counterparty_endpoint.channel_id;
+        if let Some(other) = to.channel(counterparty) {
+            if other.state != ChannelState::Closed {
+                let channel = other.channel.clone();
+                to.channel_close(IbcChannelCloseMsg::new_confirm(channel))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn timed_out(timeout: &IbcTimeout, block: &BlockInfo) -> bool {
+    let height = matches!(timeout.block(), Some(t) if block.height >= t.height);
+    let time = matches!(timeout.timestamp(), Some(t) if block.time >= t);
+    height || time
+}
@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use cosmwasm_std::{Order, Record, Storage};
+
+/// The storage of a contract. Unlike `MockStorage` it can be cloned, which is how we revert
+/// failed messages.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SimStorage(BTreeMap<Vec<u8>, Vec<u8>>);
+
+impl Storage for SimStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.0.get(key).cloned()
+    }
+
+    fn range<'a>(
+        &'a self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Record> + 'a> {
+        // BTreeMap panics on empty ranges
+        if let (Some(start), Some(end)) = (start, end) {
+            if start >= end {
+                return Box::new(std::iter::empty());
+            }
+        }
+        let start = start.map_or(Bound::Unbounded, |key| Bound::Included(key.to_vec()));
+        let end = end.map_or(Bound::Unbounded, |key| Bound::Excluded(key.to_vec()));
+        let iter = self
+            .0
+            .range((start, end))
+            .map(|(key, value)| (key.clone(), value.clone()));
+        match order {
+            Order::Ascending => Box::new(iter),
+            Order::Descending => Box::new(iter.rev()),
+        }
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.0.insert(key.to_vec(), value.to_vec());
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.0.remove(key);
+    }
+}
@@ -0,0 +1,238 @@
+//! ibc-reflect-send and ibc-reflect talking over a simulated ordered channel
+
+use cosmwasm_std::{coin, coins, from_slice, Addr, BankMsg, ContractResult, IbcOrder};
+use ibc_reflect::contract::IBC_APP_VERSION;
+use ibc_sim::{
+    open_channel, relay_packets, ChannelInfo, ChannelState, ContractWrapper, MockChain,
+    PacketOutcome,
+};
+
+use ibc_reflect::msg::{
+    AccountResponse as ReflectAccountResponse, InstantiateMsg as ReflectInstantiateMsg,
+    ListAccountsResponse as ReflectListAccountsResponse, QueryMsg as ReflectQueryMsg,
+};
+use ibc_reflect_send::ibc::PACKET_LIFETIME;
+use ibc_reflect_send::msg::{
+    AccountResponse, ExecuteMsg, InstantiateMsg, ListAccountsResponse, QueryMsg,
+};
+
+const ADMIN: &str = "admin";
+
+fn send_contract() -> ContractWrapper {
+    use ibc_reflect_send::{contract, ibc};
+    ContractWrapper::new(contract::instantiate, contract::query)
+        .with_execute(contract::execute)
+        .with_ibc(
+            ibc::ibc_channel_open,
+            ibc::ibc_channel_connect,
+            ibc::ibc_channel_close,
+            ibc::ibc_packet_receive,
+            ibc::ibc_packet_ack,
+            ibc::ibc_packet_timeout,
+        )
+}
+
+fn ibc_reflect_contract() -> ContractWrapper {
+    use ibc_reflect::contract;
+    ContractWrapper::new(contract::instantiate, contract::query)
+        .with_reply(contract::reply)
+        .with_migrate(contract::migrate)
+        .with_ibc(
+            contract::ibc_channel_open,
+            contract::ibc_channel_connect,
+            contract::ibc_channel_close,
+            contract::ibc_packet_receive,
+            contract::ibc_packet_ack,
+            contract::ibc_packet_timeout,
+        )
+}
+
+fn reflect_contract() -> ContractWrapper {
+    use reflect::contract;
+    ContractWrapper::new(contract::instantiate, contract::query)
+        .with_execute(contract::execute)
+        .with_reply(contract::reply)
+}
+
+struct Suite {
+    send_chain: MockChain,
+    reflect_chain: MockChain,
+    send: Addr,
+    ibc_reflect: Addr,
+    channel: ChannelInfo,
+}
+
+impl Suite {
+    fn relay(&mut self) -> Vec<PacketOutcome> {
+        let relayed = relay_packets(&mut self.send_chain, &mut self.reflect_chain).unwrap();
+        relayed.into_iter().map(|packet| packet.outcome).collect()
+    }
+
+    // the reflect contract created for the channel
+    fn remote_account(&self) -> Addr {
+        let query = ReflectQueryMsg::Account {
+            channel_id: self.channel.dst.channel_id.clone(),
+        };
+        let res: ReflectAccountResponse =
+            self.reflect_chain.query(&self.ibc_reflect, &query).unwrap();
+        Addr::unchecked(res.account.unwrap())
+    }
+
+    fn account(&self) -> AccountResponse {
+        let query = QueryMsg::Account {
+            channel_id: self.channel.src.channel_id.clone(),
+        };
+        self.send_chain.query(&self.send, &query).unwrap()
+    }
+
+    fn execute(&mut self, msg: &ExecuteMsg) {
+        let admin = Addr::unchecked(ADMIN);
+        self.send_chain
+            .execute(&admin, &self.send, msg, &[])
+            .unwrap();
+    }
+}
+
+fn setup() -> Suite {
+    let admin = Addr::unchecked(ADMIN);
+    let mut send_chain = MockChain::new("send-1");
+    let mut reflect_chain = MockChain::new("reflect-1");
+
+    let code_id = send_chain.store_code(send_contract());
+    let send = send_chain
+        .instantiate(code_id, &admin, &InstantiateMsg {}, &[], "send")
+        .unwrap();
+
+    let reflect_code_id = reflect_chain.store_code(reflect_contract());
+    let code_id = reflect_chain.store_code(ibc_reflect_contract());
+    let msg = ReflectInstantiateMsg { reflect_code_id };
+    let ibc_reflect = reflect_chain
+        .instantiate(code_id, &admin, &msg, &[], "ibc-reflect")
+        .unwrap();
+
+    let channel = open_channel(
+        &mut send_chain,
+        &send,
+        &mut reflect_chain,
+        &ibc_reflect,
+        IbcOrder::Ordered,
+        IBC_APP_VERSION,
+    )
+    .unwrap();
+
+    let mut suite = Suite {
+        send_chain,
+        reflect_chain,
+        send,
+        ibc_reflect,
+        channel,
+    };
+    // the WhoAmI sent on connect
+    let outcomes = suite.relay();
+    assert_eq!(outcomes.len(), 1);
+    suite
+}
+
+#[test]
+fn handshake_creates_reflect_account() {
+    let suite = setup();
+    assert_eq!(suite.channel.version, IBC_APP_VERSION);
+    let account = suite.account();
+    assert_eq!(
+        account.remote_addr,
+        Some(suite.remote_account().to_string())
+    );
+    assert_eq!(account.remote_balance, vec![]);
+}
+
+#[test]
+fn check_remote_balance() {
+    let mut suite = setup();
+    let remote = suite.remote_account();
+    suite
+        .reflect_chain
+        .set_balance(&remote, &coins(123, "ustake"));
+
+    suite.execute(&ExecuteMsg::CheckRemoteBalance {
+        channel_id: suite.channel.src.channel_id.clone(),
+    });
+    suite.relay();
+    let account = suite.account();
+    assert_eq!(account.remote_balance, vec![coin(123, "ustake")]);
+    assert_eq!(account.last_update_time, suite.send_chain.block().time);
+}
+
+#[test]
+fn dispatch_is_acknowledged() {
+    let mut suite = setup();
+    let send = BankMsg::Send {
+        to_address: "bob".into(),
+        amount: coins(1, "ustake"),
+    };
+    suite.execute(&ExecuteMsg::SendMsgs {
+        channel_id: suite.channel.src.channel_id.clone(),
+        msgs: vec![send.into()],
+    });
+    let outcomes = suite.relay();
+    let ack = match &outcomes[..] {
+        [PacketOutcome::Acknowledged(ack)] => ack,
+        _ => panic!("unexpected outcomes: {:?}", outcomes),
+    };
+    let ack: ContractResult<()> = from_slice(ack).unwrap();
+    assert_eq!(ack, ContractResult::Ok(()));
+}
+
+#[test]
+fn custom_messages_are_kept_remotely() {
+    let mut suite = setup();
+    suite.execute(&ExecuteMsg::ProvenanceTest {
+        channel_id: suite.channel.src.channel_id.clone(),
+    });
+    // the reflect contract emits a Provenance message, which the chain keeps
+    let outcomes = suite.relay();
+    let ack = match &outcomes[..] {
+        [PacketOutcome::Acknowledged(ack)] => ack,
+        _ => panic!("unexpected outcomes: {:?}", outcomes),
+    };
+    let ack: ContractResult<()> = from_slice(ack).unwrap();
+    assert_eq!(ack, ContractResult::Ok(()));
+    let custom = suite.reflect_chain.custom_msgs();
+    assert_eq!(custom.len(), 1);
+    assert_eq!(custom[0].0, suite.remote_account());
+}
+
+#[test]
+fn timeout_closes_ordered_channel() {
+    let mut suite = setup();
+    suite.execute(&ExecuteMsg::CheckRemoteBalance {
+        channel_id: suite.channel.src.channel_id.clone(),
+    });
+    suite
+        .reflect_chain
+        .update_block(|block| block.time = block.time.plus_seconds(PACKET_LIFETIME));
+    let outcomes = suite.relay();
+    assert_eq!(outcomes, vec![PacketOutcome::TimedOut]);
+
+    let src = suite
+        .send_chain
+        .channel(&suite.channel.src.channel_id)
+        .unwrap();
+    let dst = suite
+        .reflect_chain
+        .channel(&suite.channel.dst.channel_id)
+        .unwrap();
+    assert_eq!(src.state, ChannelState::Closed);
+    assert_eq!(dst.state, ChannelState::Closed);
+
+    // only the receiving end is told, so the sender keeps the account
+    let res: ListAccountsResponse = suite
+        .send_chain
+        .query(&suite.send, &QueryMsg::ListAccounts {})
+        .unwrap();
+    assert_eq!(res.accounts.len(), 1);
+    let res: ReflectListAccountsResponse = suite
+        .reflect_chain
+        .query(&suite.ibc_reflect, &ReflectQueryMsg::ListAccounts {})
+        .unwrap();
+    assert_eq!(res.accounts, vec![]);
+}
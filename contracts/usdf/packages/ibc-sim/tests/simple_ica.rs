@@ -0,0 +1,421 @@
+//! simple-ica-controller and simple-ica-host talking over a simulated channel
+
+use cosmwasm_std::{coin, coins, Addr, BankMsg, BankQuery, Empty, QueryRequest};
+#[cfg(feature = "provenance")]
+use cosmwasm_std::{from_slice, CosmosMsg};
+use ibc_sim::{
+    close_channel, open_channel, relay_packets, ChannelInfo, ChannelState, ContractWrapper,
+    MockChain, PacketOutcome,
+};
+use simple_ica::admin::{AdminExecuteMsg, AdminRole};
+use simple_ica::{ErrorCode, IbcQueryResponse, APP_ORDER};
+
+use callback_capturer::msg::{
+    ExecuteMsg as CapturerExecuteMsg, InstantiateMsg as CapturerInstantiateMsg,
+    QueryMsg as CapturerQueryMsg, ResultResponse,
+};
+use simple_ica_controller::ibc::PACKET_LIFETIME;
+use simple_ica_controller::msg::{
+    AccountResponse, ChannelStatsResponse, ChannelStatus, ExecuteMsg, InstantiateMsg, QueryMsg,
+};
+use simple_ica_host::msg::{
+    AccountResponse as HostAccountResponse, InstantiateMsg as HostInstantiateMsg,
+    QueryMsg as HostQueryMsg,
+};
+
+const OWNER: &str = "owner";
+const BOB: &str = "bob";
+
+fn controller_contract() -> ContractWrapper {
+    use simple_ica_controller::{contract, ibc};
+    ContractWrapper::new(contract::instantiate, contract::query)
+        .with_execute(contract::execute)
+        .with_reply(contract::reply)
+        .with_ibc(
+            ibc::ibc_channel_open,
+            ibc::ibc_channel_connect,
+            ibc::ibc_channel_close,
+            ibc::ibc_packet_receive,
+            ibc::ibc_packet_ack,
+            ibc::ibc_packet_timeout,
+        )
+}
+
+fn host_contract() -> ContractWrapper {
+    use simple_ica_host::contract;
+    ContractWrapper::new(contract::instantiate, contract::query)
+        .with_execute(contract::execute)
+        .with_reply(contract::reply)
+        .with_ibc(
+            contract::ibc_channel_open,
+            contract::ibc_channel_connect,
+            contract::ibc_channel_close,
+            contract::ibc_packet_receive,
+            contract::ibc_packet_ack,
+            contract::ibc_packet_timeout,
+        )
+}
+
+// the accounts of a host with the default backend
+#[cfg(not(feature = "provenance"))]
+fn account_contract() -> ContractWrapper {
+    use cw1_whitelist::contract;
+    ContractWrapper::new(contract::instantiate, contract::query).with_execute(contract::execute)
+}
+
+#[cfg(feature = "provenance")]
+fn account_contract() -> ContractWrapper {
+    use provenance_account::contract;
+    ContractWrapper::new(contract::instantiate, contract::query).with_execute(contract::execute)
+}
+
+fn capturer_contract() -> ContractWrapper {
+    use callback_capturer::contract;
+    ContractWrapper::new(contract::instantiate, contract::query).with_execute(contract::execute)
+}
+
+struct Suite {
+    controller_chain: MockChain,
+    host_chain: MockChain,
+    controller: Addr,
+    /// Sends packets through the controller, and captures the callbacks
+    capturer: Addr,
+    host: Addr,
+    channel: ChannelInfo,
+}
+
+impl Suite {
+    fn relay(&mut self) -> Vec<PacketOutcome> {
+        let relayed = relay_packets(&mut self.controller_chain, &mut self.host_chain).unwrap();
+        relayed.into_iter().map(|packet| packet.outcome).collect()
+    }
+
+    // the account the host created for the channel
+    fn remote_account(&self) -> Addr {
+        let query = HostQueryMsg::Account {
+            channel_id: self.channel.dst.channel_id.clone(),
+        };
+        let res: HostAccountResponse = self.host_chain.query(&self.host, &query).unwrap();
+        Addr::unchecked(res.account.unwrap())
+    }
+
+    fn account(&self) -> AccountResponse {
+        let query = QueryMsg::Account {
+            channel_id: self.channel.src.channel_id.clone(),
+        };
+        self.controller_chain
+            .query(&self.controller, &query)
+            .unwrap()
+    }
+
+    fn stats(&self) -> ChannelStatsResponse {
+        let query = QueryMsg::ChannelStats {
+            channel_id: self.channel.src.channel_id.clone(),
+        };
+        self.controller_chain
+            .query(&self.controller, &query)
+            .unwrap()
+    }
+
+    fn captured(&self, id: &str) -> ResultResponse {
+        let query = CapturerQueryMsg::Result { id: id.into() };
+        self.controller_chain.query(&self.capturer, &query).unwrap()
+    }
+
+    fn send_msgs(&mut self, msgs: Vec<BankMsg>, callback_id: &str) {
+        let msg = CapturerExecuteMsg::SendMsgs {
+            channel_id: self.channel.src.channel_id.clone(),
+            msgs: msgs.into_iter().map(Into::into).collect(),
+            callback_id: callback_id.into(),
+        };
+        let owner = Addr::unchecked(OWNER);
+        self.controller_chain
+            .execute(&owner, &self.capturer, &msg, &[])
+            .unwrap();
+    }
+}
+
+fn setup() -> Suite {
+    let owner = Addr::unchecked(OWNER);
+    let mut controller_chain = MockChain::new("controller-1");
+    let mut host_chain = MockChain::new("host-1");
+
+    let code_id = controller_chain.store_code(controller_contract());
+    let msg = InstantiateMsg {
+        query_result_ttl: None,
+        balance_refresh_interval: None,
+        callback_gas_limit: None,
+        crank_reward: None,
+        ibc_hooks: None,
+    };
+    let controller = controller_chain
+        .instantiate(code_id, &owner, &msg, &[], "controller")
+        .unwrap();
+    let code_id = controller_chain.store_code(capturer_contract());
+    let msg = CapturerInstantiateMsg {
+        simple_ica_controller: controller.to_string(),
+    };
+    let capturer = controller_chain
+        .instantiate(code_id, &owner, &msg, &[], "capturer")
+        .unwrap();
+    // the capturer may send packets
+    let msg = ExecuteMsg::Admin(AdminExecuteMsg::ProposeAdmin {
+        address: capturer.to_string(),
+        role: AdminRole::Operator,
+    });
+    controller_chain
+        .execute(&owner, &controller, &msg, &[])
+        .unwrap();
+    let msg = CapturerExecuteMsg::AcceptControllerAdmin {};
+    controller_chain
+        .execute(&owner, &capturer, &msg, &[])
+        .unwrap();
+
+    let cw1_code_id = host_chain.store_code(account_contract());
+    let code_id = host_chain.store_code(host_contract());
+    let msg = HostInstantiateMsg {
+        cw1_code_id,
+        policy: None,
+        account_backend: None,
+    };
+    let host = host_chain
+        .instantiate(code_id, &owner, &msg, &[], "host")
+        .unwrap();
+
+    // the controller proposes the versions it speaks
+    let channel = open_channel(
+        &mut controller_chain,
+        &controller,
+        &mut host_chain,
+        &host,
+        APP_ORDER,
+        "",
+    )
+    .unwrap();
+
+    let mut suite = Suite {
+        controller_chain,
+        host_chain,
+        controller,
+        capturer,
+        host,
+        channel,
+    };
+    // the WhoAmI sent on connect
+    let outcomes = suite.relay();
+    assert_eq!(outcomes.len(), 1);
+    suite
+}
+
+#[test]
+fn handshake_learns_remote_account() {
+    let suite = setup();
+    let src = suite
+        .controller_chain
+        .channel(&suite.channel.src.channel_id)
+        .unwrap();
+    let dst = suite
+        .host_chain
+        .channel(&suite.channel.dst.channel_id)
+        .unwrap();
+    assert_eq!(src.state, ChannelState::Open);
+    assert_eq!(dst.state, ChannelState::Open);
+    // both ends speak the version the host picked
+    assert_eq!(src.channel.version, suite.channel.version);
+    assert_eq!(dst.channel.version, suite.channel.version);
+
+    let account = suite.account();
+    assert_eq!(account.status, ChannelStatus::Open);
+    assert_eq!(
+        account.remote_addr,
+        Some(suite.remote_account().to_string())
+    );
+}
+
+#[test]
+fn dispatch_runs_on_remote_account() {
+    let mut suite = setup();
+    let remote = suite.remote_account();
+    suite.host_chain.set_balance(&remote, &coins(1000, "uosmo"));
+
+    let send = BankMsg::Send {
+        to_address: BOB.into(),
+        amount: coins(300, "uosmo"),
+    };
+    suite.send_msgs(vec![send], "pay-bob");
+    let outcomes = suite.relay();
+    assert_eq!(outcomes.len(), 1);
+
+    let bob = Addr::unchecked(BOB);
+    assert_eq!(suite.host_chain.balance(&bob, "uosmo"), coin(300, "uosmo"));
+    assert_eq!(
+        suite.host_chain.balance(&remote, "uosmo"),
+        coin(700, "uosmo")
+    );
+    // the callback went to the capturer
+    let captured = suite.captured("pay-bob");
+    assert_eq!(captured.result.error_code(), None);
+    let stats = suite.stats().stats;
+    assert_eq!(stats.packets_acked, 2);
+    assert_eq!(stats.packets_errored, 0);
+}
+
+#[test]
+fn failed_dispatch_reverts_all_messages() {
+    let mut suite = setup();
+    let remote = suite.remote_account();
+    suite.host_chain.set_balance(&remote, &coins(1000, "uosmo"));
+
+    // the second message fails, so the first is reverted too
+    let first = BankMsg::Send {
+        to_address: BOB.into(),
+        amount: coins(600, "uosmo"),
+    };
+    let second = BankMsg::Send {
+        to_address: BOB.into(),
+        amount: coins(600, "uosmo"),
+    };
+    suite.send_msgs(vec![first, second], "overspend");
+    suite.relay();
+
+    let bob = Addr::unchecked(BOB);
+    assert_eq!(suite.host_chain.balance(&bob, "uosmo"), coin(0, "uosmo"));
+    assert_eq!(
+        suite.host_chain.balance(&remote, "uosmo"),
+        coin(1000, "uosmo")
+    );
+    let captured = suite.captured("overspend");
+    assert_eq!(
+        captured.result.error_code(),
+        Some(ErrorCode::ExecutionFailed)
+    );
+    assert_eq!(suite.stats().stats.packets_errored, 1);
+}
+
+#[test]
+fn query_remote_chain() {
+    let mut suite = setup();
+    let remote = suite.remote_account();
+    suite
+        .host_chain
+        .set_balance(&remote, &[coin(5, "uatom"), coin(1000, "uosmo")]);
+
+    // the controller keeps the balance
+    let owner = Addr::unchecked(OWNER);
+    let msg = ExecuteMsg::CheckRemoteBalance {
+        channel_id: suite.channel.src.channel_id.clone(),
+    };
+    suite
+        .controller_chain
+        .execute(&owner, &suite.controller, &msg, &[])
+        .unwrap();
+    suite.relay();
+    assert_eq!(
+        suite.account().remote_balance,
+        vec![coin(5, "uatom"), coin(1000, "uosmo")]
+    );
+
+    // and any query can be run through the capturer
+    let query: QueryRequest<Empty> = BankQuery::Balance {
+        address: remote.to_string(),
+        denom: "uosmo".into(),
+    }
+    .into();
+    let msg = CapturerExecuteMsg::IbcQuery {
+        channel_id: suite.channel.src.channel_id.clone(),
+        msgs: vec![query],
+        callback_id: "balance".into(),
+    };
+    suite
+        .controller_chain
+        .execute(&owner, &suite.capturer, &msg, &[])
+        .unwrap();
+    suite.relay();
+    let res: IbcQueryResponse = suite.captured("balance").result.unwrap_into();
+    assert_eq!(res.results.len(), 1);
+    let balance: cosmwasm_std::BalanceResponse = cosmwasm_std::from_slice(&res.results[0]).unwrap();
+    assert_eq!(balance.amount, coin(1000, "uosmo"));
+}
+
+#[test]
+fn packets_time_out() {
+    let mut suite = setup();
+    let remote = suite.remote_account();
+    suite.host_chain.set_balance(&remote, &coins(1000, "uosmo"));
+
+    let send = BankMsg::Send {
+        to_address: BOB.into(),
+        amount: coins(300, "uosmo"),
+    };
+    suite.send_msgs(vec![send], "late");
+    // the relayer is too slow
+    suite
+        .host_chain
+        .update_block(|block| block.time = block.time.plus_seconds(PACKET_LIFETIME));
+    let outcomes = suite.relay();
+    assert_eq!(outcomes, vec![PacketOutcome::TimedOut]);
+
+    let bob = Addr::unchecked(BOB);
+    assert_eq!(suite.host_chain.balance(&bob, "uosmo"), coin(0, "uosmo"));
+    let captured = suite.captured("late");
+    assert_eq!(captured.result.error_code(), Some(ErrorCode::Timeout));
+    assert_eq!(suite.stats().stats.packets_timed_out, 1);
+}
+
+#[test]
+fn closing_the_channel() {
+    let mut suite = setup();
+    close_channel(
+        &mut suite.host_chain,
+        &mut suite.controller_chain,
+        &suite.channel.dst.channel_id,
+    )
+    .unwrap();
+    assert_eq!(suite.account().status, ChannelStatus::Closed);
+
+    // no packets are sent on it anymore
+    let msg = ExecuteMsg::CheckRemoteBalance {
+        channel_id: suite.channel.src.channel_id.clone(),
+    };
+    let owner = Addr::unchecked(OWNER);
+    suite
+        .controller_chain
+        .execute(&owner, &suite.controller, &msg, &[])
+        .unwrap_err();
+    assert!(suite.controller_chain.pending_packets().is_empty());
+}
+
+#[cfg(feature = "provenance")]
+#[test]
+fn provenance_dispatch_runs_on_remote_account() {
+    use cosmwasm_std::to_binary;
+    use provwasm_std::{bind_name, NameBinding, ProvenanceMsg};
+
+    let mut suite = setup();
+    let remote = suite.remote_account();
+    let bind: CosmosMsg<ProvenanceMsg> =
+        bind_name("acct.pb", remote.clone(), NameBinding::Restricted).unwrap();
+    let msg = ExecuteMsg::SendProvenanceMsgs {
+        channel_id: suite.channel.src.channel_id.clone(),
+        msgs: vec![to_binary(&bind).unwrap()],
+        callback_id: None,
+        fee: None,
+    };
+    let owner = Addr::unchecked(OWNER);
+    suite
+        .controller_chain
+        .execute(&owner, &suite.controller, &msg, &[])
+        .unwrap();
+    let outcomes = suite.relay();
+    assert_eq!(outcomes.len(), 1);
+
+    // the account sent the message on the host chain
+    let stats = suite.stats().stats;
+    assert_eq!(stats.packets_acked, 2);
+    assert_eq!(stats.packets_errored, 0);
+    let custom = suite.host_chain.custom_msgs();
+    assert_eq!(custom.len(), 1);
+    assert_eq!(custom[0].0, remote);
+    let sent: ProvenanceMsg = from_slice(&custom[0].1).unwrap();
+    assert_eq!(CosmosMsg::Custom(sent), bind);
+}
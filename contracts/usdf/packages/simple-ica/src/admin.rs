@@ -0,0 +1,254 @@
+//! Admins of a contract, with two roles: owners manage the admins and the config, operators
+//! may only use the contract. A role is given in two steps, an owner proposes it and the
+//! address accepts it, so a typo can not lock the contract. Only renouncing the last owner
+//! leaves it without one, which is on purpose then.
+
+use cosmwasm_std::{Addr, DepsMut, MessageInfo, Order, Response, StdResult, Storage};
+use cw_storage_plus::Map;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::SimpleIcaError;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminRole {
+    /// Manages the admins and the config, and may do what operators do
+    Owner,
+    /// May use the contract, like sending packets
+    Operator,
+}
+
+impl AdminRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdminRole::Owner => "owner",
+            AdminRole::Operator => "operator",
+        }
+    }
+}
+
+/// Embed as `Admin(AdminExecuteMsg)` in the `ExecuteMsg` of the contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminExecuteMsg {
+    /// Offers the role to the address, which gets it with `AcceptAdmin` (owners only).
+    /// Proposing to an admin changes its role once accepted.
+    ProposeAdmin { address: String, role: AdminRole },
+    /// Withdraws a proposal that was not accepted yet (owners only)
+    CancelProposal { address: String },
+    /// Takes the role proposed to the sender
+    AcceptAdmin {},
+    /// Removes an admin (owners only)
+    RemoveAdmin { address: String },
+    /// Gives up the role of the sender
+    RenounceAdmin {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AdminsResponse {
+    pub admins: Vec<AdminInfo>,
+    /// Roles proposed, but not accepted yet
+    pub proposals: Vec<AdminInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AdminInfo {
+    pub address: String,
+    pub role: AdminRole,
+}
+
+/// The admins of a contract and the roles proposed to others
+pub struct Admins<'a> {
+    admins: Map<'a, &'a Addr, AdminRole>,
+    proposals: Map<'a, &'a Addr, AdminRole>,
+}
+
+impl<'a> Admins<'a> {
+    pub const fn new(admins_namespace: &'a str, proposals_namespace: &'a str) -> Self {
+        Admins {
+            admins: Map::new(admins_namespace),
+            proposals: Map::new(proposals_namespace),
+        }
+    }
+
+    /// Sets the role without a proposal, like for the instantiator
+    pub fn set(&self, storage: &mut dyn Storage, address: &Addr, role: AdminRole) -> StdResult<()> {
+        self.admins.save(storage, address, &role)
+    }
+
+    pub fn role(&self, storage: &dyn Storage, address: &Addr) -> StdResult<Option<AdminRole>> {
+        self.admins.may_load(storage, address)
+    }
+
+    pub fn has_owner(&self, storage: &dyn Storage) -> StdResult<bool> {
+        for item in self.admins.range(storage, None, None, Order::Ascending) {
+            if item?.1 == AdminRole::Owner {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Owners and operators pass
+    pub fn assert_admin(
+        &self,
+        storage: &dyn Storage,
+        address: &Addr,
+    ) -> Result<(), SimpleIcaError> {
+        match self.role(storage, address)? {
+            Some(_) => Ok(()),
+            None => Err(SimpleIcaError::NotAdmin(address.to_string())),
+        }
+    }
+
+    pub fn assert_owner(
+        &self,
+        storage: &dyn Storage,
+        address: &Addr,
+    ) -> Result<(), SimpleIcaError> {
+        match self.role(storage, address)? {
+            Some(AdminRole::Owner) => Ok(()),
+            _ => Err(SimpleIcaError::NotOwner(address.to_string())),
+        }
+    }
+
+    pub fn execute(
+        &self,
+        deps: DepsMut,
+        info: &MessageInfo,
+        msg: AdminExecuteMsg,
+    ) -> Result<Response, SimpleIcaError> {
+        let res = match msg {
+            AdminExecuteMsg::ProposeAdmin { address, role } => {
+                self.assert_owner(deps.storage, &info.sender)?;
+                let address = deps.api.addr_validate(&address)?;
+                self.proposals.save(deps.storage, &address, &role)?;
+                Response::new()
+                    .add_attribute("action", "propose_admin")
+                    .add_attribute("address", address)
+                    .add_attribute("role", role.as_str())
+            }
+            AdminExecuteMsg::CancelProposal { address } => {
+                self.assert_owner(deps.storage, &info.sender)?;
+                let address = deps.api.addr_validate(&address)?;
+                if !self.proposals.has(deps.storage, &address) {
+                    return Err(SimpleIcaError::NoAdminProposal(address.into()));
+                }
+                self.proposals.remove(deps.storage, &address);
+                Response::new()
+                    .add_attribute("action", "cancel_admin_proposal")
+                    .add_attribute("address", address)
+            }
+            AdminExecuteMsg::AcceptAdmin {} => {
+                let role = self
+                    .proposals
+                    .may_load(deps.storage, &info.sender)?
+                    .ok_or_else(|| SimpleIcaError::NoAdminProposal(info.sender.to_string()))?;
+                self.proposals.remove(deps.storage, &info.sender);
+                self.admins.save(deps.storage, &info.sender, &role)?;
+                Response::new()
+                    .add_attribute("action", "accept_admin")
+                    .add_attribute("address", &info.sender)
+                    .add_attribute("role", role.as_str())
+            }
+            AdminExecuteMsg::RemoveAdmin { address } => {
+                self.assert_owner(deps.storage, &info.sender)?;
+                let address = deps.api.addr_validate(&address)?;
+                self.assert_admin(deps.storage, &address)?;
+                self.admins.remove(deps.storage, &address);
+                Response::new()
+                    .add_attribute("action", "remove_admin")
+                    .add_attribute("address", address)
+            }
+            AdminExecuteMsg::RenounceAdmin {} => {
+                self.assert_admin(deps.storage, &info.sender)?;
+                self.admins.remove(deps.storage, &info.sender);
+                Response::new()
+                    .add_attribute("action", "renounce_admin")
+                    .add_attribute("address", &info.sender)
+            }
+        };
+        Ok(res)
+    }
+
+    pub fn query(&self, storage: &dyn Storage) -> StdResult<AdminsResponse> {
+        Ok(AdminsResponse {
+            admins: list(storage, &self.admins)?,
+            proposals: list(storage, &self.proposals)?,
+        })
+    }
+}
+
+fn list(storage: &dyn Storage, roles: &Map<&Addr, AdminRole>) -> StdResult<Vec<AdminInfo>> {
+    roles
+        .range(storage, None, None, Order::Ascending)
+        .map(|r| {
+            let (address, role) = r?;
+            Ok(AdminInfo {
+                address: address.into(),
+                role,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_info};
+
+    const ADMINS: Admins = Admins::new("admins", "admin_proposals");
+
+    #[test]
+    fn propose_and_accept_roles() {
+        let mut deps = mock_dependencies();
+        let owner = Addr::unchecked("owner");
+        ADMINS
+            .set(&mut deps.storage, &owner, AdminRole::Owner)
+            .unwrap();
+
+        // only owners may propose
+        let propose = AdminExecuteMsg::ProposeAdmin {
+            address: "bot".into(),
+            role: AdminRole::Operator,
+        };
+        let err = ADMINS
+            .execute(deps.as_mut(), &mock_info("bot", &[]), propose.clone())
+            .unwrap_err();
+        assert_eq!(err, SimpleIcaError::NotOwner("bot".into()));
+        ADMINS
+            .execute(deps.as_mut(), &mock_info("owner", &[]), propose)
+            .unwrap();
+        // the role is only given once accepted, and only to the address it was proposed to
+        let bot = Addr::unchecked("bot");
+        assert_eq!(ADMINS.role(&deps.storage, &bot).unwrap(), None);
+        let accept = AdminExecuteMsg::AcceptAdmin {};
+        let err = ADMINS
+            .execute(deps.as_mut(), &mock_info("typo", &[]), accept.clone())
+            .unwrap_err();
+        assert_eq!(err, SimpleIcaError::NoAdminProposal("typo".into()));
+        ADMINS
+            .execute(deps.as_mut(), &mock_info("bot", &[]), accept)
+            .unwrap();
+        ADMINS.assert_admin(&deps.storage, &bot).unwrap();
+        ADMINS.assert_owner(&deps.storage, &bot).unwrap_err();
+
+        let admins = ADMINS.query(&deps.storage).unwrap();
+        assert_eq!(admins.admins.len(), 2);
+        assert_eq!(admins.proposals.len(), 0);
+
+        // operators can not remove others, but can leave
+        let remove = AdminExecuteMsg::RemoveAdmin {
+            address: "owner".into(),
+        };
+        ADMINS
+            .execute(deps.as_mut(), &mock_info("bot", &[]), remove)
+            .unwrap_err();
+        let renounce = AdminExecuteMsg::RenounceAdmin {};
+        ADMINS
+            .execute(deps.as_mut(), &mock_info("bot", &[]), renounce)
+            .unwrap();
+        ADMINS.assert_admin(&deps.storage, &bot).unwrap_err();
+    }
+}
@@ -5,6 +5,10 @@ use cosmwasm_std::{to_binary, Binary, CosmosMsg, StdResult, WasmMsg};
 
 use crate::StdAck;
 
+/// The version of the callback format sent by this version of the controller.
+/// Version 1 only had the `id` and `msg`, version 2 added `version` and `packet`.
+pub const CALLBACK_VERSION: u32 = 2;
+
 /// ReceiveIbcResponseMsg should be de/serialized under `Receive()` variant in a ExecuteMsg
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
@@ -12,9 +16,70 @@ pub struct ReceiveIcaResponseMsg {
     /// The ID chosen by the caller in the `callback_id`
     pub id: String,
     pub msg: StdAck,
+    /// The format of the callback. Receivers should ignore fields they do not know,
+    /// so later versions can add more.
+    #[serde(default = "version_1")]
+    pub version: u32,
+    /// Which packet the result belongs to. Unset in version 1, and for broadcasts,
+    /// whose `BroadcastResponse` lists the packets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packet: Option<CallbackPacket>,
+}
+
+fn version_1() -> u32 {
+    1
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct CallbackPacket {
+    /// The channel the packet was sent on
+    pub channel_id: String,
+    /// Unset if no packet was sent, as the transfers of a `SendFundsAndMsgs` failed
+    pub sequence: Option<u64>,
+    pub kind: PacketKind,
+    /// The account on the remote chain, if the controller knows it
+    pub remote_account: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketKind {
+    /// The result of dispatched messages
+    Dispatch,
+    /// The result of an `IbcQuery`
+    Query,
+    /// The result of a trade, `msg` holds a `trade::TradeResponse`
+    Trade,
+    /// The packet timed out, `msg` is an error with the `timeout` code
+    Timeout,
+}
+
+/// The result of a `BroadcastMsgs`, sent once every packet was acknowledged or timed out
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct BroadcastResponse {
+    pub results: Vec<BroadcastResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct BroadcastResult {
+    pub channel_id: String,
+    pub sequence: u64,
+    /// The ack of the host, or an error with the `timeout` code
+    pub result: StdAck,
 }
 
 impl ReceiveIcaResponseMsg {
+    /// A callback in the current format
+    pub fn new(id: impl Into<String>, msg: StdAck, packet: CallbackPacket) -> Self {
+        ReceiveIcaResponseMsg {
+            id: id.into(),
+            msg,
+            version: CALLBACK_VERSION,
+            packet: Some(packet),
+        }
+    }
+
     /// serializes the message
     pub fn into_binary(self) -> StdResult<Binary> {
         let msg = SimpleIcaReceiverExecuteMsg::ReceiveIcaResponse(self);
@@ -43,3 +108,38 @@ impl ReceiveIcaResponseMsg {
 enum SimpleIcaReceiverExecuteMsg {
     ReceiveIcaResponse(ReceiveIcaResponseMsg),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{from_slice, to_vec};
+
+    #[test]
+    fn callback_versions() {
+        // callbacks of older controllers still parse
+        let v1: ReceiveIcaResponseMsg = from_slice(br#"{"id":"a","msg":{"error":"x"}}"#).unwrap();
+        assert_eq!(v1.version, 1);
+        assert_eq!(v1.packet, None);
+
+        let packet = CallbackPacket {
+            channel_id: "channel-1".into(),
+            sequence: Some(5),
+            kind: PacketKind::Query,
+            remote_account: Some("account".into()),
+        };
+        let v2 = ReceiveIcaResponseMsg::new("a", StdAck::Error("x".into()), packet);
+        let json = to_vec(&v2).unwrap();
+        assert_eq!(
+            String::from_utf8(json.clone()).unwrap(),
+            r#"{"id":"a","msg":{"error":"x"},"version":2,"packet":{"channel_id":"channel-1","sequence":5,"kind":"query","remote_account":"account"}}"#
+        );
+        assert_eq!(from_slice::<ReceiveIcaResponseMsg>(&json).unwrap(), v2);
+
+        // and receivers built for version 1 ignore the new fields
+        #[derive(Deserialize)]
+        struct V1 {
+            id: String,
+        }
+        assert_eq!(from_slice::<V1>(&json).unwrap().id, "a");
+    }
+}
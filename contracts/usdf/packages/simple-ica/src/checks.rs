@@ -1,15 +1,66 @@
-pub use crate::{APP_ORDER, IBC_APP_VERSION};
-use cosmwasm_std::IbcOrder;
+pub use crate::APP_ORDER;
+use cosmwasm_std::{IbcOrder, StdError};
 
 use thiserror::Error;
 
+use crate::version::{negotiate_version, IcaVersion, SUPPORTED_VERSIONS};
+
 #[derive(Error, Debug, PartialEq)]
 pub enum SimpleIcaError {
     #[error("Only supports unordered channels")]
     InvalidChannelOrder,
 
-    #[error("Counterparty version must be '{0}'")]
-    InvalidChannelVersion(&'static str),
+    #[error("Unsupported channel version '{0}'")]
+    InvalidChannelVersion(String),
+
+    #[error("No supported version in proposal '{0}'")]
+    NoCommonVersion(String),
+
+    #[error("Invalid protobuf: {0}")]
+    InvalidProtobuf(String),
+
+    #[error("Remote query failed: {0}")]
+    RemoteQueryFailed(String),
+
+    #[error("Invalid query response: {0}")]
+    InvalidQueryResponse(String),
+
+    #[error("Expected {expected} query results, got {got}")]
+    QueryCountMismatch { expected: usize, got: usize },
+
+    #[error("Query result {index} is not a {target}: {msg}")]
+    QueryResultMismatch {
+        index: usize,
+        target: String,
+        msg: String,
+    },
+
+    #[error("Callbacks must come from the controller, not {0}")]
+    NotController(String),
+
+    #[error("No handler for callback '{0}'")]
+    UnknownCallback(String),
+
+    #[error("Remote call failed: {0}")]
+    CallbackFailed(String),
+
+    #[error("Invalid callback result: {0}")]
+    InvalidCallbackResult(String),
+
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("Only admins may do this, not {0}")]
+    NotAdmin(String),
+
+    #[error("Only owners may do this, not {0}")]
+    NotOwner(String),
+
+    #[error("No admin role was proposed to {0}")]
+    NoAdminProposal(String),
+
+    #[error("Cannot migrate from {0}")]
+    CannotMigrate(String),
 }
 
 pub fn check_order(order: &IbcOrder) -> Result<(), SimpleIcaError> {
@@ -20,10 +71,34 @@ pub fn check_order(order: &IbcOrder) -> Result<(), SimpleIcaError> {
     }
 }
 
-pub fn check_version(version: &str) -> Result<(), SimpleIcaError> {
-    if version != IBC_APP_VERSION {
-        Err(SimpleIcaError::InvalidChannelVersion(IBC_APP_VERSION))
-    } else {
-        Ok(())
+/// Ensures this is a single version we support, as agreed on at the end of the handshake
+pub fn check_version(version: &str) -> Result<IcaVersion, SimpleIcaError> {
+    IcaVersion::parse(version)
+        .filter(|version| SUPPORTED_VERSIONS.contains(version))
+        .ok_or_else(|| SimpleIcaError::InvalidChannelVersion(version.to_string()))
+}
+
+/// Ensures the channel fits our protocol, and picks the version to speak. The version may
+/// be a proposal of several versions, of which the highest one we support is chosen.
+pub fn check_channel(order: &IbcOrder, version: &str) -> Result<IcaVersion, SimpleIcaError> {
+    check_order(order)?;
+    negotiate_version(SUPPORTED_VERSIONS, version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BAD_APP_ORDER, IBC_APP_VERSION};
+
+    #[test]
+    fn check_channel_picks_version() {
+        let version = check_channel(&APP_ORDER, IBC_APP_VERSION).unwrap();
+        assert_eq!(version, IcaVersion::V2);
+        let version = check_channel(&APP_ORDER, "simple-ica-v3,simple-ica-v2").unwrap();
+        assert_eq!(version, IcaVersion::V3);
+
+        let err = check_channel(&BAD_APP_ORDER, IBC_APP_VERSION).unwrap_err();
+        assert_eq!(err, SimpleIcaError::InvalidChannelOrder);
+        assert_eq!(check_version(IBC_APP_VERSION), Ok(IcaVersion::V2));
     }
 }
@@ -0,0 +1,125 @@
+//! Support for the ICS-29 fee middleware, which pays relayers for the packets they relay.
+//! See https://github.com/cosmos/ibc/tree/main/spec/app/ics-029-fee-payment
+//!
+//! The middleware unwraps its channel version before it calls the app, so we only ever
+//! see our own version in the handshake.
+
+use cosmwasm_std::{Coin, CosmosMsg, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::proto::Encoder;
+
+/// The fees paid to relayers for one packet
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct IbcFee {
+    /// Paid to the relayer delivering the packet
+    pub recv_fee: Vec<Coin>,
+    /// Paid to the relayer delivering the acknowledgement
+    pub ack_fee: Vec<Coin>,
+    /// Paid to the relayer delivering the timeout, instead of the two above
+    pub timeout_fee: Vec<Coin>,
+}
+
+impl IbcFee {
+    /// The amount escrowed when paying the fee
+    pub fn total(&self) -> Vec<Coin> {
+        add_coins(&add_coins(&self.recv_fee, &self.ack_fee), &self.timeout_fee)
+    }
+
+    /// What is not paid out and returned to the payer if the packet is acknowledged
+    pub fn refund_on_ack(&self) -> Vec<Coin> {
+        add_coins(&[], &self.timeout_fee)
+    }
+
+    /// What is not paid out and returned to the payer if the packet times out
+    pub fn refund_on_timeout(&self) -> Vec<Coin> {
+        add_coins(&self.recv_fee, &self.ack_fee)
+    }
+
+    /// A `MsgPayPacketFee`, which escrows the fee for the next packet sent on the channel.
+    /// It must be executed right before the packet is sent, and fails if the channel has
+    /// no fee middleware.
+    pub fn pay_packet_fee_msg(&self, port_id: &str, channel_id: &str, signer: &str) -> CosmosMsg {
+        let fee = Encoder::new();
+        let fee = encode_coins(fee, 1, &self.recv_fee);
+        let fee = encode_coins(fee, 2, &self.ack_fee);
+        let fee = encode_coins(fee, 3, &self.timeout_fee);
+        let msg = Encoder::new()
+            .message(1, &fee.finish())
+            .string(2, port_id)
+            .string(3, channel_id)
+            .string(4, signer)
+            .finish();
+        CosmosMsg::Stargate {
+            type_url: "/ibc.applications.fee.v1.MsgPayPacketFee".to_string(),
+            value: msg.into(),
+        }
+    }
+}
+
+fn encode_coins(mut encoder: Encoder, field: u32, coins: &[Coin]) -> Encoder {
+    for coin in coins {
+        let coin = Encoder::new()
+            .string(1, &coin.denom)
+            .string(2, &coin.amount.to_string())
+            .finish();
+        encoder = encoder.message(field, &coin);
+    }
+    encoder
+}
+
+/// Merges two lists of coins, summing up the amounts of the same denom.
+/// Zero amounts are dropped and the result is sorted by denom.
+pub fn add_coins(a: &[Coin], b: &[Coin]) -> Vec<Coin> {
+    let mut total: Vec<Coin> = vec![];
+    for coin in a.iter().chain(b) {
+        match total.iter_mut().find(|c| c.denom == coin.denom) {
+            Some(existing) => existing.amount += coin.amount,
+            None => total.push(coin.clone()),
+        }
+    }
+    total.retain(|c| c.amount != Uint128::zero());
+    total.sort_by(|a, b| a.denom.cmp(&b.denom));
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{coin, coins};
+
+    #[test]
+    fn fee_amounts() {
+        let fee = IbcFee {
+            recv_fee: coins(100, "uatom"),
+            ack_fee: vec![coin(50, "uatom"), coin(7, "uosmo")],
+            timeout_fee: coins(30, "uatom"),
+        };
+        assert_eq!(fee.total(), vec![coin(180, "uatom"), coin(7, "uosmo")]);
+        assert_eq!(fee.refund_on_ack(), coins(30, "uatom"));
+        assert_eq!(
+            fee.refund_on_timeout(),
+            vec![coin(150, "uatom"), coin(7, "uosmo")]
+        );
+        assert_eq!(IbcFee::default().total(), vec![]);
+    }
+
+    #[test]
+    fn encode_pay_packet_fee() {
+        let fee = IbcFee {
+            recv_fee: coins(1, "a"),
+            ack_fee: vec![],
+            timeout_fee: vec![],
+        };
+        match fee.pay_packet_fee_msg("wasm.x", "channel-1", "x") {
+            CosmosMsg::Stargate { type_url, value } => {
+                assert_eq!(type_url, "/ibc.applications.fee.v1.MsgPayPacketFee");
+                let expected =
+                    b"\x0a\x08\x0a\x06\x0a\x01a\x12\x011\x12\x06wasm.x\x1a\x09channel-1\x22\x01x";
+                assert_eq!(value.as_slice(), expected);
+            }
+            o => panic!("unexpected message: {:?}", o),
+        }
+    }
+}
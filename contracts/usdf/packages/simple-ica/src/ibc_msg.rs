@@ -1,3 +1,5 @@
+use std::fmt;
+
 use cosmwasm_std::{from_slice, to_binary, Binary, Coin, CosmosMsg, Empty, QueryRequest};
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
@@ -19,6 +21,34 @@ pub enum PacketMsg {
     },
     WhoAmI {},
     Balances {},
+    /// Like `Dispatch`, for hosts on Provenance. Each message is the JSON of a
+    /// `CosmosMsg<ProvenanceMsg>`, so controllers need not link the Provenance bindings.
+    /// Other hosts answer with an `unsupported_packet` error.
+    ProvenanceDispatch {
+        sender: String,
+        msgs: Vec<Binary>,
+        callback_id: Option<String>,
+    },
+    /// Like `IbcQuery`, for hosts on Provenance. Each query is the JSON of a
+    /// `QueryRequest<ProvenanceQuery>`, answered with an `IbcQueryResponse`.
+    ProvenanceQuery {
+        sender: String,
+        msgs: Vec<Binary>,
+        callback_id: Option<String>,
+    },
+    /// The account trades `offer` on the `exchange` contract, answered with a
+    /// `trade::TradeResponse`
+    Trade {
+        sender: String,
+        exchange: String,
+        offer: Coin,
+        callback_id: Option<String>,
+    },
+    /// Sends the funds swept from the account of our closed channel `channel_id` to the
+    /// account of this channel, answered with a `ClaimOrphanedResponse`
+    ClaimOrphaned {
+        channel_id: String,
+    },
 }
 
 /// This is a generic ICS acknowledgement format.
@@ -43,6 +73,28 @@ impl StdAck {
         StdAck::Error(err).ack()
     }
 
+    // create a serialized error message, prefixed with a machine-readable code
+    pub fn fail_with_code(code: ErrorCode, err: impl fmt::Display) -> Binary {
+        StdAck::error_with_code(code, err).ack()
+    }
+
+    // create an error, prefixed with a machine-readable code
+    pub fn error_with_code(code: ErrorCode, err: impl fmt::Display) -> Self {
+        StdAck::Error(format!("{}: {}", code, err))
+    }
+
+    /// Returns the code of an error created by `fail_with_code`.
+    /// None on success, or if the error has no (known) code.
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        match self {
+            StdAck::Result(_) => None,
+            StdAck::Error(err) => {
+                let (code, _) = err.split_once(": ")?;
+                ErrorCode::from_code(code)
+            }
+        }
+    }
+
     pub fn ack(&self) -> Binary {
         to_binary(self).unwrap()
     }
@@ -66,6 +118,69 @@ impl StdAck {
     }
 }
 
+/// Categories of errors the host reports in an error acknowledgement,
+/// so controllers can react to them programmatically
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The packet data could not be parsed
+    InvalidPacket,
+    /// There is no account registered for the channel the packet came in on
+    UnknownChannel,
+    /// The host policy does not allow the requested messages or queries
+    PolicyViolation,
+    /// One of the requested queries failed
+    QueryFailed,
+    /// Executing the dispatched messages failed
+    ExecutionFailed,
+    /// Any other error
+    Internal,
+    /// One of the ICS-20 transfers sent ahead of a dispatch failed, so it was not sent.
+    /// This is reported by the controller, not the host.
+    TransferFailed,
+    /// The packet timed out, reported by the controller
+    Timeout,
+    /// The host can not run this kind of packet, like Provenance messages off Provenance
+    UnsupportedPacket,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidPacket => "invalid_packet",
+            ErrorCode::UnknownChannel => "unknown_channel",
+            ErrorCode::PolicyViolation => "policy_violation",
+            ErrorCode::QueryFailed => "query_failed",
+            ErrorCode::ExecutionFailed => "execution_failed",
+            ErrorCode::Internal => "internal",
+            ErrorCode::TransferFailed => "transfer_failed",
+            ErrorCode::Timeout => "timeout",
+            ErrorCode::UnsupportedPacket => "unsupported_packet",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "invalid_packet" => Some(ErrorCode::InvalidPacket),
+            "unknown_channel" => Some(ErrorCode::UnknownChannel),
+            "policy_violation" => Some(ErrorCode::PolicyViolation),
+            "query_failed" => Some(ErrorCode::QueryFailed),
+            "execution_failed" => Some(ErrorCode::ExecutionFailed),
+            "internal" => Some(ErrorCode::Internal),
+            "transfer_failed" => Some(ErrorCode::TransferFailed),
+            "timeout" => Some(ErrorCode::Timeout),
+            "unsupported_packet" => Some(ErrorCode::UnsupportedPacket),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Return the data field for each message
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct DispatchResponse {
@@ -92,3 +207,33 @@ pub struct BalancesResponse {
     pub account: String,
     pub balances: Vec<Coin>,
 }
+
+/// This is the success response we send on ack for PacketMsg::ClaimOrphaned.
+/// The claimed funds were sent to the account.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimOrphanedResponse {
+    pub account: String,
+    pub claimed: Vec<Coin>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes_round_trip() {
+        let ack: StdAck = from_slice(&StdAck::fail_with_code(
+            ErrorCode::UnknownChannel,
+            "channel-7",
+        ))
+        .unwrap();
+        assert_eq!(ack.error_code(), Some(ErrorCode::UnknownChannel));
+        assert_eq!(ack.unwrap_err(), "unknown_channel: channel-7");
+
+        // errors without a code, or from older hosts, have none
+        let ack: StdAck = from_slice(&StdAck::fail("invalid packet: foo".into())).unwrap();
+        assert_eq!(ack.error_code(), None);
+        let ack: StdAck = from_slice(&StdAck::success(())).unwrap();
+        assert_eq!(ack.error_code(), None);
+    }
+}
@@ -0,0 +1,145 @@
+//! ICS-20 transfers sent as protobuf `MsgTransfer`, as `IbcMsg::Transfer` can not carry a memo.
+//! With the ibc-hooks middleware on the chain, the memo asks for a callback with the outcome
+//! of the transfer, which is delivered to the `sudo` entry point as `IbcLifecycleComplete`.
+
+use cosmwasm_std::{Coin, CosmosMsg};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::proto::{Decoder, Encoder};
+use crate::SimpleIcaError;
+
+pub const TRANSFER_PORT: &str = "transfer";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MsgTransfer {
+    pub source_channel: String,
+    pub token: Coin,
+    pub sender: String,
+    pub receiver: String,
+    /// In nanoseconds since the epoch
+    pub timeout_timestamp: u64,
+    pub memo: String,
+}
+
+impl MsgTransfer {
+    pub fn to_msg(&self) -> CosmosMsg {
+        let token = Encoder::new()
+            .string(1, &self.token.denom)
+            .string(2, &self.token.amount.to_string())
+            .finish();
+        let msg = Encoder::new()
+            .string(1, TRANSFER_PORT)
+            .string(2, &self.source_channel)
+            .message(3, &token)
+            .string(4, &self.sender)
+            .string(5, &self.receiver)
+            .uint64(7, self.timeout_timestamp)
+            .string(8, &self.memo)
+            .finish();
+        CosmosMsg::Stargate {
+            type_url: "/ibc.applications.transfer.v1.MsgTransfer".to_string(),
+            value: msg.into(),
+        }
+    }
+}
+
+/// The memo asking ibc-hooks to tell `contract` how the transfer went
+pub fn callback_memo(contract: &str) -> String {
+    format!(r#"{{"ibc_callback":"{}"}}"#, contract)
+}
+
+/// Reads the packet sequence from the `MsgTransferResponse` returned by the transfer module
+pub fn decode_transfer_response(data: &[u8]) -> Result<u64, SimpleIcaError> {
+    let mut decoder = Decoder::new(data);
+    while let Some((field, value)) = decoder.next_field()? {
+        if field == 1 {
+            return value.into_u64();
+        }
+    }
+    Err(SimpleIcaError::InvalidProtobuf(
+        "MsgTransferResponse without sequence".to_string(),
+    ))
+}
+
+/// The callback of ibc-hooks once a transfer with a callback memo is done
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IbcLifecycleComplete {
+    IbcAck {
+        /// The source channel of the transfer
+        channel: String,
+        sequence: u64,
+        ack: String,
+        success: bool,
+    },
+    IbcTimeout {
+        channel: String,
+        sequence: u64,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::Field;
+    use cosmwasm_std::{coin, from_slice};
+
+    #[test]
+    fn encode_transfer() {
+        let transfer = MsgTransfer {
+            source_channel: "channel-3".to_string(),
+            token: coin(100, "uatom"),
+            sender: "me".to_string(),
+            receiver: "you".to_string(),
+            timeout_timestamp: 7,
+            memo: callback_memo("me"),
+        };
+        let value = match transfer.to_msg() {
+            CosmosMsg::Stargate { type_url, value } => {
+                assert_eq!(type_url, "/ibc.applications.transfer.v1.MsgTransfer");
+                value
+            }
+            o => panic!("unexpected message: {:?}", o),
+        };
+        let mut decoder = Decoder::new(&value);
+        let mut fields = vec![];
+        while let Some(field) = decoder.next_field().unwrap() {
+            fields.push(field);
+        }
+        assert_eq!(fields[0], (1, Field::Bytes(b"transfer")));
+        assert_eq!(fields[1], (2, Field::Bytes(b"channel-3")));
+        assert_eq!(fields[2], (3, Field::Bytes(b"\x0a\x05uatom\x12\x03100")));
+        assert_eq!(fields[5], (7, Field::Varint(7)));
+        assert_eq!(fields[6], (8, Field::Bytes(br#"{"ibc_callback":"me"}"#)));
+    }
+
+    #[test]
+    fn decode_transfer_sequence() {
+        assert_eq!(decode_transfer_response(b"\x08\x2a"), Ok(42));
+        decode_transfer_response(b"").unwrap_err();
+    }
+
+    #[test]
+    fn parse_lifecycle_callbacks() {
+        let ack = br#"{"ibc_ack":{"channel":"channel-3","sequence":5,"ack":"eyJyZXN1bHQiOiJBUT09In0=","success":true}}"#;
+        match from_slice(ack).unwrap() {
+            IbcLifecycleComplete::IbcAck {
+                sequence, success, ..
+            } => {
+                assert_eq!(sequence, 5);
+                assert!(success);
+            }
+            o => panic!("unexpected callback: {:?}", o),
+        }
+        let timeout = br#"{"ibc_timeout":{"channel":"channel-3","sequence":6}}"#;
+        let parsed: IbcLifecycleComplete = from_slice(timeout).unwrap();
+        assert_eq!(
+            parsed,
+            IbcLifecycleComplete::IbcTimeout {
+                channel: "channel-3".to_string(),
+                sequence: 6
+            }
+        );
+    }
+}
@@ -1,15 +1,34 @@
+pub mod admin;
 mod callback;
 mod checks;
+pub mod fee;
 mod ibc_msg;
+pub mod ics20;
+pub mod migrate;
+pub mod proto;
+pub mod query;
+pub mod receiver;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod testing;
+pub mod trade;
+mod version;
 
 use cosmwasm_std::IbcOrder;
 
-pub use crate::callback::ReceiveIcaResponseMsg;
-pub use crate::checks::{check_order, check_version, SimpleIcaError};
+pub use crate::callback::{
+    BroadcastResponse, BroadcastResult, CallbackPacket, PacketKind, ReceiveIcaResponseMsg,
+    CALLBACK_VERSION,
+};
+pub use crate::checks::{check_channel, check_order, check_version, SimpleIcaError};
 pub use crate::ibc_msg::{
-    BalancesResponse, DispatchResponse, IbcQueryResponse, PacketMsg, StdAck, WhoAmIResponse,
+    BalancesResponse, ClaimOrphanedResponse, DispatchResponse, ErrorCode, IbcQueryResponse,
+    PacketMsg, StdAck, WhoAmIResponse,
+};
+pub use crate::version::{
+    negotiate_version, version_proposal, IcaVersion, PacketEnvelope, SUPPORTED_VERSIONS,
 };
 
+/// The version spoken by peers from before version negotiation was added
 pub const IBC_APP_VERSION: &str = "simple-ica-v2";
 pub const APP_ORDER: IbcOrder = IbcOrder::Unordered;
 // we use this for tests to ensure it is rejected
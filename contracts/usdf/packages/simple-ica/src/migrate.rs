@@ -0,0 +1,74 @@
+//! Version checks shared by the `migrate` entry points of our contracts
+
+use cosmwasm_std::Storage;
+use cw2::get_contract_version;
+pub use semver::Version;
+
+use crate::SimpleIcaError;
+
+/// The version the contract is migrated from, `None` if it was deployed before it stored one.
+/// Fails if the stored contract is another one, or newer than the code we migrate to.
+pub fn previous_version(
+    storage: &dyn Storage,
+    contract: &str,
+    version: &str,
+) -> Result<Option<Version>, SimpleIcaError> {
+    let stored = match get_contract_version(storage) {
+        Ok(stored) => stored,
+        // the contract version was never set
+        Err(_) => return Ok(None),
+    };
+    let cannot_migrate =
+        || SimpleIcaError::CannotMigrate(format!("{} {}", stored.contract, stored.version));
+    if stored.contract != contract {
+        return Err(cannot_migrate());
+    }
+    let previous = Version::parse(&stored.version).map_err(|_| cannot_migrate())?;
+    let current = Version::parse(version).map_err(|_| cannot_migrate())?;
+    if previous > current {
+        return Err(cannot_migrate());
+    }
+    Ok(Some(previous))
+}
+
+/// Whether the previous version is older than `version`. Deployments from before the version
+/// was stored are older than any.
+pub fn is_older(previous: Option<&Version>, version: &Version) -> bool {
+    match previous {
+        Some(previous) => previous < version,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+    use cw2::set_contract_version;
+
+    #[test]
+    fn only_older_versions_of_the_contract_migrate() {
+        let mut storage = MockStorage::new();
+        let found = previous_version(&storage, "crates.io:foo", "0.3.0").unwrap();
+        assert_eq!(found, None);
+
+        set_contract_version(&mut storage, "crates.io:foo", "0.2.0").unwrap();
+        let found = previous_version(&storage, "crates.io:foo", "0.3.0").unwrap();
+        assert_eq!(found, Some(Version::new(0, 2, 0)));
+        let err = previous_version(&storage, "crates.io:foo", "0.1.0").unwrap_err();
+        assert_eq!(
+            err,
+            SimpleIcaError::CannotMigrate("crates.io:foo 0.2.0".into())
+        );
+        let err = previous_version(&storage, "crates.io:bar", "0.3.0").unwrap_err();
+        assert_eq!(
+            err,
+            SimpleIcaError::CannotMigrate("crates.io:foo 0.2.0".into())
+        );
+
+        let v0_3 = Version::new(0, 3, 0);
+        assert!(is_older(None, &v0_3));
+        assert!(is_older(Some(&Version::new(0, 2, 0)), &v0_3));
+        assert!(!is_older(Some(&v0_3), &v0_3));
+    }
+}
@@ -0,0 +1,183 @@
+//! Just enough of the protobuf wire format to build and read the few
+//! messages we exchange with native ibc-go modules, without pulling in
+//! a full protobuf implementation.
+
+use crate::SimpleIcaError;
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_LEN: u64 = 2;
+
+/// Builds a protobuf message field by field. As in proto3, empty values are omitted.
+#[derive(Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn uint64(mut self, field: u32, value: u64) -> Self {
+        if value != 0 {
+            self.key(field, WIRE_VARINT);
+            write_varint(&mut self.buf, value);
+        }
+        self
+    }
+
+    pub fn string(self, field: u32, value: &str) -> Self {
+        self.bytes(field, value.as_bytes())
+    }
+
+    pub fn bytes(mut self, field: u32, value: &[u8]) -> Self {
+        if !value.is_empty() {
+            self.key(field, WIRE_LEN);
+            write_varint(&mut self.buf, value.len() as u64);
+            self.buf.extend_from_slice(value);
+        }
+        self
+    }
+
+    /// Embedded messages are written even if empty, as their presence is meaningful
+    pub fn message(mut self, field: u32, value: &[u8]) -> Self {
+        self.key(field, WIRE_LEN);
+        write_varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn key(&mut self, field: u32, wire_type: u64) {
+        write_varint(&mut self.buf, (u64::from(field) << 3) | wire_type);
+    }
+}
+
+/// One field read by the `Decoder`
+#[derive(Debug, PartialEq)]
+pub enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+impl<'a> Field<'a> {
+    pub fn into_u64(self) -> Result<u64, SimpleIcaError> {
+        match self {
+            Field::Varint(value) => Ok(value),
+            Field::Bytes(_) => Err(invalid("expected varint, got bytes")),
+        }
+    }
+
+    pub fn into_bytes(self) -> Result<&'a [u8], SimpleIcaError> {
+        match self {
+            Field::Bytes(value) => Ok(value),
+            Field::Varint(_) => Err(invalid("expected bytes, got varint")),
+        }
+    }
+
+    pub fn into_string(self) -> Result<String, SimpleIcaError> {
+        String::from_utf8(self.into_bytes()?.to_vec()).map_err(|_| invalid("invalid utf-8 string"))
+    }
+}
+
+/// Iterates over the fields of a protobuf message
+pub struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Decoder { data }
+    }
+
+    /// Returns the next field number and value, or None at the end of the message.
+    /// Fixed-size fields are not used by any message we read and are rejected.
+    pub fn next_field(&mut self) -> Result<Option<(u32, Field<'a>)>, SimpleIcaError> {
+        if self.data.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let field = (key >> 3) as u32;
+        let value = match key & 0x7 {
+            WIRE_VARINT => Field::Varint(self.varint()?),
+            WIRE_LEN => {
+                let len = self.varint()? as usize;
+                if len > self.data.len() {
+                    return Err(invalid("length exceeds message"));
+                }
+                let (value, rest) = self.data.split_at(len);
+                self.data = rest;
+                Field::Bytes(value)
+            }
+            wire_type => return Err(invalid(&format!("unsupported wire type {}", wire_type))),
+        };
+        Ok(Some((field, value)))
+    }
+
+    fn varint(&mut self) -> Result<u64, SimpleIcaError> {
+        let mut value = 0u64;
+        for (i, byte) in self.data.iter().enumerate().take(10) {
+            value |= u64::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                self.data = &self.data[i + 1..];
+                return Ok(value);
+            }
+        }
+        Err(invalid("truncated varint"))
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn invalid(msg: &str) -> SimpleIcaError {
+    SimpleIcaError::InvalidProtobuf(msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let inner = Encoder::new().string(1, "uatom").string(2, "1234").finish();
+        let encoded = Encoder::new()
+            .string(1, "transfer")
+            .uint64(2, 300)
+            .message(3, &inner)
+            .uint64(4, 0)
+            .finish();
+        // 300 needs a two byte varint, the zero value is skipped
+        assert_eq!(&encoded[10..13], &[0x10, 0xac, 0x02]);
+
+        let mut decoder = Decoder::new(&encoded);
+        let (field, value) = decoder.next_field().unwrap().unwrap();
+        assert_eq!(1, field);
+        assert_eq!("transfer", value.into_string().unwrap());
+        let (field, value) = decoder.next_field().unwrap().unwrap();
+        assert_eq!(2, field);
+        assert_eq!(300, value.into_u64().unwrap());
+        let (field, value) = decoder.next_field().unwrap().unwrap();
+        assert_eq!(3, field);
+        assert_eq!(inner.as_slice(), value.into_bytes().unwrap());
+        assert_eq!(None, decoder.next_field().unwrap());
+    }
+
+    #[test]
+    fn decode_rejects_truncated_data() {
+        let encoded = Encoder::new().string(1, "transfer").finish();
+        let mut decoder = Decoder::new(&encoded[..5]);
+        decoder.next_field().unwrap_err();
+
+        let mut decoder = Decoder::new(&[0x08, 0x80]);
+        decoder.next_field().unwrap_err();
+    }
+}
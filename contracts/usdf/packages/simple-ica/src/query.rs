@@ -0,0 +1,280 @@
+//! Typed helpers for `IbcQuery`. Every query is paired with the type of its response,
+//! so the results of the ack can be decoded without knowing their order by heart.
+//!
+//! ```
+//! use cosmwasm_std::{AllBalanceResponse, BalanceResponse};
+//! use simple_ica::query::{all_balances, balance, QueryBatch};
+//!
+//! let batch = (balance("remote-addr", "uatom"), all_balances("remote-addr"));
+//! // send `batch.requests()` as the `msgs` of an `IbcQuery`, and in the callback:
+//! # let ack = simple_ica::StdAck::Error("not sent".into());
+//! let decoded: Result<(BalanceResponse, AllBalanceResponse), _> = batch.decode(&ack);
+//! ```
+
+use std::any::type_name;
+use std::marker::PhantomData;
+
+use cosmwasm_std::{
+    from_slice, to_binary, AllBalanceResponse, BalanceResponse, BankQuery, Binary, Empty,
+    QueryRequest, StdResult, WasmQuery,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{IbcQueryResponse, SimpleIcaError, StdAck};
+
+/// A query, along with the type of its response
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypedQuery<T> {
+    request: QueryRequest<Empty>,
+    response: PhantomData<T>,
+}
+
+impl<T> TypedQuery<T> {
+    /// Pairs any query with a response type. It is up to the caller to pick the right one.
+    pub fn new(request: QueryRequest<Empty>) -> Self {
+        TypedQuery {
+            request,
+            response: PhantomData,
+        }
+    }
+
+    pub fn request(&self) -> &QueryRequest<Empty> {
+        &self.request
+    }
+}
+
+impl<T: DeserializeOwned> TypedQuery<T> {
+    /// Decodes the result of this query, which is at `index` in the ack
+    pub fn decode_result(&self, index: usize, result: &[u8]) -> Result<T, SimpleIcaError> {
+        from_slice(result).map_err(|err| SimpleIcaError::QueryResultMismatch {
+            index,
+            target: type_name::<T>().to_string(),
+            msg: err.to_string(),
+        })
+    }
+}
+
+pub fn balance(
+    address: impl Into<String>,
+    denom: impl Into<String>,
+) -> TypedQuery<BalanceResponse> {
+    TypedQuery::new(
+        BankQuery::Balance {
+            address: address.into(),
+            denom: denom.into(),
+        }
+        .into(),
+    )
+}
+
+pub fn all_balances(address: impl Into<String>) -> TypedQuery<AllBalanceResponse> {
+    TypedQuery::new(
+        BankQuery::AllBalances {
+            address: address.into(),
+        }
+        .into(),
+    )
+}
+
+/// A smart query to a contract on the remote chain, answered with a `T`
+pub fn smart<T>(
+    contract_addr: impl Into<String>,
+    msg: &impl Serialize,
+) -> StdResult<TypedQuery<T>> {
+    Ok(TypedQuery::new(
+        WasmQuery::Smart {
+            contract_addr: contract_addr.into(),
+            msg: to_binary(msg)?,
+        }
+        .into(),
+    ))
+}
+
+/// Several typed queries sent in one `IbcQuery`: a single query, a tuple of them,
+/// or a `Vec` of queries of the same type. To decode into a struct, convert the tuple.
+pub trait QueryBatch {
+    type Output;
+
+    /// The `msgs` to send in the `IbcQuery`
+    fn requests(&self) -> Vec<QueryRequest<Empty>>;
+
+    /// Decodes the results of the `IbcQueryResponse`, which come in the order of the requests
+    fn decode_results(&self, results: &[Binary]) -> Result<Self::Output, SimpleIcaError>;
+
+    /// Decodes the ack of the `IbcQuery`
+    fn decode(&self, ack: &StdAck) -> Result<Self::Output, SimpleIcaError> {
+        match ack {
+            StdAck::Result(data) => {
+                let response: IbcQueryResponse = from_slice(data)
+                    .map_err(|err| SimpleIcaError::InvalidQueryResponse(err.to_string()))?;
+                self.decode_results(&response.results)
+            }
+            StdAck::Error(err) => Err(SimpleIcaError::RemoteQueryFailed(err.clone())),
+        }
+    }
+}
+
+fn check_count(expected: usize, results: &[Binary]) -> Result<(), SimpleIcaError> {
+    if results.len() != expected {
+        return Err(SimpleIcaError::QueryCountMismatch {
+            expected,
+            got: results.len(),
+        });
+    }
+    Ok(())
+}
+
+impl<T: DeserializeOwned> QueryBatch for TypedQuery<T> {
+    type Output = T;
+
+    fn requests(&self) -> Vec<QueryRequest<Empty>> {
+        vec![self.request.clone()]
+    }
+
+    fn decode_results(&self, results: &[Binary]) -> Result<T, SimpleIcaError> {
+        check_count(1, results)?;
+        self.decode_result(0, &results[0])
+    }
+}
+
+impl<T: DeserializeOwned> QueryBatch for Vec<TypedQuery<T>> {
+    type Output = Vec<T>;
+
+    fn requests(&self) -> Vec<QueryRequest<Empty>> {
+        self.iter().map(|query| query.request.clone()).collect()
+    }
+
+    fn decode_results(&self, results: &[Binary]) -> Result<Vec<T>, SimpleIcaError> {
+        check_count(self.len(), results)?;
+        self.iter()
+            .zip(results)
+            .enumerate()
+            .map(|(index, (query, result))| query.decode_result(index, result))
+            .collect()
+    }
+}
+
+macro_rules! impl_query_batch {
+    ($count:expr; $($name:ident $index:tt),+) => {
+        impl<$($name: DeserializeOwned),+> QueryBatch for ($(TypedQuery<$name>,)+) {
+            type Output = ($($name,)+);
+
+            fn requests(&self) -> Vec<QueryRequest<Empty>> {
+                vec![$(self.$index.request.clone()),+]
+            }
+
+            fn decode_results(&self, results: &[Binary]) -> Result<Self::Output, SimpleIcaError> {
+                check_count($count, results)?;
+                Ok(($(self.$index.decode_result($index, &results[$index])?,)+))
+            }
+        }
+    };
+}
+
+impl_query_batch!(1; A 0);
+impl_query_batch!(2; A 0, B 1);
+impl_query_batch!(3; A 0, B 1, C 2);
+impl_query_batch!(4; A 0, B 1, C 2, D 3);
+impl_query_batch!(5; A 0, B 1, C 2, D 3, E 4);
+impl_query_batch!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{coin, coins};
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct ConfigResponse {
+        owner: String,
+    }
+
+    // what the host answers to the queries
+    fn ack(results: Vec<Binary>) -> StdAck {
+        StdAck::Result(to_binary(&IbcQueryResponse { results }).unwrap())
+    }
+
+    #[test]
+    fn decode_typed_results() {
+        let batch = (
+            balance("remote", "uatom"),
+            all_balances("remote"),
+            smart::<ConfigResponse>("contract", &"config").unwrap(),
+        );
+        let requests = batch.requests();
+        assert_eq!(3, requests.len());
+        assert_eq!(
+            requests[2],
+            QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr: "contract".into(),
+                msg: Binary::from(br#""config""#),
+            })
+        );
+
+        let results = vec![
+            to_binary(&BalanceResponse {
+                amount: coin(5, "uatom"),
+            })
+            .unwrap(),
+            to_binary(&AllBalanceResponse {
+                amount: coins(5, "uatom"),
+            })
+            .unwrap(),
+            to_binary(&ConfigResponse { owner: "me".into() }).unwrap(),
+        ];
+        let (balance, all, config) = batch.decode(&ack(results.clone())).unwrap();
+        assert_eq!(balance.amount, coin(5, "uatom"));
+        assert_eq!(all.amount, coins(5, "uatom"));
+        assert_eq!(config.owner, "me");
+
+        // queries of the same type can be decoded as a vec
+        let batch = vec![all_balances("a"), all_balances("b")];
+        let decoded = batch
+            .decode(&ack(vec![results[1].clone(), results[1].clone()]))
+            .unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[1].amount, coins(5, "uatom"));
+    }
+
+    #[test]
+    fn decode_reports_mismatches() {
+        let batch = (balance("remote", "uatom"), all_balances("remote"));
+
+        let err = batch.decode(&ack(vec![])).unwrap_err();
+        assert_eq!(
+            err,
+            SimpleIcaError::QueryCountMismatch {
+                expected: 2,
+                got: 0
+            }
+        );
+
+        let result = to_binary(&BalanceResponse {
+            amount: coin(5, "uatom"),
+        })
+        .unwrap();
+        let err = batch
+            .decode(&ack(vec![result.clone(), result]))
+            .unwrap_err();
+        match err {
+            SimpleIcaError::QueryResultMismatch { index, target, .. } => {
+                assert_eq!(index, 1);
+                assert!(target.ends_with("AllBalanceResponse"));
+            }
+            o => panic!("unexpected error: {:?}", o),
+        }
+
+        let err = batch
+            .decode(&StdAck::Error("query_failed: no such denom".into()))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            SimpleIcaError::RemoteQueryFailed("query_failed: no such denom".into())
+        );
+        let err = batch
+            .decode(&StdAck::Result(Binary::from(b"nope")))
+            .unwrap_err();
+        assert!(matches!(err, SimpleIcaError::InvalidQueryResponse(_)));
+    }
+}
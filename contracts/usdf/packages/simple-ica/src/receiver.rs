@@ -0,0 +1,177 @@
+//! Helpers for contracts that get the results of their packets from simple-ica-controller.
+//! Add `ReceiveIcaResponse(ReceiveIcaResponseMsg)` to the `ExecuteMsg` and pass it to a
+//! `CallbackRouter`, which checks it comes from the controller and calls the handler for its id.
+//!
+//! ```
+//! use cosmwasm_std::{Addr, DepsMut, Env, MessageInfo, Response};
+//! use simple_ica::receiver::{parse_ack, CallbackRouter};
+//! use simple_ica::{DispatchResponse, ReceiveIcaResponseMsg, SimpleIcaError};
+//!
+//! fn on_delegate(
+//!     _deps: DepsMut,
+//!     _env: Env,
+//!     callback: ReceiveIcaResponseMsg,
+//! ) -> Result<Response, SimpleIcaError> {
+//!     let response: DispatchResponse = parse_ack(&callback.msg)?;
+//!     Ok(Response::new().add_attribute("results", response.results.len().to_string()))
+//! }
+//!
+//! fn receive(
+//!     deps: DepsMut,
+//!     env: Env,
+//!     info: MessageInfo,
+//!     callback: ReceiveIcaResponseMsg,
+//! ) -> Result<Response, SimpleIcaError> {
+//!     let controller = Addr::unchecked("controller"); // usually loaded from the config
+//!     CallbackRouter::new()
+//!         .route("delegate-", on_delegate)
+//!         .dispatch(deps, env, &info, &controller, callback)
+//! }
+//! ```
+
+use cosmwasm_std::{from_slice, Addr, DepsMut, Env, MessageInfo, Response};
+use serde::de::DeserializeOwned;
+
+use crate::{ReceiveIcaResponseMsg, SimpleIcaError, StdAck};
+
+/// Handles the callbacks routed to it
+pub type CallbackHandler<E> = fn(DepsMut, Env, ReceiveIcaResponseMsg) -> Result<Response, E>;
+
+/// Routes callbacks to handlers by the prefix of their id
+pub struct CallbackRouter<E> {
+    routes: Vec<(String, CallbackHandler<E>)>,
+}
+
+impl<E> Default for CallbackRouter<E> {
+    fn default() -> Self {
+        CallbackRouter { routes: vec![] }
+    }
+}
+
+impl<E: From<SimpleIcaError>> CallbackRouter<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Callbacks whose id starts with `prefix` go to `handler`. If several prefixes match,
+    /// the longest wins. An empty prefix matches every id.
+    pub fn route(mut self, prefix: impl Into<String>, handler: CallbackHandler<E>) -> Self {
+        self.routes.push((prefix.into(), handler));
+        self
+    }
+
+    /// Checks the callback was sent by the controller, and calls the handler for its id
+    pub fn dispatch(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: &MessageInfo,
+        controller: &Addr,
+        callback: ReceiveIcaResponseMsg,
+    ) -> Result<Response, E> {
+        verify_controller(info, controller)?;
+        let handler = self
+            .routes
+            .iter()
+            .filter(|(prefix, _)| callback.id.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, handler)| handler)
+            .ok_or_else(|| SimpleIcaError::UnknownCallback(callback.id.clone()))?;
+        handler(deps, env, callback)
+    }
+}
+
+/// Only the controller may send callbacks, anyone else could fake results
+pub fn verify_controller(info: &MessageInfo, controller: &Addr) -> Result<(), SimpleIcaError> {
+    if info.sender != *controller {
+        return Err(SimpleIcaError::NotController(info.sender.to_string()));
+    }
+    Ok(())
+}
+
+/// Decodes the result of a callback, like a `DispatchResponse` or an `IbcQueryResponse`.
+/// Errors of the host or controller are returned as `CallbackFailed`.
+pub fn parse_ack<T: DeserializeOwned>(ack: &StdAck) -> Result<T, SimpleIcaError> {
+    match ack {
+        StdAck::Result(data) => {
+            from_slice(data).map_err(|err| SimpleIcaError::InvalidCallbackResult(err.to_string()))
+        }
+        StdAck::Error(err) => Err(SimpleIcaError::CallbackFailed(err.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{mock_dispatch_callback, mock_error_callback};
+    use crate::{DispatchResponse, ErrorCode};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::Binary;
+
+    fn short(
+        _deps: DepsMut,
+        _env: Env,
+        _: ReceiveIcaResponseMsg,
+    ) -> Result<Response, SimpleIcaError> {
+        Ok(Response::new().add_attribute("route", "short"))
+    }
+
+    fn long(
+        _deps: DepsMut,
+        _env: Env,
+        _: ReceiveIcaResponseMsg,
+    ) -> Result<Response, SimpleIcaError> {
+        Ok(Response::new().add_attribute("route", "long"))
+    }
+
+    #[test]
+    fn route_callbacks_by_prefix() {
+        let mut deps = mock_dependencies();
+        let controller = Addr::unchecked("controller");
+        let router = CallbackRouter::new()
+            .route("swap-", short)
+            .route("swap-osmo-", long);
+        let info = mock_info("controller", &[]);
+
+        let callback = mock_dispatch_callback("swap-osmo-1", vec![]);
+        let res = router
+            .dispatch(deps.as_mut(), mock_env(), &info, &controller, callback)
+            .unwrap();
+        assert_eq!(res.attributes[0].value, "long");
+        let callback = mock_dispatch_callback("swap-juno-1", vec![]);
+        let res = router
+            .dispatch(deps.as_mut(), mock_env(), &info, &controller, callback)
+            .unwrap();
+        assert_eq!(res.attributes[0].value, "short");
+
+        let callback = mock_dispatch_callback("stake-1", vec![]);
+        let err = router
+            .dispatch(deps.as_mut(), mock_env(), &info, &controller, callback)
+            .unwrap_err();
+        assert_eq!(err, SimpleIcaError::UnknownCallback("stake-1".into()));
+
+        // only the controller may call back
+        let callback = mock_dispatch_callback("swap-1", vec![]);
+        let info = mock_info("someone", &[]);
+        let err = router
+            .dispatch(deps.as_mut(), mock_env(), &info, &controller, callback)
+            .unwrap_err();
+        assert_eq!(err, SimpleIcaError::NotController("someone".into()));
+    }
+
+    #[test]
+    fn parse_callback_results() {
+        let callback = mock_dispatch_callback("a", vec![Binary::from(b"done")]);
+        let response: DispatchResponse = parse_ack(&callback.msg).unwrap();
+        assert_eq!(response.results, vec![Binary::from(b"done")]);
+
+        let callback = mock_error_callback("a", ErrorCode::ExecutionFailed, "out of funds");
+        let err = parse_ack::<DispatchResponse>(&callback.msg).unwrap_err();
+        assert_eq!(
+            err,
+            SimpleIcaError::CallbackFailed("execution_failed: out of funds".into())
+        );
+        let err = parse_ack::<u64>(&StdAck::Result(b"{}".into())).unwrap_err();
+        assert!(matches!(err, SimpleIcaError::InvalidCallbackResult(_)));
+    }
+}
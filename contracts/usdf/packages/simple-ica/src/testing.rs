@@ -0,0 +1,45 @@
+//! Callbacks like the controller sends them, to test receivers without setting up IBC
+
+use cosmwasm_std::{to_binary, Binary};
+
+use crate::{
+    CallbackPacket, DispatchResponse, ErrorCode, IbcQueryResponse, PacketKind,
+    ReceiveIcaResponseMsg, StdAck,
+};
+
+pub const MOCK_CHANNEL_ID: &str = "channel-0";
+pub const MOCK_REMOTE_ACCOUNT: &str = "remote-account";
+
+/// A callback with the given result, about the first packet on `MOCK_CHANNEL_ID`
+pub fn mock_callback(id: &str, msg: StdAck, kind: PacketKind) -> ReceiveIcaResponseMsg {
+    let packet = CallbackPacket {
+        channel_id: MOCK_CHANNEL_ID.to_string(),
+        sequence: Some(1),
+        kind,
+        remote_account: Some(MOCK_REMOTE_ACCOUNT.to_string()),
+    };
+    ReceiveIcaResponseMsg::new(id, msg, packet)
+}
+
+/// The callback of dispatched messages, which returned `results`
+pub fn mock_dispatch_callback(id: &str, results: Vec<Binary>) -> ReceiveIcaResponseMsg {
+    let msg = StdAck::Result(to_binary(&DispatchResponse { results }).unwrap());
+    mock_callback(id, msg, PacketKind::Dispatch)
+}
+
+/// The callback of an `IbcQuery`, with the serialized query responses
+pub fn mock_query_callback(id: &str, results: Vec<Binary>) -> ReceiveIcaResponseMsg {
+    let msg = StdAck::Result(to_binary(&IbcQueryResponse { results }).unwrap());
+    mock_callback(id, msg, PacketKind::Query)
+}
+
+/// The callback of dispatched messages the host failed to execute
+pub fn mock_error_callback(id: &str, code: ErrorCode, err: &str) -> ReceiveIcaResponseMsg {
+    mock_callback(id, StdAck::error_with_code(code, err), PacketKind::Dispatch)
+}
+
+/// The callback of a packet that timed out
+pub fn mock_timeout_callback(id: &str) -> ReceiveIcaResponseMsg {
+    let msg = StdAck::error_with_code(ErrorCode::Timeout, "packet timed out");
+    mock_callback(id, msg, PacketKind::Timeout)
+}
@@ -0,0 +1,97 @@
+//! Trades on the `exchange` contract, which swaps its collateral denom for its native denom and
+//! back at 1:1. The host runs them with `PacketMsg::Trade` and reads the result from the events.
+
+use cosmwasm_std::{Coin, Event, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The `action` attribute of a trade on the exchange
+pub const TRADE_ACTION: &str = "provwasm.contracts.exchange.trade";
+
+/// The message of the exchange we send
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExchangeExecuteMsg {
+    /// Trades the one coin sent along
+    Trade {},
+}
+
+/// This is the success response we send on ack for PacketMsg::Trade
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TradeResponse {
+    /// What the account gave to the exchange
+    pub sent: Coin,
+    /// What the account got back
+    pub received: Coin,
+}
+
+impl TradeResponse {
+    /// Finds the trade in the events of the execution. None if there was none, or more than one.
+    pub fn from_events(events: &[Event]) -> Option<Self> {
+        let mut trades = events.iter().filter(|event| {
+            event
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "action" && attr.value == TRADE_ACTION)
+        });
+        let event = trades.next()?;
+        if trades.next().is_some() {
+            return None;
+        }
+        let coin = |key: &str| {
+            let attr = event.attributes.iter().find(|attr| attr.key == key)?;
+            parse_coin(&attr.value)
+        };
+        Some(TradeResponse {
+            sent: coin("sent")?,
+            received: coin("received")?,
+        })
+    }
+}
+
+// parses a coin as formatted by `Coin::to_string`, like "200nhash"
+fn parse_coin(value: &str) -> Option<Coin> {
+    let split = value.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, denom) = value.split_at(split);
+    let amount: u128 = amount.parse().ok()?;
+    Some(Coin {
+        denom: denom.to_string(),
+        amount: Uint128::new(amount),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::coin;
+
+    #[test]
+    fn read_trade_from_events() {
+        let account = Event::new("wasm")
+            .add_attribute("_contract_address", "account")
+            .add_attribute("action", "execute");
+        let trade = Event::new("wasm")
+            .add_attribute("_contract_address", "exchange")
+            .add_attribute("action", TRADE_ACTION)
+            .add_attribute("sent", coin(200, "ibc/27394FB092D2ECCD").to_string())
+            .add_attribute("received", coin(200, "nhash").to_string());
+        let events = vec![account.clone(), trade.clone()];
+        assert_eq!(
+            TradeResponse::from_events(&events),
+            Some(TradeResponse {
+                sent: coin(200, "ibc/27394FB092D2ECCD"),
+                received: coin(200, "nhash"),
+            })
+        );
+
+        // no trade, or two of them
+        assert_eq!(TradeResponse::from_events(&[account]), None);
+        assert_eq!(TradeResponse::from_events(&[trade.clone(), trade]), None);
+        // or attributes which are no coins
+        let trade = Event::new("wasm")
+            .add_attribute("action", TRADE_ACTION)
+            .add_attribute("sent", "nhash")
+            .add_attribute("received", "200nhash");
+        assert_eq!(TradeResponse::from_events(&[trade]), None);
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+use cosmwasm_std::{from_slice, to_binary, Binary, StdError, StdResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{PacketMsg, SimpleIcaError};
+
+/// The versions of the simple-ica protocol. In the handshake a side may propose
+/// several of them as a comma-separated list, the other side picks the highest
+/// one both support. The channel then speaks that version.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum IcaVersion {
+    /// Packets are a plain JSON `PacketMsg`
+    V2,
+    /// Packets are wrapped in a `PacketEnvelope` naming the version,
+    /// so a packet sent with the wrong version is detected
+    V3,
+}
+
+/// All versions this code can speak, oldest first
+pub const SUPPORTED_VERSIONS: &[IcaVersion] = &[IcaVersion::V2, IcaVersion::V3];
+
+impl IcaVersion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IcaVersion::V2 => "simple-ica-v2",
+            IcaVersion::V3 => "simple-ica-v3",
+        }
+    }
+
+    pub fn parse(version: &str) -> Option<Self> {
+        match version.trim() {
+            "simple-ica-v2" => Some(IcaVersion::V2),
+            "simple-ica-v3" => Some(IcaVersion::V3),
+            _ => None,
+        }
+    }
+
+    /// Encodes a packet for a channel speaking this version
+    pub fn encode_packet(&self, msg: &PacketMsg) -> StdResult<Binary> {
+        match self {
+            IcaVersion::V2 => to_binary(msg),
+            IcaVersion::V3 => to_binary(&PacketEnvelope {
+                version: *self,
+                packet: msg.clone(),
+            }),
+        }
+    }
+
+    /// Decodes a packet received on a channel speaking this version
+    pub fn decode_packet(&self, data: &[u8]) -> StdResult<PacketMsg> {
+        match self {
+            IcaVersion::V2 => from_slice(data),
+            IcaVersion::V3 => {
+                let envelope: PacketEnvelope = from_slice(data)?;
+                if envelope.version != *self {
+                    return Err(StdError::generic_err(format!(
+                        "packet version {} does not match channel version {}",
+                        envelope.version, self
+                    )));
+                }
+                Ok(envelope.packet)
+            }
+        }
+    }
+}
+
+impl fmt::Display for IcaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The wire format of packets from v3 on
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PacketEnvelope {
+    pub version: IcaVersion,
+    pub packet: PacketMsg,
+}
+
+/// The version string we propose when opening a channel, newest first
+pub fn version_proposal(supported: &[IcaVersion]) -> String {
+    let mut versions: Vec<_> = supported.iter().map(IcaVersion::as_str).collect();
+    versions.reverse();
+    versions.join(",")
+}
+
+/// Picks the highest version from the comma-separated proposal that is also in `supported`.
+/// Unknown versions in the proposal are ignored, as they may come from newer peers.
+pub fn negotiate_version(
+    supported: &[IcaVersion],
+    proposal: &str,
+) -> Result<IcaVersion, SimpleIcaError> {
+    proposal
+        .split(',')
+        .filter_map(IcaVersion::parse)
+        .filter(|version| supported.contains(version))
+        .max()
+        .ok_or_else(|| SimpleIcaError::NoCommonVersion(proposal.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_between_mixed_version_peers() {
+        let v2_only = &[IcaVersion::V2];
+
+        // a peer offering both meets a v2-only peer on v2, and us on v3
+        let proposal = version_proposal(SUPPORTED_VERSIONS);
+        assert_eq!(proposal, "simple-ica-v3,simple-ica-v2");
+        assert_eq!(negotiate_version(v2_only, &proposal), Ok(IcaVersion::V2));
+        assert_eq!(
+            negotiate_version(SUPPORTED_VERSIONS, &proposal),
+            Ok(IcaVersion::V3)
+        );
+
+        // a v2-only peer proposes the plain old version string
+        let proposal = version_proposal(v2_only);
+        assert_eq!(proposal, "simple-ica-v2");
+        assert_eq!(
+            negotiate_version(SUPPORTED_VERSIONS, &proposal),
+            Ok(IcaVersion::V2)
+        );
+
+        // versions from the future are skipped
+        assert_eq!(
+            negotiate_version(SUPPORTED_VERSIONS, "simple-ica-v9, simple-ica-v3"),
+            Ok(IcaVersion::V3)
+        );
+
+        // and there may be nothing in common
+        let err = negotiate_version(v2_only, "simple-ica-v3").unwrap_err();
+        assert_eq!(err, SimpleIcaError::NoCommonVersion("simple-ica-v3".into()));
+        negotiate_version(SUPPORTED_VERSIONS, "ics20-1").unwrap_err();
+    }
+
+    #[test]
+    fn packet_encoding_depends_on_version() {
+        let msg = PacketMsg::WhoAmI {};
+
+        let v2 = IcaVersion::V2.encode_packet(&msg).unwrap();
+        assert_eq!(v2.as_slice(), br#"{"who_am_i":{}}"#);
+        assert_eq!(IcaVersion::V2.decode_packet(&v2).unwrap(), msg);
+
+        let v3 = IcaVersion::V3.encode_packet(&msg).unwrap();
+        assert_eq!(
+            v3.as_slice(),
+            br#"{"version":"v3","packet":{"who_am_i":{}}}"#
+        );
+        assert_eq!(IcaVersion::V3.decode_packet(&v3).unwrap(), msg);
+
+        // a packet in the wrong format is rejected
+        IcaVersion::V3.decode_packet(&v2).unwrap_err();
+        IcaVersion::V2.decode_packet(&v3).unwrap_err();
+        let v2_tagged = br#"{"version":"v2","packet":{"who_am_i":{}}}"#;
+        IcaVersion::V3.decode_packet(v2_tagged).unwrap_err();
+    }
+}
@@ -398,13 +398,14 @@ test.serial("query with callback", async (t) => {
   );
   const callback = new CallbackCapturerClient(wasmClient.sign, wasmClient.senderAddress, callbackAddr);
 
-  // and update the admin, so the callback contract can use the controller
+  // and make it an operator, so the callback contract can use the controller
   await wasmClient.sign.execute(
     wasmClient.senderAddress,
     wasmController,
-    { update_admin: { admin: callbackAddr } },
+    { admin: { propose_admin: { address: callbackAddr, role: "operator" } } },
     "auto"
   );
+  await wasmClient.sign.execute(wasmClient.senderAddress, callbackAddr, { accept_controller_admin: {} }, "auto");
 
   // get the account info
   const accounts = await listAccounts(wasmClient, wasmController);